@@ -0,0 +1,1452 @@
+use proc_macro2::{Span, TokenStream};
+
+use syn::{
+    bracketed, parenthesized,
+    parse::{self, Parse, ParseStream, Parser},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, Expr, Ident, Lit, Meta, NestedMeta, Path, Token, Type,
+};
+
+use crate::Diagnostic;
+
+/// A single argument of the attribute.
+pub(crate) enum Arg {
+    /// A key with a value, e.g. `nested = true`, or a flag, e.g. `nested`.
+    Value(Ident, Value),
+    /// A list of expressions, `fields(expr, ...)` or `span_fields(expr, ...)`.
+    Fields(Ident, Punctuated<Expr, Token![,]>),
+    /// A list of parameters that `args` leaves out, `skip(name, ...)`.
+    Skip(Ident, Punctuated<Ident, Token![,]>),
+    /// Arguments for nested functions and closures, `nested_opts(arg, ...)`.
+    NestedOpts(Ident, Punctuated<Arg, Token![,]>),
+    /// A list of error types, `only = [type, ...]`.
+    Only(Ident, Punctuated<Type, Token![,]>),
+    /// The expression of a `slog` logger, `slog = expr`.
+    Slog(Ident, Expr),
+    /// A configuration predicate, `cfg(predicate)`.
+    Cfg(Ident, Punctuated<NestedMeta, Token![,]>),
+}
+
+/// The value of an argument.
+pub(crate) enum Value {
+    /// A literal, e.g. `true`, `10` or `"stderr"`.
+    Lit(Lit),
+    /// A path, e.g. `my_crate::on_propagation` or `stderr`.
+    Path(Path),
+    /// No value, for a flag like `nested`, which is short for `nested = true`.
+    Flag(Ident),
+}
+
+impl Value {
+    fn span(&self) -> Span {
+        match self {
+            Value::Lit(lit) => lit.span(),
+            Value::Path(path) => path.span(),
+            Value::Flag(ident) => ident.span(),
+        }
+    }
+
+    /// Returns the string literal, or an error if the value is something else.
+    fn str_lit(&self) -> Result<&syn::LitStr, Diagnostic> {
+        match self {
+            Value::Lit(Lit::Str(str_lit)) => Ok(str_lit),
+            _ => Err(Diagnostic::error(self.span(), "Expected string literal")),
+        }
+    }
+}
+
+impl Arg {
+    /// Returns the key of the argument, e.g. `nested` or `fields`.
+    pub fn key(&self) -> &Ident {
+        match self {
+            Arg::Value(ident, _)
+            | Arg::Fields(ident, _)
+            | Arg::Skip(ident, _)
+            | Arg::NestedOpts(ident, _)
+            | Arg::Only(ident, _)
+            | Arg::Slog(ident, _)
+            | Arg::Cfg(ident, _) => ident,
+        }
+    }
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> parse::Result<Arg> {
+        let ident: Ident = input.parse()?;
+
+        if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            return match &*ident.to_string() {
+                "fields" | "span_fields" => {
+                    Ok(Arg::Fields(ident, content.parse_terminated(Expr::parse)?))
+                }
+                "skip" => Ok(Arg::Skip(ident, content.parse_terminated(Ident::parse)?)),
+                "nested_opts" => Ok(Arg::NestedOpts(
+                    ident,
+                    content.parse_terminated(Arg::parse)?,
+                )),
+                "cfg" => Ok(Arg::Cfg(
+                    ident,
+                    content.parse_terminated(NestedMeta::parse)?,
+                )),
+                key if KEYS.contains(&key) => Err(parse::Error::new(
+                    ident.span(),
+                    format!("Expected `{} = ...`", key),
+                )),
+                _ => {
+                    let diag = unknown_argument(&ident);
+                    Err(parse::Error::new(diag.span, diag.message))
+                }
+            };
+        }
+
+        if !input.peek(Token![=]) {
+            return Ok(Arg::Value(ident.clone(), Value::Flag(ident)));
+        }
+        input.parse::<Token![=]>()?;
+
+        if ident == "only" {
+            let content;
+            bracketed!(content in input);
+            return Ok(Arg::Only(ident, content.parse_terminated(Type::parse)?));
+        }
+        if ident == "slog" {
+            return Ok(Arg::Slog(ident, input.parse()?));
+        }
+
+        let value = if input.peek(Lit) {
+            Value::Lit(input.parse()?)
+        } else {
+            Value::Path(input.parse()?)
+        };
+        Ok(Arg::Value(ident, value))
+    }
+}
+
+/// Parses the comma-separated arguments of the attribute.
+pub(crate) fn parse_args(args: TokenStream) -> parse::Result<Punctuated<Arg, Token![,]>> {
+    Punctuated::<Arg, Token![,]>::parse_terminated.parse2(args)
+}
+
+/// The keys of all arguments, for suggestions when a key is misspelled.
+const KEYS: &[&str] = &[
+    "nested",
+    "nested_opts",
+    "combinators",
+    "verbosity",
+    "layout",
+    "pretty",
+    "wasi_logging",
+    "color",
+    "hyperlinks",
+    "on_panic",
+    "paths",
+    "site_ids",
+    "counts",
+    "error_ids",
+    "host_info",
+    "task_ids",
+    "callers",
+    "context",
+    "labels",
+    "prefix",
+    "name",
+    "group",
+    "package",
+    "build_info",
+    "fields",
+    "args",
+    "skip",
+    "debug_repr",
+    "io_details",
+    "formatters",
+    "redact",
+    "type_names",
+    "bound",
+    "elapsed",
+    "delta",
+    "indent",
+    "timing",
+    "cfg",
+    "snippet",
+    "backend",
+    "level",
+    "target",
+    "log_kv",
+    "slog",
+    "span_trace",
+    "span",
+    "span_fields",
+    "aggregate_ms",
+    "summary",
+    "sink",
+    "sink_path",
+    "handler",
+    "format",
+    "no_std",
+    "print_macro",
+    "metrics",
+    "env_switch",
+    "site_lists",
+    "module_filter",
+    "thread_switch",
+    "strip_release",
+    "enabled",
+    "print_expansion",
+    "stats",
+    "rich",
+    "crash_report",
+    "only",
+    "filter",
+    "map",
+    "once",
+    "locate",
+    "eyre",
+    "unwraps",
+    "discards",
+    "ignored",
+    "yeets",
+    "tests",
+    "lints",
+    "rate_limit",
+    "sample",
+    "panic_on_error",
+    "break_on_error",
+    "pause_on_error",
+    "pause_per_site",
+    "max_errors",
+    "max_len",
+    "sanitize",
+    "lock_output",
+    "retry",
+    "retry_delay_ms",
+    "or_default",
+    "ffi",
+    "repropagated",
+    "location",
+];
+
+/// Returns the error for an unknown key, which suggests the closest known key, if there is one
+/// that is close enough to be a typo.
+fn unknown_argument(ident: &Ident) -> Diagnostic {
+    let key = ident.to_string();
+    let closest = KEYS
+        .iter()
+        .map(|known| (edit_distance(&key, known), known))
+        .filter(|&(distance, known)| distance <= 2 && distance < known.len() / 2)
+        .min();
+    match closest {
+        Some((_, known)) => Diagnostic::error(
+            ident.span(),
+            format!("Unknown argument, did you mean `{}`?", known),
+        ),
+        None => Diagnostic::error(ident.span(), "Unknown argument"),
+    }
+}
+
+/// Computes the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct DebugTryArgs {
+    pub nested: Option<bool>,
+    pub combinators: Option<bool>,
+    pub verbosity: Option<Verbosity>,
+    pub layout: Option<Layout>,
+    pub pretty: Option<bool>,
+    pub wasi_logging: Option<Path>,
+    pub color: Option<Color>,
+    pub hyperlinks: Option<Hyperlinks>,
+    pub on_panic: Option<OnPanic>,
+    pub paths: Option<Paths>,
+    pub site_ids: Option<bool>,
+    pub counts: Option<bool>,
+    pub error_ids: Option<bool>,
+    pub host_info: Option<bool>,
+    pub task_ids: Option<bool>,
+    pub callers: Option<bool>,
+    pub context: Option<bool>,
+    pub labels: Option<Labels>,
+    pub prefix: Option<String>,
+    pub name: Option<String>,
+    pub group: Option<String>,
+    pub package: Option<bool>,
+    pub build_info: Option<BuildInfo>,
+    pub fields: Option<Vec<Expr>>,
+    pub fn_args: Option<bool>,
+    pub skip: Option<Vec<Ident>>,
+    pub debug_repr: Option<bool>,
+    pub io_details: Option<bool>,
+    pub formatters: Option<bool>,
+    pub redact: Option<bool>,
+    pub type_names: Option<bool>,
+    pub bound: Option<Bound>,
+    pub elapsed: Option<bool>,
+    pub delta: Option<bool>,
+    pub indent: Option<bool>,
+    pub timing: Option<bool>,
+    pub cfg: Option<Meta>,
+    pub snippet: Option<bool>,
+    pub nested_opts: Option<Box<DebugTryArgs>>,
+    pub backend: Option<Backend>,
+    pub level: Option<Level>,
+    pub target: Option<String>,
+    pub log_kv: Option<bool>,
+    pub slog: Option<Expr>,
+    pub span_trace: Option<bool>,
+    pub span: Option<bool>,
+    pub span_fields: Option<Vec<Expr>>,
+    pub aggregate_ms: Option<u64>,
+    pub summary: Option<Summary>,
+    pub sink: Option<Sink>,
+    pub sink_path: Option<String>,
+    pub handler: Option<Path>,
+    pub format: Option<Format>,
+    pub no_std: Option<bool>,
+    pub print_macro: Option<Path>,
+    pub metrics: Option<bool>,
+    pub env_switch: Option<String>,
+    pub site_lists: Option<bool>,
+    pub module_filter: Option<bool>,
+    pub thread_switch: Option<bool>,
+    pub strip_release: Option<bool>,
+    pub enabled: Option<bool>,
+    pub print_expansion: Option<bool>,
+    pub stats: Option<bool>,
+    pub rich: Option<bool>,
+    pub crash_report: Option<bool>,
+    pub only: Option<Vec<Type>>,
+    pub filter: Option<Path>,
+    pub rate_limit: Option<(u32, u64)>,
+    pub sample: Option<usize>,
+    pub panic_on_error: Option<bool>,
+    pub break_on_error: Option<bool>,
+    pub pause_on_error: Option<Pause>,
+    pub pause_per_site: Option<bool>,
+    pub once: Option<bool>,
+    pub max_errors: Option<usize>,
+    pub max_len: Option<usize>,
+    pub sanitize: Option<bool>,
+    pub lock_output: Option<bool>,
+    pub retry: Option<u32>,
+    pub retry_delay_ms: Option<u64>,
+    pub or_default: Option<bool>,
+    pub ffi: Option<Ffi>,
+    pub repropagated: Option<Repropagated>,
+    pub locate: Option<bool>,
+    pub eyre: Option<bool>,
+    pub unwraps: Option<bool>,
+    pub discards: Option<bool>,
+    pub ignored: Option<bool>,
+    pub yeets: Option<bool>,
+    pub tests: Option<bool>,
+    pub lints: Option<bool>,
+    pub map: Option<Path>,
+    pub location: Option<Location>,
+    /// Whether the sites record their location for `debug_try::main`, which is not an argument.
+    pub record_site: Option<bool>,
+    /// The parameters of the function that are captured with `args`, which is not an argument.
+    pub params: Option<Vec<Expr>>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Backend {
+    Stderr,
+    Log,
+    Tracing,
+    Defmt,
+    Sentry,
+    OpenTelemetry,
+    Usdt,
+    Slog,
+    Wasm,
+    None,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verbosity {
+    Compact,
+    Normal,
+    Full,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Layout {
+    Line,
+    Multiline,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Format {
+    Text,
+    Json,
+    Logfmt,
+    Rustc,
+    /// Text when standard error is a terminal, and JSON otherwise.
+    Auto,
+}
+
+/// What an `extern` function with `ffi` does when its body returns an error.
+#[derive(Clone)]
+pub(crate) enum Ffi {
+    Abort,
+    /// A function that maps the error to the value that is returned to the caller.
+    Return(Path),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bound {
+    Display,
+    None,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Repropagated {
+    Report,
+    Compact,
+    Quiet,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Location {
+    Span,
+    Runtime,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Sink {
+    Stderr,
+    Stdout,
+    File,
+    Capture,
+    Logcat,
+    OsLog,
+    Journald,
+    Etw,
+    ChromeTrace,
+}
+
+/// When the summaries of `summary` are emitted.
+#[derive(Clone, Copy)]
+pub(crate) enum Summary {
+    /// After this many errors.
+    Events(u64),
+    /// For the first error at least this many milliseconds after the previous summary.
+    Period(u64),
+}
+
+/// How a site waits with `pause_on_error`.
+#[derive(Clone, Copy)]
+pub(crate) enum Pause {
+    /// Sleeps for this many seconds.
+    Seconds(u64),
+    /// Waits for a line on standard input.
+    Enter,
+    /// Stops the process with `SIGSTOP`, until it gets `SIGCONT`.
+    Signal,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Labels {
+    Comments,
+    None,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Paths {
+    Relative,
+    Absolute,
+    Crate,
+    Remapped,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum OnPanic {
+    Emergency,
+    Skip,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Color {
+    Always,
+    Auto,
+    Never,
+}
+
+#[derive(Clone)]
+pub(crate) enum Hyperlinks {
+    Off,
+    /// A URL with `{path}`, `{line}` and `{column}` placeholders.
+    Url(String),
+}
+
+#[derive(Clone)]
+pub(crate) enum BuildInfo {
+    Off,
+    /// The first of the variables that tools like `vergen` set, see [`BUILD_VARS`].
+    Default,
+    /// The name of the variable that identifies the build.
+    Var(String),
+}
+
+/// The variables of the environment at compile time that identify the build with
+/// `build_info = true`, of which the first one that is set is used.
+pub(crate) const BUILD_VARS: &[&str] = &["GIT_HASH", "VERGEN_GIT_SHA", "VERGEN_GIT_DESCRIBE"];
+
+impl DebugTryArgs {
+    /// Returns the arguments that apply to inner functions of the annotated function.
+    pub fn inner_fn_args(&self) -> DebugTryArgs {
+        DebugTryArgs {
+            // the fields and the logger refer to variables of the outer function
+            fields: None,
+            slog: None,
+            span_fields: None,
+            params: None,
+            // errors of inner functions may be handled by the outer function
+            record_site: None,
+            ffi: None,
+            ..self.clone()
+        }
+        .with_nested_opts()
+    }
+
+    /// Returns the arguments that apply to closures in the annotated function.
+    pub fn closure_args(&self) -> DebugTryArgs {
+        DebugTryArgs {
+            // a `move` closure would take the parameters from the function
+            params: None,
+            ..self.clone()
+        }
+        .with_nested_opts()
+    }
+
+    /// Returns whether the instrumentation is turned off, with `enabled = false` or the `disabled`
+    /// feature, in which case the function is left as it is.
+    pub fn disabled(&self) -> bool {
+        cfg!(feature = "disabled") || self.enabled == Some(false)
+    }
+
+    /// Returns the backend that the messages are emitted with, which is `slog` if a logger is
+    /// given with `slog = ...`, regardless of `backend`.
+    pub fn backend(&self) -> Backend {
+        match self.slog {
+            Some(_) => Backend::Slog,
+            None => self.backend.unwrap_or(Backend::Stderr),
+        }
+    }
+
+    /// Returns the preset that the messages are based on, `"normal"` by default.
+    pub fn verbosity(&self) -> Verbosity {
+        self.verbosity.unwrap_or(Verbosity::Normal)
+    }
+
+    /// Returns whether text messages put each part on a line of its own, with
+    /// `layout = "multiline"`. Messages like those of rustc keep the error on the line before the
+    /// location, which is what tools look for.
+    pub fn multiline(&self) -> bool {
+        self.layout == Some(Layout::Multiline) && !matches!(self.format, Some(Format::Rustc))
+    }
+
+    /// Returns whether the control characters of the errors in messages are escaped, which they
+    /// are unless `sanitize = false` is given.
+    pub fn sanitize(&self) -> bool {
+        self.sanitize.unwrap_or(true)
+    }
+
+    /// Returns the fields of each message: those given with `fields(...)`, followed by the
+    /// parameters captured with `args`.
+    pub fn all_fields(&self) -> Vec<&Expr> {
+        self.fields
+            .iter()
+            .flatten()
+            .chain(self.params.iter().flatten())
+            .collect()
+    }
+
+    /// Overrides the arguments with those given in `nested_opts(...)`.
+    fn with_nested_opts(self) -> DebugTryArgs {
+        let overrides = match self.nested_opts {
+            Some(ref overrides) => (**overrides).clone(),
+            None => return self,
+        };
+
+        // destructure, so that new arguments can not be forgotten here
+        let DebugTryArgs {
+            nested,
+            combinators,
+            verbosity,
+            layout,
+            pretty,
+            wasi_logging,
+            color,
+            hyperlinks,
+            on_panic,
+            paths,
+            site_ids,
+            counts,
+            error_ids,
+            host_info,
+            task_ids,
+            callers,
+            context,
+            labels,
+            prefix,
+            name,
+            group,
+            package,
+            build_info,
+            fields,
+            fn_args,
+            skip,
+            debug_repr,
+            io_details,
+            formatters,
+            redact,
+            type_names,
+            bound,
+            elapsed,
+            delta,
+            indent,
+            timing,
+            cfg,
+            snippet,
+            nested_opts: _,
+            backend,
+            level,
+            target,
+            log_kv,
+            slog,
+            span_trace,
+            span,
+            span_fields,
+            aggregate_ms,
+            summary,
+            sink,
+            sink_path,
+            handler,
+            format,
+            no_std,
+            print_macro,
+            metrics,
+            env_switch,
+            site_lists,
+            module_filter,
+            thread_switch,
+            strip_release,
+            enabled,
+            print_expansion,
+            stats,
+            rich,
+            crash_report,
+            only,
+            filter,
+            rate_limit,
+            sample,
+            panic_on_error,
+            break_on_error,
+            pause_on_error,
+            pause_per_site,
+            once,
+            max_errors,
+            max_len,
+            sanitize,
+            lock_output,
+            retry,
+            retry_delay_ms,
+            or_default,
+            ffi,
+            repropagated,
+            locate,
+            eyre,
+            unwraps,
+            discards,
+            ignored,
+            yeets,
+            tests,
+            lints,
+            map,
+            location,
+            record_site,
+            params,
+        } = overrides;
+
+        DebugTryArgs {
+            nested: nested.or(self.nested),
+            combinators: combinators.or(self.combinators),
+            verbosity: verbosity.or(self.verbosity),
+            layout: layout.or(self.layout),
+            pretty: pretty.or(self.pretty),
+            wasi_logging: wasi_logging.or(self.wasi_logging),
+            color: color.or(self.color),
+            hyperlinks: hyperlinks.or(self.hyperlinks),
+            on_panic: on_panic.or(self.on_panic),
+            paths: paths.or(self.paths),
+            site_ids: site_ids.or(self.site_ids),
+            counts: counts.or(self.counts),
+            error_ids: error_ids.or(self.error_ids),
+            host_info: host_info.or(self.host_info),
+            task_ids: task_ids.or(self.task_ids),
+            callers: callers.or(self.callers),
+            context: context.or(self.context),
+            labels: labels.or(self.labels),
+            prefix: prefix.or(self.prefix),
+            name: name.or(self.name),
+            group: group.or(self.group),
+            package: package.or(self.package),
+            build_info: build_info.or(self.build_info),
+            fields: fields.or(self.fields),
+            fn_args: fn_args.or(self.fn_args),
+            skip: skip.or(self.skip),
+            debug_repr: debug_repr.or(self.debug_repr),
+            io_details: io_details.or(self.io_details),
+            formatters: formatters.or(self.formatters),
+            redact: redact.or(self.redact),
+            type_names: type_names.or(self.type_names),
+            bound: bound.or(self.bound),
+            elapsed: elapsed.or(self.elapsed),
+            delta: delta.or(self.delta),
+            indent: indent.or(self.indent),
+            timing: timing.or(self.timing),
+            cfg: cfg.or(self.cfg),
+            snippet: snippet.or(self.snippet),
+            backend: backend.or(self.backend),
+            level: level.or(self.level),
+            target: target.or(self.target),
+            log_kv: log_kv.or(self.log_kv),
+            slog: slog.or(self.slog),
+            span_trace: span_trace.or(self.span_trace),
+            span: span.or(self.span),
+            span_fields: span_fields.or(self.span_fields),
+            aggregate_ms: aggregate_ms.or(self.aggregate_ms),
+            summary: summary.or(self.summary),
+            sink: sink.or(self.sink),
+            sink_path: sink_path.or(self.sink_path),
+            handler: handler.or(self.handler),
+            format: format.or(self.format),
+            no_std: no_std.or(self.no_std),
+            print_macro: print_macro.or(self.print_macro),
+            metrics: metrics.or(self.metrics),
+            env_switch: env_switch.or(self.env_switch),
+            site_lists: site_lists.or(self.site_lists),
+            module_filter: module_filter.or(self.module_filter),
+            thread_switch: thread_switch.or(self.thread_switch),
+            strip_release: strip_release.or(self.strip_release),
+            enabled: enabled.or(self.enabled),
+            print_expansion: print_expansion.or(self.print_expansion),
+            stats: stats.or(self.stats),
+            rich: rich.or(self.rich),
+            crash_report: crash_report.or(self.crash_report),
+            only: only.or(self.only),
+            filter: filter.or(self.filter),
+            rate_limit: rate_limit.or(self.rate_limit),
+            sample: sample.or(self.sample),
+            panic_on_error: panic_on_error.or(self.panic_on_error),
+            break_on_error: break_on_error.or(self.break_on_error),
+            pause_on_error: pause_on_error.or(self.pause_on_error),
+            pause_per_site: pause_per_site.or(self.pause_per_site),
+            once: once.or(self.once),
+            max_errors: max_errors.or(self.max_errors),
+            max_len: max_len.or(self.max_len),
+            sanitize: sanitize.or(self.sanitize),
+            lock_output: lock_output.or(self.lock_output),
+            retry: retry.or(self.retry),
+            retry_delay_ms: retry_delay_ms.or(self.retry_delay_ms),
+            or_default: or_default.or(self.or_default),
+            ffi: ffi.or(self.ffi),
+            repropagated: repropagated.or(self.repropagated),
+            locate: locate.or(self.locate),
+            eyre: eyre.or(self.eyre),
+            unwraps: unwraps.or(self.unwraps),
+            discards: discards.or(self.discards),
+            ignored: ignored.or(self.ignored),
+            yeets: yeets.or(self.yeets),
+            tests: tests.or(self.tests),
+            lints: lints.or(self.lints),
+            map: map.or(self.map),
+            location: location.or(self.location),
+            record_site: record_site.or(self.record_site),
+            params: params.or(self.params),
+            // the same overrides apply at every level of nesting
+            nested_opts: self.nested_opts,
+        }
+    }
+
+    pub fn try_from<I: IntoIterator<Item = Arg>>(args: I) -> Result<DebugTryArgs, Diagnostic> {
+        let mut result: DebugTryArgs = Default::default();
+        // the spans of the keys, for errors about combinations of arguments
+        let mut spans = Vec::new();
+
+        for arg in args {
+            match arg {
+                Arg::Fields(ident, exprs) => {
+                    let fields = match ident == "span_fields" {
+                        true => &mut result.span_fields,
+                        false => &mut result.fields,
+                    };
+                    if fields.is_some() {
+                        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+                    }
+                    *fields = Some(exprs.into_iter().collect());
+                }
+                Arg::Skip(ident, names) => {
+                    if result.skip.is_some() {
+                        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+                    }
+                    result.skip = Some(names.into_iter().collect());
+                }
+                Arg::NestedOpts(ident, args) => {
+                    if result.nested_opts.is_some() {
+                        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+                    }
+                    result.nested_opts = Some(Box::new(DebugTryArgs::try_from(args)?));
+                }
+                Arg::Only(ident, types) => {
+                    if result.only.is_some() {
+                        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+                    }
+                    spans.push(("only".to_owned(), ident.span()));
+                    result.only = Some(types.into_iter().collect());
+                }
+                Arg::Slog(ident, logger) => {
+                    if result.slog.is_some() {
+                        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+                    }
+                    if !cfg!(feature = "slog") {
+                        return Err(Diagnostic::error(
+                            ident.span(),
+                            "`slog` requires the `slog` feature of `debug-try`",
+                        ));
+                    }
+                    spans.push(("slog".to_owned(), ident.span()));
+                    result.slog = Some(logger);
+                }
+                Arg::Value(ref ident, ref value) => {
+                    let key: &str = &ident.to_string();
+                    spans.push((key.to_owned(), ident.span()));
+                    let kv = (ident, value);
+
+                    match key {
+                        "nested" => set_once(&mut result.nested, kv, parse_bool)?,
+                        "combinators" => set_once(&mut result.combinators, kv, parse_bool)?,
+                        "verbosity" => set_once(&mut result.verbosity, kv, parse_verbosity)?,
+                        "layout" => set_once(&mut result.layout, kv, parse_layout)?,
+                        "pretty" => set_once(&mut result.pretty, kv, parse_bool)?,
+                        "wasi_logging" => set_once(&mut result.wasi_logging, kv, parse_path)?,
+                        "color" => set_once(&mut result.color, kv, parse_color)?,
+                        "hyperlinks" => set_once(&mut result.hyperlinks, kv, parse_hyperlinks)?,
+                        "on_panic" => set_once(&mut result.on_panic, kv, parse_on_panic)?,
+                        "paths" => set_once(&mut result.paths, kv, parse_paths)?,
+                        "site_ids" => set_once(&mut result.site_ids, kv, parse_bool)?,
+                        "counts" => set_once(&mut result.counts, kv, parse_bool)?,
+                        "error_ids" => set_once(&mut result.error_ids, kv, parse_bool)?,
+                        "host_info" => set_once(&mut result.host_info, kv, parse_bool)?,
+                        "task_ids" => {
+                            set_once(&mut result.task_ids, kv, parse_bool)?;
+                            if result.task_ids == Some(true) && !cfg!(feature = "tokio") {
+                                return Err(Diagnostic::error(
+                                    ident.span(),
+                                    "`task_ids` requires the `tokio` feature of `debug-try`",
+                                ));
+                            }
+                        }
+                        "callers" => set_once(&mut result.callers, kv, parse_bool)?,
+                        "context" => set_once(&mut result.context, kv, parse_bool)?,
+                        "labels" => set_once(&mut result.labels, kv, parse_labels)?,
+                        "prefix" => set_once(&mut result.prefix, kv, parse_str)?,
+                        "name" => set_once(&mut result.name, kv, parse_str)?,
+                        "group" => set_once(&mut result.group, kv, parse_str)?,
+                        "package" => set_once(&mut result.package, kv, parse_bool)?,
+                        "build_info" => set_once(&mut result.build_info, kv, parse_build_info)?,
+                        "debug_repr" => set_once(&mut result.debug_repr, kv, parse_bool)?,
+                        "io_details" => set_once(&mut result.io_details, kv, parse_bool)?,
+                        "formatters" => set_once(&mut result.formatters, kv, parse_bool)?,
+                        "redact" => set_once(&mut result.redact, kv, parse_bool)?,
+                        "type_names" => set_once(&mut result.type_names, kv, parse_bool)?,
+                        "bound" => set_once(&mut result.bound, kv, parse_bound)?,
+                        "elapsed" => set_once(&mut result.elapsed, kv, parse_bool)?,
+                        "delta" => set_once(&mut result.delta, kv, parse_bool)?,
+                        "indent" => set_once(&mut result.indent, kv, parse_bool)?,
+                        "timing" => set_once(&mut result.timing, kv, parse_bool)?,
+                        "cfg" => set_once(&mut result.cfg, kv, parse_cfg)?,
+                        "snippet" => set_once(&mut result.snippet, kv, parse_bool)?,
+                        "backend" => set_once(&mut result.backend, kv, parse_backend)?,
+                        "level" => set_once(&mut result.level, kv, parse_level)?,
+                        "target" => set_once(&mut result.target, kv, parse_str)?,
+                        "log_kv" => set_once(&mut result.log_kv, kv, parse_bool)?,
+                        "span_trace" => set_once(&mut result.span_trace, kv, parse_bool)?,
+                        "span" => set_once(&mut result.span, kv, parse_bool)?,
+                        "aggregate_ms" => set_once(&mut result.aggregate_ms, kv, parse_int)?,
+                        "summary" => set_once(&mut result.summary, kv, parse_summary)?,
+                        "sink" => set_once(&mut result.sink, kv, parse_sink)?,
+                        "sink_path" => set_once(&mut result.sink_path, kv, parse_str)?,
+                        "handler" => set_once(&mut result.handler, kv, parse_path)?,
+                        "format" => set_once(&mut result.format, kv, parse_format)?,
+                        "no_std" => set_once(&mut result.no_std, kv, parse_bool)?,
+                        "print_macro" => set_once(&mut result.print_macro, kv, parse_path)?,
+                        "metrics" => set_once(&mut result.metrics, kv, parse_bool)?,
+                        "env_switch" => set_once(&mut result.env_switch, kv, parse_str)?,
+                        "site_lists" => set_once(&mut result.site_lists, kv, parse_bool)?,
+                        "module_filter" => set_once(&mut result.module_filter, kv, parse_bool)?,
+                        "thread_switch" => set_once(&mut result.thread_switch, kv, parse_bool)?,
+                        "strip_release" => set_once(&mut result.strip_release, kv, parse_bool)?,
+                        "enabled" => set_once(&mut result.enabled, kv, parse_bool)?,
+                        "print_expansion" => set_once(&mut result.print_expansion, kv, parse_bool)?,
+                        "stats" => set_once(&mut result.stats, kv, parse_bool)?,
+                        "rich" => set_once(&mut result.rich, kv, parse_bool)?,
+                        "crash_report" => set_once(&mut result.crash_report, kv, parse_bool)?,
+                        "filter" => set_once(&mut result.filter, kv, parse_path)?,
+                        "map" => set_once(&mut result.map, kv, parse_path)?,
+                        "once" => set_once(&mut result.once, kv, parse_bool)?,
+                        "locate" => set_once(&mut result.locate, kv, parse_bool)?,
+                        "eyre" => set_once(&mut result.eyre, kv, parse_bool)?,
+                        "unwraps" => set_once(&mut result.unwraps, kv, parse_bool)?,
+                        "discards" => set_once(&mut result.discards, kv, parse_bool)?,
+                        "ignored" => set_once(&mut result.ignored, kv, parse_bool)?,
+                        "yeets" => set_once(&mut result.yeets, kv, parse_bool)?,
+                        "tests" => set_once(&mut result.tests, kv, parse_bool)?,
+                        "lints" => set_once(&mut result.lints, kv, parse_bool)?,
+                        "rate_limit" => set_once(&mut result.rate_limit, kv, parse_rate_limit)?,
+                        "sample" => set_once(&mut result.sample, kv, parse_sample)?,
+                        "panic_on_error" => set_once(&mut result.panic_on_error, kv, parse_bool)?,
+                        "break_on_error" => set_once(&mut result.break_on_error, kv, parse_bool)?,
+                        "pause_on_error" => set_once(&mut result.pause_on_error, kv, parse_pause)?,
+                        "pause_per_site" => set_once(&mut result.pause_per_site, kv, parse_bool)?,
+                        "max_errors" => set_once(&mut result.max_errors, kv, parse_int)?,
+                        "max_len" => set_once(&mut result.max_len, kv, parse_int)?,
+                        "sanitize" => set_once(&mut result.sanitize, kv, parse_bool)?,
+                        "lock_output" => set_once(&mut result.lock_output, kv, parse_bool)?,
+                        "retry" => set_once(&mut result.retry, kv, parse_int)?,
+                        "retry_delay_ms" => set_once(&mut result.retry_delay_ms, kv, parse_int)?,
+                        "or_default" => set_once(&mut result.or_default, kv, parse_bool)?,
+                        "ffi" => set_once(&mut result.ffi, kv, parse_ffi)?,
+                        "repropagated" => {
+                            set_once(&mut result.repropagated, kv, parse_repropagated)?
+                        }
+                        "location" => set_once(&mut result.location, kv, parse_location)?,
+                        "args" => set_once(&mut result.fn_args, kv, parse_bool)?,
+                        "fields" | "span_fields" | "nested_opts" | "skip" => {
+                            return Err(Diagnostic::error(
+                                ident.span(),
+                                format!("Expected `{}(...)`", key),
+                            ))
+                        }
+                        _ => return Err(unknown_argument(ident)),
+                    }
+                }
+                Arg::Cfg(ident, predicates) => {
+                    if result.cfg.is_some() {
+                        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+                    }
+                    let span = predicates.span();
+                    let mut predicates = predicates.into_iter();
+                    match (predicates.next(), predicates.next()) {
+                        (Some(NestedMeta::Meta(predicate)), None) => result.cfg = Some(predicate),
+                        _ => {
+                            return Err(Diagnostic::error(
+                                span,
+                                "Expected a single configuration predicate",
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        // the arguments given explicitly take precedence over the preset
+        if result.verbosity == Some(Verbosity::Full) {
+            result.fn_args = result.fn_args.or(Some(true));
+        }
+
+        if result.no_std.unwrap_or(false) {
+            result.check_no_std(&spans)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Checks that the arguments do not need `std`.
+    fn check_no_std(&self, spans: &[(String, Span)]) -> Result<(), Diagnostic> {
+        let span = |key: &str| {
+            spans
+                .iter()
+                .find(|(name, _)| name == key)
+                .map_or_else(Span::call_site, |&(_, span)| span)
+        };
+
+        let needs_std = [
+            ("verbosity", self.verbosity == Some(Verbosity::Full)),
+            ("layout", self.layout == Some(Layout::Multiline)),
+            ("elapsed", self.elapsed == Some(true)),
+            ("delta", self.delta == Some(true)),
+            ("indent", self.indent == Some(true)),
+            ("timing", self.timing == Some(true)),
+            ("aggregate_ms", self.aggregate_ms.is_some()),
+            ("summary", self.summary.is_some()),
+            ("retry_delay_ms", self.retry_delay_ms.is_some()),
+            ("ffi", self.ffi.is_some()),
+            ("lock_output", self.lock_output == Some(true)),
+            ("format", matches!(self.format, Some(Format::Auto))),
+            ("sink", self.sink.is_some()),
+            ("sink_path", self.sink_path.is_some()),
+            ("wasi_logging", self.wasi_logging.is_some()),
+            ("metrics", self.metrics == Some(true)),
+            ("env_switch", self.env_switch.is_some()),
+            ("site_lists", self.site_lists == Some(true)),
+            ("module_filter", self.module_filter == Some(true)),
+            ("thread_switch", self.thread_switch == Some(true)),
+            ("only", self.only.is_some()),
+            ("slog", self.slog.is_some()),
+            ("rate_limit", self.rate_limit.is_some()),
+            ("repropagated", self.repropagated.is_some()),
+            ("error_ids", self.error_ids == Some(true)),
+            ("host_info", self.host_info == Some(true)),
+            ("task_ids", self.task_ids == Some(true)),
+            ("callers", self.callers == Some(true)),
+            ("context", self.context == Some(true)),
+            ("crash_report", self.crash_report == Some(true)),
+            ("pause_on_error", self.pause_on_error.is_some()),
+            ("io_details", self.io_details == Some(true)),
+            ("formatters", self.formatters == Some(true)),
+            ("eyre", self.eyre == Some(true)),
+            (
+                "backend",
+                matches!(
+                    self.backend,
+                    Some(Backend::Tracing)
+                        | Some(Backend::Sentry)
+                        | Some(Backend::OpenTelemetry)
+                        | Some(Backend::Usdt)
+                        | Some(Backend::Wasm)
+                ),
+            ),
+        ];
+        if let Some(&(key, _)) = needs_std.iter().find(|(_, needs_std)| *needs_std) {
+            return Err(Diagnostic::error(
+                span(key),
+                format!("`{}` can not be used with `no_std`", key),
+            ));
+        }
+
+        let prints = match self.backend() {
+            Backend::Stderr => self.print_macro.is_some(),
+            _ => true,
+        };
+        if !prints && self.handler.is_none() && self.panic_on_error != Some(true) {
+            return Err(Diagnostic::error(
+                span("no_std"),
+                "`no_std` requires `print_macro`, `handler` or a backend other than \"stderr\"",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn set_once<T>(
+    field: &mut Option<T>,
+    (ident, value): (&Ident, &Value),
+    parse: fn(&Value) -> Result<T, Diagnostic>,
+) -> Result<(), Diagnostic> {
+    if field.is_some() {
+        return Err(Diagnostic::error(ident.span(), "Duplicate argument"));
+    }
+
+    *field = Some(parse(value)?);
+    Ok(())
+}
+
+fn parse_bool(value: &Value) -> Result<bool, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Bool(bool_lit)) => Ok(bool_lit.value),
+        Value::Flag(_) => Ok(true),
+        _ => Err(Diagnostic::error(value.span(), "Expected boolean literal")),
+    }
+}
+
+fn parse_int<T>(value: &Value) -> Result<T, Diagnostic>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match value {
+        Value::Lit(Lit::Int(int_lit)) => int_lit
+            .value()
+            .to_string()
+            .parse()
+            .map_err(|err| Diagnostic::error(value.span(), format!("Invalid integer: {}", err))),
+        _ => Err(Diagnostic::error(value.span(), "Expected integer literal")),
+    }
+}
+
+fn parse_str(value: &Value) -> Result<String, Diagnostic> {
+    value.str_lit().map(syn::LitStr::value)
+}
+
+fn parse_path(value: &Value) -> Result<Path, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Str(str_lit)) => str_lit.parse().map_err(Diagnostic::from),
+        Value::Path(path) => Ok(path.clone()),
+        _ => Err(Diagnostic::error(
+            value.span(),
+            "Expected path or string literal",
+        )),
+    }
+}
+
+fn parse_cfg(value: &Value) -> Result<Meta, Diagnostic> {
+    value.str_lit()?.parse().map_err(Diagnostic::from)
+}
+
+/// Parses a rate limit like `"10/s"` or `"1/500ms"` into a count and a period in milliseconds.
+fn parse_rate_limit(value: &Value) -> Result<(u32, u64), Diagnostic> {
+    let str_lit = value.str_lit()?;
+
+    let value = str_lit.value();
+    let parse = || {
+        let (count, period) = value.split_once('/')?;
+        match (count.parse().ok()?, parse_period(period)?) {
+            (count, period_ms) if count > 0 => Some((count, period_ms)),
+            _ => None,
+        }
+    };
+
+    parse().ok_or_else(|| {
+        Diagnostic::error(
+            str_lit.span(),
+            "Expected a rate limit like \"10/s\" or \"1/500ms\"",
+        )
+    })
+}
+
+/// Parses a period like `"s"`, `"10s"` or `"500ms"` into a number of milliseconds, which is not
+/// zero.
+fn parse_period(period: &str) -> Option<u64> {
+    let unit_start = period.find(|ch: char| !ch.is_ascii_digit())?;
+    let (amount, unit) = period.split_at(unit_start);
+    let amount: u64 = if amount.is_empty() {
+        1
+    } else {
+        amount.parse().ok()?
+    };
+    let unit_ms = match unit {
+        "ms" => 1,
+        "s" => 1000,
+        "min" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        _ => return None,
+    };
+    match amount.checked_mul(unit_ms)? {
+        0 => None,
+        period_ms => Some(period_ms),
+    }
+}
+
+/// Parses a summary, either as a number of errors, e.g. `1000`, or as a period, e.g. `"10s"`.
+fn parse_summary(value: &Value) -> Result<Summary, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Int(_)) => match parse_int(value)? {
+            0 => Err(Diagnostic::error(
+                value.span(),
+                "Expected a positive integer",
+            )),
+            n => Ok(Summary::Events(n)),
+        },
+        Value::Lit(Lit::Str(str_lit)) => parse_period(&str_lit.value())
+            .map(Summary::Period)
+            .ok_or_else(|| {
+                Diagnostic::error(
+                    str_lit.span(),
+                    "Expected a period like \"10s\" or \"500ms\"",
+                )
+            }),
+        _ => Err(Diagnostic::error(
+            value.span(),
+            "Expected a number of errors or a period like \"10s\"",
+        )),
+    }
+}
+
+/// Parses a pause, either as a number of seconds, e.g. `30`, or as `"enter"` or `"signal"`.
+fn parse_pause(value: &Value) -> Result<Pause, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Int(_)) => parse_int(value).map(Pause::Seconds),
+        _ => parse_choice(value, &[("enter", Pause::Enter), ("signal", Pause::Signal)]),
+    }
+}
+
+/// Parses the `N` of `sample = N`, which must not be zero.
+fn parse_sample(value: &Value) -> Result<usize, Diagnostic> {
+    match parse_int(value)? {
+        0 => Err(Diagnostic::error(
+            value.span(),
+            "Expected a positive integer",
+        )),
+        n => Ok(n),
+    }
+}
+
+/// Parses one of the given choices, either as a string literal, e.g. `"never"`, or as an
+/// identifier, e.g. `never`.
+fn parse_choice<T: Copy>(value: &Value, choices: &[(&str, T)]) -> Result<T, Diagnostic> {
+    let name = match value {
+        Value::Lit(Lit::Str(str_lit)) => str_lit.value(),
+        Value::Path(path) if path.leading_colon.is_none() && path.segments.len() == 1 => {
+            path.segments[0].ident.to_string()
+        }
+        _ => return Err(Diagnostic::error(value.span(), "Expected string literal")),
+    };
+
+    match choices.iter().find(|(choice, _)| *choice == name) {
+        Some(&(_, choice)) => Ok(choice),
+        None => {
+            let names: Vec<String> = choices
+                .iter()
+                .map(|(name, _)| format!("\"{}\"", name))
+                .collect();
+            let (last, rest) = names.split_last().expect("no choices");
+            let message = if rest.is_empty() {
+                format!("Expected {}", last)
+            } else {
+                format!("Expected one of {} or {}", rest.join(", "), last)
+            };
+            Err(Diagnostic::error(value.span(), message))
+        }
+    }
+}
+
+fn parse_color(value: &Value) -> Result<Color, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("always", Color::Always),
+            ("auto", Color::Auto),
+            ("never", Color::Never),
+        ],
+    )
+}
+
+/// Parses `true`, for links to `file://` URLs, `false`, or a URL with a `{path}` placeholder.
+fn parse_hyperlinks(value: &Value) -> Result<Hyperlinks, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Str(str_lit)) => {
+            let url = str_lit.value();
+            if url.contains("{path}") {
+                Ok(Hyperlinks::Url(url))
+            } else {
+                Err(Diagnostic::error(
+                    str_lit.span(),
+                    "Expected a URL with a `{path}` placeholder",
+                ))
+            }
+        }
+        _ => match parse_bool(value)? {
+            true => Ok(Hyperlinks::Url("file://{path}".to_owned())),
+            false => Ok(Hyperlinks::Off),
+        },
+    }
+}
+
+fn parse_build_info(value: &Value) -> Result<BuildInfo, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Str(str_lit)) => match str_lit.value() {
+            ref name if name.is_empty() => Err(Diagnostic::error(
+                str_lit.span(),
+                "Expected the name of an environment variable",
+            )),
+            name => Ok(BuildInfo::Var(name)),
+        },
+        _ => match parse_bool(value)? {
+            true => Ok(BuildInfo::Default),
+            false => Ok(BuildInfo::Off),
+        },
+    }
+}
+
+fn parse_on_panic(value: &Value) -> Result<OnPanic, Diagnostic> {
+    parse_choice(
+        value,
+        &[("emergency", OnPanic::Emergency), ("skip", OnPanic::Skip)],
+    )
+}
+
+fn parse_paths(value: &Value) -> Result<Paths, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("relative", Paths::Relative),
+            ("absolute", Paths::Absolute),
+            ("crate", Paths::Crate),
+            ("remapped", Paths::Remapped),
+        ],
+    )
+}
+
+fn parse_labels(value: &Value) -> Result<Labels, Diagnostic> {
+    parse_choice(
+        value,
+        &[("comments", Labels::Comments), ("none", Labels::None)],
+    )
+}
+
+fn parse_backend(value: &Value) -> Result<Backend, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("stderr", Backend::Stderr),
+            ("log", Backend::Log),
+            ("tracing", Backend::Tracing),
+            ("defmt", Backend::Defmt),
+            ("sentry", Backend::Sentry),
+            ("opentelemetry", Backend::OpenTelemetry),
+            ("usdt", Backend::Usdt),
+            ("wasm", Backend::Wasm),
+            ("none", Backend::None),
+        ],
+    )
+    .and_then(|backend| {
+        let feature = match backend {
+            Backend::Defmt if !cfg!(feature = "defmt") => "defmt",
+            Backend::Sentry if !cfg!(feature = "sentry") => "sentry",
+            Backend::OpenTelemetry if !cfg!(feature = "opentelemetry") => "opentelemetry",
+            Backend::Usdt if !cfg!(feature = "usdt") => "usdt",
+            Backend::Wasm if !cfg!(feature = "wasm") => "wasm",
+            backend => return Ok(backend),
+        };
+        Err(Diagnostic::error(
+            value.span(),
+            format!(
+                "The \"{0}\" backend requires the `{0}` feature of `debug-try`",
+                feature
+            ),
+        ))
+    })
+}
+
+fn parse_format(value: &Value) -> Result<Format, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("text", Format::Text),
+            ("json", Format::Json),
+            ("logfmt", Format::Logfmt),
+            ("rustc", Format::Rustc),
+            ("auto", Format::Auto),
+        ],
+    )
+}
+
+fn parse_ffi(value: &Value) -> Result<Ffi, Diagnostic> {
+    match value {
+        Value::Lit(Lit::Str(str_lit)) if str_lit.value() == "abort" => Ok(Ffi::Abort),
+        _ => parse_path(value).map(Ffi::Return),
+    }
+}
+
+fn parse_bound(value: &Value) -> Result<Bound, Diagnostic> {
+    parse_choice(value, &[("Display", Bound::Display), ("none", Bound::None)])
+}
+
+fn parse_repropagated(value: &Value) -> Result<Repropagated, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("report", Repropagated::Report),
+            ("compact", Repropagated::Compact),
+            ("quiet", Repropagated::Quiet),
+        ],
+    )
+}
+
+fn parse_level(value: &Value) -> Result<Level, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("error", Level::Error),
+            ("warn", Level::Warn),
+            ("info", Level::Info),
+            ("debug", Level::Debug),
+            ("trace", Level::Trace),
+        ],
+    )
+}
+
+fn parse_verbosity(value: &Value) -> Result<Verbosity, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("compact", Verbosity::Compact),
+            ("normal", Verbosity::Normal),
+            ("full", Verbosity::Full),
+        ],
+    )
+}
+
+fn parse_layout(value: &Value) -> Result<Layout, Diagnostic> {
+    parse_choice(
+        value,
+        &[("line", Layout::Line), ("multiline", Layout::Multiline)],
+    )
+}
+
+fn parse_location(value: &Value) -> Result<Location, Diagnostic> {
+    parse_choice(
+        value,
+        &[("span", Location::Span), ("runtime", Location::Runtime)],
+    )
+}
+
+fn parse_sink(value: &Value) -> Result<Sink, Diagnostic> {
+    parse_choice(
+        value,
+        &[
+            ("stderr", Sink::Stderr),
+            ("stdout", Sink::Stdout),
+            ("file", Sink::File),
+            ("capture", Sink::Capture),
+            ("logcat", Sink::Logcat),
+            ("os_log", Sink::OsLog),
+            ("journald", Sink::Journald),
+            ("etw", Sink::Etw),
+            ("chrome_trace", Sink::ChromeTrace),
+        ],
+    )
+    .and_then(|sink| {
+        let (name, feature) = match sink {
+            Sink::Logcat if !cfg!(feature = "android") => ("logcat", "android"),
+            Sink::OsLog if !cfg!(feature = "apple") => ("os_log", "apple"),
+            Sink::Etw if !cfg!(feature = "etw") => ("etw", "etw"),
+            sink => return Ok(sink),
+        };
+        Err(Diagnostic::error(
+            value.span(),
+            format!(
+                "The \"{}\" sink requires the `{}` feature of `debug-try`",
+                name, feature
+            ),
+        ))
+    })
+}
@@ -0,0 +1,170 @@
+use std::{env, fs, path::PathBuf, sync::Mutex};
+
+use proc_macro2::{Span, TokenStream};
+
+use syn::LitStr;
+
+use quote::quote;
+
+use crate::{
+    args::{self, Arg, DebugTryArgs},
+    Diagnostic,
+};
+
+/// The name of the file with the crate-wide defaults, next to the manifest of the crate.
+const CONFIG_FILE: &str = "debug_try.toml";
+
+/// Returns the path of the configuration file of the crate being compiled, if it has one.
+fn config_path() -> Option<PathBuf> {
+    let path = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?).join(CONFIG_FILE);
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// The arguments of the `configure!` macros expanded in this process, as source text, for each
+/// crate, since the compiler expands the macros of all the crates of a build on the same process
+/// when it is a language server. They only apply to the attributes expanded after `configure!`.
+static CONFIGURED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Identifies the crate being compiled, for [`CONFIGURED`].
+fn crate_key() -> String {
+    let var = |name| env::var(name).unwrap_or_default();
+    format!("{}:{}", var("CARGO_MANIFEST_DIR"), var("CARGO_CRATE_NAME"))
+}
+
+/// Records the arguments of `configure!` as the defaults of the crate being compiled, after
+/// checking them, replacing those of an earlier expansion of it.
+pub(crate) fn configure(input: TokenStream) -> Result<(), Diagnostic> {
+    DebugTryArgs::try_from(args::parse_args(input.clone())?)?;
+    let key = crate_key();
+    let mut configured = CONFIGURED.lock().unwrap_or_else(|err| err.into_inner());
+    configured.retain(|(crate_key, _)| *crate_key != key);
+    configured.push((key, input.to_string()));
+    Ok(())
+}
+
+/// Returns the arguments recorded by `configure!` for the crate being compiled, if it has any.
+fn configured() -> Option<Vec<Arg>> {
+    let key = crate_key();
+    let configured = CONFIGURED.lock().unwrap_or_else(|err| err.into_inner());
+    let (_, text) = configured.iter().find(|(crate_key, _)| *crate_key == key)?;
+    // the arguments have been checked when they were recorded
+    let args = args::parse_args(text.parse().ok()?).ok()?;
+    Some(args.into_iter().collect())
+}
+
+/// Returns the defaults followed by the arguments, leaving out the defaults for the keys that the
+/// arguments also have.
+fn merge(defaults: impl Iterator<Item = Arg>, args: Vec<Arg>) -> Vec<Arg> {
+    let keys: Vec<String> = args.iter().map(|arg| arg.key().to_string()).collect();
+    defaults
+        .filter(|arg| !keys.contains(&arg.key().to_string()))
+        .chain(args)
+        .collect()
+}
+
+/// Returns the arguments given in the configuration file and with `configure!`, followed by those
+/// of the attribute, leaving out the defaults for the keys that the attribute also has. The
+/// arguments of `configure!` take precedence over the file.
+pub(crate) fn with_defaults<I: IntoIterator<Item = Arg>>(args: I) -> Result<Vec<Arg>, Diagnostic> {
+    let args: Vec<Arg> = args.into_iter().collect();
+    let args = match configured() {
+        Some(defaults) => merge(defaults.into_iter(), args),
+        None => args,
+    };
+    let path = match config_path() {
+        Some(path) => path,
+        None => return Ok(args),
+    };
+    let error = |line: Option<usize>, message: &str| {
+        let location = match line {
+            Some(line) => format!("{}:{}", path.display(), line),
+            None => path.display().to_string(),
+        };
+        Diagnostic::error(Span::call_site(), format!("{}: {}", location, message))
+    };
+
+    let text = fs::read_to_string(&path).map_err(|err| error(None, &err.to_string()))?;
+    let parse = || {
+        read(&text)
+            .map_err(|(line, message)| error(Some(line), &message))
+            .map(Vec::into_iter)
+    };
+    // the defaults are checked on their own, so that an error in the file is reported as such,
+    // rather than as an error in the arguments of whichever function is expanded first
+    DebugTryArgs::try_from(parse()?).map_err(|diag| error(None, &diag.message))?;
+
+    Ok(merge(parse()?, args))
+}
+
+/// Parses the arguments of a configuration file, or returns an error with the number of the line.
+///
+/// The file holds a subset of TOML: one `key = value` pair per line, with the same keys as the
+/// attribute, and values that are strings, booleans or integers. Comments start with `#`.
+fn read(text: &str) -> Result<Vec<Arg>, (usize, String)> {
+    let mut args = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err((number, "Tables are not supported".to_owned()));
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Err((number, "Expected `key = value`".to_owned())),
+        };
+        let key: TokenStream = key
+            .parse()
+            .map_err(|_| (number, format!("Invalid key `{}`", key)))?;
+        let value =
+            value_tokens(value).ok_or_else(|| (number, format!("Invalid value `{}`", value)))?;
+        let arg = syn::parse2(quote!(#key = #value)).map_err(|err| (number, err.to_string()))?;
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Returns the tokens of a TOML value, converting literal strings like `'...'` to Rust strings.
+fn value_tokens(value: &str) -> Option<TokenStream> {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        let value = LitStr::new(&value[1..value.len() - 1], Span::call_site());
+        return Some(quote!(#value));
+    }
+    value.parse().ok()
+}
+
+/// Removes a comment at the end of a line, outside of any string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, ch) in line.char_indices() {
+        match (quote, ch) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if ch == open && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(ch),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Returns an item that makes Cargo rebuild the crate when the configuration file changes, if
+/// there is one.
+pub(crate) fn track_tokens() -> Option<TokenStream> {
+    let path = config_path()?;
+    let path = path.to_str()?;
+    Some(quote! {
+        const _: &[u8] = ::core::include_bytes!(#path);
+    })
+}
@@ -0,0 +1,61 @@
+use syn::{Attribute, Lit, Meta, Stmt};
+
+use quote::ToTokens;
+
+use crate::source::Sources;
+
+/// Returns the label for a statement, taken from its doc comments, or from the line comments
+/// immediately above it.
+pub(crate) fn statement_label(sources: &mut Sources, stmt: &Stmt) -> Option<String> {
+    if let Stmt::Local(local) = stmt {
+        if let Some(label) = doc_label(&local.attrs) {
+            return Some(label);
+        }
+    }
+
+    let span = stmt.into_token_stream().into_iter().next()?.span();
+    let (lines, line) = sources.lines(span)?;
+
+    // collect the comment lines directly above the statement, bottom to top
+    let mut comments: Vec<&str> = lines[..line.saturating_sub(1).min(lines.len())]
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .take_while(|line| line.starts_with("//"))
+        .map(|line| line.trim_start_matches('/').trim())
+        .collect();
+    comments.reverse();
+
+    join_label(comments)
+}
+
+/// Returns the label from the doc comments among the given attributes.
+fn doc_label(attrs: &[Attribute]) -> Option<String> {
+    let docs: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.interpret_meta() {
+            Some(Meta::NameValue(nv)) => match nv.lit {
+                Lit::Str(str_lit) => Some(str_lit.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    join_label(docs.iter().map(|doc| doc.trim()).collect())
+}
+
+fn join_label(lines: Vec<&str>) -> Option<String> {
+    let label = lines
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if label.is_empty() {
+        None
+    } else {
+        Some(label)
+    }
+}
@@ -0,0 +1,872 @@
+//! Implementation of the transformation performed by the `debug_try` attribute macro.
+//!
+//! This crate is an implementation detail of the `debug-try` crate, and works on `proc_macro2`
+//! token streams so that the transformation can also be run outside of a procedural macro, e.g.
+//! in snapshot tests. Its only stable API is [`instrument_item_fn`], with which other procedural
+//! macros can instrument the functions they generate or wrap:
+//!
+//! ```
+//! use debug_try_core::{instrument_item_fn, Config};
+//! use quote::quote;
+//!
+//! let config = Config::parse(quote!(color = "never")).ok().unwrap();
+//! let item = quote! {
+//!     fn load() -> Result<String, std::io::Error> {
+//!         Ok(std::fs::read_to_string("config.toml")?)
+//!     }
+//! };
+//! let output = instrument_item_fn(item, &config).ok().unwrap();
+//! assert!(output.to_string().starts_with("fn load"));
+//! ```
+//!
+//! The function is instrumented like with `#[debug_try(...)]` and the arguments of the
+//! configuration, and the generated code refers to the `debug-try` crate, which the crate that
+//! the function ends up in has to depend on. The function is passed and returned as
+//! `proc_macro2` tokens, so that a macro can use any version of `syn` to build and parse it.
+
+#![cfg_attr(feature = "nightly", feature(proc_macro_span, proc_macro_diagnostic))]
+#![recursion_limit = "256"]
+
+extern crate proc_macro;
+
+mod args;
+mod config;
+mod label;
+mod lints;
+mod report;
+mod select;
+mod site_map;
+mod snapshot;
+mod source;
+mod stats;
+mod visit;
+mod yeet;
+
+use std::cell::RefCell;
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+
+use syn::{
+    parse::{ParseStream, Parser},
+    spanned::Spanned,
+    ArgCaptured, AttrStyle, Attribute, FnArg, GenericArgument, Ident, ImplItem, ImplItemMethod,
+    Item, ItemFn, ItemImpl, ItemMod, Pat, PathArguments, ReturnType, Type,
+};
+
+use quote::{quote, ToTokens};
+
+use crate::args::{DebugTryArgs, Ffi};
+
+/// An error produced while expanding the macro.
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn error<S: Into<String>>(span: Span, message: S) -> Diagnostic {
+        Diagnostic {
+            span,
+            message: message.into(),
+        }
+    }
+
+    /// Renders the diagnostic as a `compile_error!` invocation at its span, which reports it on
+    /// any toolchain.
+    pub fn to_compile_error(&self) -> TokenStream {
+        syn::Error::new(self.span, &self.message).to_compile_error()
+    }
+}
+
+impl From<syn::Error> for Diagnostic {
+    fn from(err: syn::Error) -> Diagnostic {
+        Diagnostic::error(err.span(), err.to_string())
+    }
+}
+
+/// Expands the `debug_try` attribute with the given arguments on the given function or module.
+pub fn expand(args: TokenStream, input: TokenStream) -> Result<TokenStream, Vec<Diagnostic>> {
+    let (args, given) = parse_given_args(args)?;
+    let hidden = yeet::hide(input.clone());
+    let item = match syn::parse2::<ItemFn>(hidden.clone()) {
+        Ok(item) => Ok(item),
+        Err(err) => match syn::parse2::<ItemMod>(hidden) {
+            Ok(module) => Err(module),
+            Err(_) => return Err(vec![err.into()]),
+        },
+    };
+    if args.disabled() {
+        return Ok(input);
+    }
+    let (ident, output) = match (item, &args.ffi) {
+        (Ok(item), Some(ffi)) => {
+            let output = expand_ffi(&args, ffi, item.clone())?;
+            lints::check(&args, &given, &item, "debug_try");
+            (item.ident, output)
+        }
+        (Ok(item), None) => {
+            let output = expand_fn(&args, item.clone())?;
+            lints::check(&args, &given, &item, "debug_try");
+            (item.ident, output)
+        }
+        (Err(module), _) => (module.ident.clone(), expand_mod(&args, module)?),
+    };
+    lints::check_args(&args, &given);
+    let output = yeet::restore(output);
+    print_expansion(&args, "debug_try", &ident, &output);
+    Ok(output)
+}
+
+/// The arguments that [`instrument_item_fn`] instruments a function with.
+#[derive(Clone)]
+pub struct Config(DebugTryArgs);
+
+impl Config {
+    /// Parses the arguments of the `debug_try` attribute, e.g. `nested, color = "never"`, on top
+    /// of the defaults of the crate, as set with `configure!` or in its configuration file.
+    pub fn parse(args: TokenStream) -> Result<Config, Vec<Diagnostic>> {
+        parse_args(args).map(Config)
+    }
+}
+
+/// Instruments the tokens of a function like the `debug_try` attribute, and returns the tokens of
+/// the instrumented function, so that other procedural macros can instrument the functions that
+/// they generate or wrap, e.g. the handlers of a `#[handler]` attribute. The function is returned
+/// as it is if the instrumentation is turned off.
+///
+/// Unlike the attribute, it does not keep a copy of the function for `cfg` and `strip_release`,
+/// which have no effect, nor does it show the expansion with `print_expansion`.
+pub fn instrument_item_fn(
+    item: TokenStream,
+    config: &Config,
+) -> Result<TokenStream, Vec<Diagnostic>> {
+    let args = &config.0;
+    if args.disabled() {
+        return Ok(item);
+    }
+    let item: ItemFn = syn::parse2(yeet::hide(item)).map_err(|err| vec![err.into()])?;
+    let mut output = visit::instrument(args, item)?;
+    if let Some(track) = config::track_tokens() {
+        output.block.stmts.insert(0, syn::parse_quote!(#track));
+    }
+    Ok(yeet::restore(output.into_token_stream()))
+}
+
+fn parse_args(args: TokenStream) -> Result<DebugTryArgs, Vec<Diagnostic>> {
+    parse_given_args(args).map(|(args, _)| args)
+}
+
+/// Parses the arguments of an attribute like [`parse_args`], along with the keys of the arguments
+/// that were given to the attribute itself, for the warnings of [`lints`].
+fn parse_given_args(args: TokenStream) -> Result<(DebugTryArgs, Vec<Ident>), Vec<Diagnostic>> {
+    let args = args::parse_args(args).map_err(|err| vec![err.into()])?;
+    let given = args.iter().map(|arg| arg.key().clone()).collect();
+    let args = config::with_defaults(args).map_err(|diag| vec![diag])?;
+    let args = DebugTryArgs::try_from(args).map_err(|diag| vec![diag])?;
+    Ok((args, given))
+}
+
+fn expand_fn(args: &DebugTryArgs, input: ItemFn) -> Result<TokenStream, Vec<Diagnostic>> {
+    if args.disabled() {
+        return Ok(input.into_token_stream());
+    }
+
+    // alter input
+    let mut output = visit::instrument(args, input.clone())?;
+    if let Some(track) = config::track_tokens() {
+        output.block.stmts.insert(0, syn::parse_quote!(#track));
+    }
+
+    // keep the original function around for builds where the instrumentation is disabled
+    let cfg = match (&args.cfg, args.strip_release.unwrap_or(false)) {
+        (Some(cfg), true) => Some(quote!(all(debug_assertions, #cfg))),
+        (None, true) => Some(quote!(debug_assertions)),
+        (Some(cfg), false) => Some(cfg.into_token_stream()),
+        (None, false) => None,
+    };
+    Ok(match cfg {
+        Some(cfg) => quote! {
+            #[cfg(#cfg)]
+            #output
+            #[cfg(not(#cfg))]
+            #input
+        },
+        None => output.into_token_stream(),
+    })
+}
+
+/// Expands the `debug_try` attribute on an inline module, by instrumenting the functions in it, in
+/// its `impl` blocks and in its inner modules that return a `Result`, except for those that have
+/// an attribute of this crate of their own. Test functions are expanded like with
+/// `debug_try::test`, unless `tests = false`.
+fn expand_mod(args: &DebugTryArgs, module: ItemMod) -> Result<TokenStream, Vec<Diagnostic>> {
+    if args.ffi.is_some() {
+        return Err(vec![Diagnostic::error(
+            module.mod_token.span,
+            "`ffi` can not be used on modules",
+        )]);
+    }
+    let tokens = match module.content {
+        Some((_, ref items)) => expand_items(args, items)?,
+        None => {
+            return Err(vec![Diagnostic::error(
+                module.mod_token.span,
+                "`debug_try` can only be used on modules with a body, like `mod tests { ... }`",
+            )])
+        }
+    };
+
+    let (outer, inner): (Vec<Attribute>, Vec<Attribute>) = module
+        .attrs
+        .into_iter()
+        .partition(|attr| matches!(attr.style, AttrStyle::Outer));
+    let ItemMod {
+        vis,
+        mod_token,
+        ident,
+        ..
+    } = module;
+    Ok(quote! {
+        #(#outer)*
+        #vis #mod_token #ident {
+            #(#inner)*
+            #tokens
+        }
+    })
+}
+
+/// Expands the items of a module that has the `debug_try` attribute, or of `debug_try_all!`.
+fn expand_items(args: &DebugTryArgs, items: &[Item]) -> Result<TokenStream, Vec<Diagnostic>> {
+    let mut diags = Vec::new();
+    let mut tokens = TokenStream::new();
+    for item in items {
+        match expand_mod_item(args, item.clone()) {
+            Ok(item) => tokens.extend(item),
+            Err(errors) => diags.extend(errors),
+        }
+    }
+    if diags.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(diags)
+    }
+}
+
+/// Expands an item of a module that has the `debug_try` attribute.
+fn expand_mod_item(args: &DebugTryArgs, item: Item) -> Result<TokenStream, Vec<Diagnostic>> {
+    match item {
+        Item::Fn(mut item) if !has_own_attr(&item.attrs) => {
+            let test = item
+                .attrs
+                .iter()
+                .position(|attr| attr.path.is_ident("test"));
+            if item.constness.is_some() || !visit::returns_result(&mut item) {
+                return Ok(item.into_token_stream());
+            }
+            match test {
+                Some(_) if args.tests == Some(false) => Ok(item.into_token_stream()),
+                Some(index) => {
+                    item.attrs.remove(index);
+                    expand_test_fn(args.clone(), &[], item)
+                }
+                None => {
+                    let output = expand_fn(args, item.clone())?;
+                    lints::check(args, &[], &item, "debug_try");
+                    Ok(output)
+                }
+            }
+        }
+        Item::Mod(ref module) if module.content.is_some() && !has_own_attr(&module.attrs) => {
+            expand_mod(args, module.clone())
+        }
+        Item::Impl(item) if !has_own_attr(&item.attrs) => {
+            let mut tokens = TokenStream::new();
+            for impl_item in &item.items {
+                tokens.extend(expand_impl_item(args, impl_item.clone())?);
+            }
+            let ItemImpl {
+                attrs,
+                defaultness,
+                unsafety,
+                impl_token,
+                generics,
+                trait_,
+                self_ty,
+                ..
+            } = item;
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+            let trait_ = trait_.map(|(bang, path, for_token)| quote!(#bang #path #for_token));
+            Ok(quote! {
+                #(#attrs)*
+                #defaultness #unsafety #impl_token #impl_generics #trait_ #self_ty #where_clause {
+                    #tokens
+                }
+            })
+        }
+        item => Ok(item.into_token_stream()),
+    }
+}
+
+/// Expands a method of an `impl` block in a module that has the `debug_try` attribute.
+fn expand_impl_item(args: &DebugTryArgs, item: ImplItem) -> Result<TokenStream, Vec<Diagnostic>> {
+    let method = match item {
+        ImplItem::Method(method)
+            if method.defaultness.is_none()
+                && method.sig.constness.is_none()
+                && !has_own_attr(&method.attrs) =>
+        {
+            method
+        }
+        item => return Ok(item.into_token_stream()),
+    };
+    let ImplItemMethod {
+        attrs,
+        vis,
+        sig,
+        block,
+        ..
+    } = method;
+    let mut item = ItemFn {
+        attrs,
+        vis,
+        constness: None,
+        unsafety: sig.unsafety,
+        asyncness: sig.asyncness,
+        abi: sig.abi,
+        ident: sig.ident,
+        decl: Box::new(sig.decl),
+        block: Box::new(block),
+    };
+    if !visit::returns_result(&mut item) {
+        return Ok(item.into_token_stream());
+    }
+    let output = expand_fn(args, item.clone())?;
+    lints::check(args, &[], &item, "debug_try");
+    Ok(output)
+}
+
+/// Returns whether the attributes of an item include one of this crate, e.g. `#[debug_try(...)]`,
+/// which takes precedence over the attribute of the module.
+fn has_own_attr(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let segments: Vec<String> = attr
+            .path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        match segments.as_slice() {
+            [name] => name == "debug_try",
+            [krate, name] => {
+                krate == "debug_try" && matches!(name.as_str(), "debug_try" | "test" | "main")
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Expands the `debug_try_all!` macro, which instruments the items in it like a module with the
+/// `debug_try` attribute, with the arguments of a `#![debug_try(...)]` attribute at its start.
+pub fn expand_all(input: TokenStream) -> Result<TokenStream, Vec<Diagnostic>> {
+    let file: syn::File = syn::parse2(yeet::hide(input)).map_err(|err| vec![err.into()])?;
+    let (args, given) = parse_given_args(inner_args(&file.attrs, "debug_try_all!")?)?;
+    let items = &file.items;
+    if args.disabled() {
+        return Ok(yeet::restore(quote!(#(#items)*)));
+    }
+    if args.ffi.is_some() {
+        return Err(vec![Diagnostic::error(
+            Span::call_site(),
+            "`ffi` can not be used with `debug_try_all!`",
+        )]);
+    }
+    let output = expand_items(&args, items)?;
+    lints::check_args(&args, &given);
+    Ok(yeet::restore(output))
+}
+
+/// Expands `debug_try_item!` with the given function or module, which is instrumented like with
+/// the `debug_try` attribute, or with the given block, whose `?` operators are instrumented. The
+/// arguments are given in a `#![debug_try(...)]` attribute at the start.
+pub fn expand_item(input: TokenStream) -> Result<TokenStream, Vec<Diagnostic>> {
+    // the rest of the input is either a block, or an item that `expand` parses
+    let parser = |input: ParseStream| {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let block = match input.peek(syn::token::Brace) {
+            true => Some(input.parse::<syn::Block>()?),
+            false => None,
+        };
+        Ok((attrs, block, input.parse::<TokenStream>()?))
+    };
+    let (attrs, block, rest) = parser
+        .parse2(yeet::hide(input))
+        .map_err(|err| vec![err.into()])?;
+    let args = inner_args(&attrs, "debug_try_item!")?;
+    match block {
+        None => expand(args, yeet::restore(rest)),
+        Some(block) => {
+            if !rest.is_empty() {
+                return Err(vec![Diagnostic::error(
+                    rest.span(),
+                    "unexpected tokens after the block",
+                )]);
+            }
+            let (args, given) = parse_given_args(args)?;
+            if args.disabled() {
+                return Ok(yeet::restore(block.into_token_stream()));
+            }
+            let block = visit::instrument_block(&args, block)?;
+            lints::check_args(&args, &given);
+            Ok(yeet::restore(block.into_token_stream()))
+        }
+    }
+}
+
+/// Returns the arguments given in the `#![debug_try(...)]` attribute among the inner attributes
+/// at the start of the input of the given macro, which is the only one that can be used there.
+fn inner_args(attrs: &[Attribute], name: &str) -> Result<TokenStream, Vec<Diagnostic>> {
+    let mut args = None;
+    for attr in attrs {
+        let span = attr.pound_token.spans[0];
+        if !attr.path.is_ident("debug_try") {
+            return Err(vec![Diagnostic::error(
+                span,
+                format!(
+                    "only `#![debug_try(...)]` can be used as an inner attribute of `{}`",
+                    name
+                ),
+            )]);
+        }
+        if args.is_some() {
+            return Err(vec![Diagnostic::error(
+                span,
+                "`#![debug_try(...)]` can only be given once",
+            )]);
+        }
+        let mut tts = attr.tts.clone().into_iter();
+        args = Some(match (tts.next(), tts.next()) {
+            (None, _) => TokenStream::new(),
+            (Some(TokenTree::Group(ref group)), None)
+                if group.delimiter() == Delimiter::Parenthesis =>
+            {
+                group.stream()
+            }
+            _ => {
+                return Err(vec![Diagnostic::error(
+                    span,
+                    "expected `#![debug_try]` or `#![debug_try(...)]`",
+                )])
+            }
+        });
+    }
+    Ok(args.unwrap_or_default())
+}
+
+/// Expands the `debug_try` attribute with `ffi` on the given `extern` function.
+///
+/// The body is moved to an inner function that returns the `Result`, which is instrumented as
+/// usual, and the function returns the value of `Ok` to its caller. An error is reported with its
+/// sources and the location of the last `?` operator that propagated it, after which the process
+/// is aborted, or the value that the function given with `ffi` maps the error to is returned.
+fn expand_ffi(
+    args: &DebugTryArgs,
+    ffi: &Ffi,
+    input: ItemFn,
+) -> Result<TokenStream, Vec<Diagnostic>> {
+    if input.abi.is_none() {
+        return Err(vec![Diagnostic::error(
+            input.decl.fn_token.span,
+            "`ffi` can only be used on `extern` functions",
+        )]);
+    }
+    let ok = match input.decl.output {
+        ReturnType::Type(_, ref ty) => ok_type(ty),
+        ReturnType::Default => None,
+    };
+    let ok = match ok {
+        Some(ok) => ok,
+        None => {
+            return Err(vec![Diagnostic::error(
+                input.ident.span(),
+                "`ffi` requires a return type like `Result<T, E>`",
+            )])
+        }
+    };
+
+    // the parameters are passed on by name, so the outer function binds them without `mut`
+    let mut inputs = input.decl.inputs.clone();
+    let mut names = Vec::new();
+    for arg in inputs.iter_mut() {
+        match arg {
+            FnArg::Captured(ArgCaptured {
+                pat: Pat::Ident(pat),
+                ..
+            }) if pat.subpat.is_none() => {
+                pat.by_ref = None;
+                pat.mutability = None;
+                names.push(pat.ident.clone());
+            }
+            arg => {
+                return Err(vec![Diagnostic::error(
+                    arg.span(),
+                    "`ffi` requires every parameter to be bound to a name",
+                )])
+            }
+        }
+    }
+
+    let mut args = args.clone();
+    args.record_site = Some(true);
+    let mut inner = inner_fn(&input, "__debug_try_ffi");
+    inner.abi = None;
+    let inner = expand_fn(&args, inner)?;
+
+    let ItemFn {
+        attrs,
+        vis,
+        unsafety,
+        abi,
+        ident,
+        decl,
+        ..
+    } = input;
+    let (impl_generics, _, where_clause) = decl.generics.split_for_impl();
+    let call = quote!(__debug_try_ffi(#(#names),*));
+    let call = match unsafety {
+        Some(_) => quote!(unsafe { #call }),
+        None => call,
+    };
+    let failure = report::main_failure_tokens(&args, &format!("Error returned by `{}`", ident));
+    let on_error = match ffi {
+        Ffi::Abort => quote!(::std::process::abort()),
+        Ffi::Return(path) => quote!(#path(__debug_try_err)),
+    };
+    Ok(quote! {
+        #(#attrs)*
+        #vis #unsafety #abi fn #ident #impl_generics(#inputs) -> #ok #where_clause {
+            #inner
+            match #call {
+                ::core::result::Result::Ok(__debug_try_ok) => __debug_try_ok,
+                ::core::result::Result::Err(__debug_try_err) => {
+                    #failure
+                    #on_error
+                }
+            }
+        }
+    })
+}
+
+/// Returns the type of the value of a return type like `Result<T, E>` or `io::Result<T>`.
+fn ok_type(ty: &Type) -> Option<Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?.into_value(),
+        Type::Paren(paren) => return ok_type(&paren.elem),
+        Type::Group(group) => return ok_type(&group.elem),
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) => match args.args.first()?.into_value() {
+            GenericArgument::Type(ok) => Some(ok.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Expands `configure! { ... }`, which records the arguments as the defaults of the functions
+/// of the crate that are instrumented after it, and expands to a constant, so that a second
+/// `configure!` in the same module is an error.
+pub fn expand_configure(input: TokenStream) -> Result<TokenStream, Vec<Diagnostic>> {
+    config::configure(input).map_err(|diag| vec![diag])?;
+    Ok(quote! {
+        const __DEBUG_TRY_CONFIGURED: () = ();
+    })
+}
+
+/// Expands the `debug_try::test` attribute with the given arguments on the given test function.
+///
+/// The body is moved to an inner function, which is instrumented like with the `debug_try`
+/// attribute, and the test prints the error and its sources if that function returns one.
+pub fn expand_test(args: TokenStream, input: TokenStream) -> Result<TokenStream, Vec<Diagnostic>> {
+    let (args, given) = parse_given_args(args)?;
+    let input: ItemFn = syn::parse2(yeet::hide(input)).map_err(|err| vec![err.into()])?;
+    let ident = input.ident.clone();
+    let output = expand_test_fn(args.clone(), &given, input)?;
+    if !args.disabled() {
+        lints::check_args(&args, &given);
+    }
+    let output = yeet::restore(output);
+    print_expansion(&args, "debug_try::test", &ident, &output);
+    Ok(output)
+}
+
+/// Expands a test function like the `debug_try::test` attribute, without `#[test]` among its
+/// attributes, which was given the given arguments itself.
+fn expand_test_fn(
+    mut args: DebugTryArgs,
+    given: &[Ident],
+    input: ItemFn,
+) -> Result<TokenStream, Vec<Diagnostic>> {
+    if args.disabled() {
+        return Ok(quote! {
+            #[test]
+            #input
+        });
+    }
+    // the sites of the test are recorded for the sections of `rich`
+    if args.rich.unwrap_or(false) {
+        args.record_site = Some(true);
+    }
+    let inner = expand_fn(&args, inner_fn(&input, "__debug_try_test"))?;
+    lints::check(&args, given, &input, "debug_try::test");
+
+    let ItemFn {
+        attrs,
+        vis,
+        ident,
+        decl,
+        ..
+    } = input;
+    let output = &decl.output;
+    let failure = report::test_failure_tokens(&args, &ident.to_string());
+    let output = quote! {
+        #[test]
+        #(#attrs)*
+        #vis fn #ident() #output {
+            #inner
+            let __debug_try_result = __debug_try_test();
+            if let ::core::result::Result::Err(ref __debug_try_err) = __debug_try_result {
+                #failure
+            }
+            __debug_try_result
+        }
+    };
+    Ok(output)
+}
+
+/// Expands the `debug_try::main` attribute with the given arguments on the given `main` function.
+///
+/// The body is moved to an inner function, which is instrumented like with the `debug_try`
+/// attribute, and `main` reports the error with its sources if that function returns one, along
+/// with the location of the last `?` operator that propagated it.
+pub fn expand_main(args: TokenStream, input: TokenStream) -> Result<TokenStream, Vec<Diagnostic>> {
+    let (mut args, given) = parse_given_args(args)?;
+    let input: ItemFn = syn::parse2(yeet::hide(input)).map_err(|err| vec![err.into()])?;
+    if args.disabled() {
+        return Ok(yeet::restore(input.into_token_stream()));
+    }
+    if let Some(asyncness) = input.asyncness {
+        return Err(vec![Diagnostic::error(
+            asyncness.span,
+            "`debug_try::main` can not be used on `async` functions, but can be used below an \
+             attribute that runs them, e.g. `#[tokio::main]`",
+        )]);
+    }
+    args.record_site = Some(true);
+    let inner = expand_fn(&args, inner_fn(&input, "__debug_try_main"))?;
+    lints::check(&args, &given, &input, "debug_try::main");
+    lints::check_args(&args, &given);
+
+    let ItemFn {
+        attrs, vis, ident, ..
+    } = input;
+    let mut failure = report::main_failure_tokens(&args, "Error");
+    let mut setup = TokenStream::new();
+    if args.crash_report.unwrap_or(false) {
+        let crash_report = report::crash_report_tokens(&args);
+        failure = quote! {
+            #crash_report
+            #failure
+            ::debug_try::__private::report_crash(&__debug_try_crash);
+        };
+        setup = quote!(::debug_try::__private::enable_crash_reports(););
+    }
+    let output = quote! {
+        #(#attrs)*
+        #vis fn #ident() -> ::std::process::ExitCode {
+            #inner
+            #setup
+            match __debug_try_main() {
+                ::core::result::Result::Ok(__debug_try_ok) => {
+                    ::std::process::Termination::report(__debug_try_ok)
+                }
+                ::core::result::Result::Err(__debug_try_err) => {
+                    #failure
+                    ::std::process::ExitCode::FAILURE
+                }
+            }
+        }
+    };
+    let output = yeet::restore(output);
+    print_expansion(&args, "debug_try::main", &ident, &output);
+    Ok(output)
+}
+
+/// Shows the expansion of the given attribute on the function with the given name as a compiler
+/// note, if `print_expansion` is set. On stable toolchains, the note is printed to stderr, where
+/// Cargo passes it through.
+fn print_expansion(args: &DebugTryArgs, attr: &str, ident: &Ident, output: &TokenStream) {
+    if !args.print_expansion.unwrap_or(false) || !proc_macro::is_available() {
+        return;
+    }
+    let message = format!(
+        "expansion of `#[{}]` on `{}`:\n{}",
+        attr,
+        ident,
+        snapshot::render(output.clone())
+    );
+    note(ident, message);
+}
+
+thread_local! {
+    /// The lines of the notes and warnings shown outside of a procedural macro, which
+    /// [`test_expand`] appends to its output.
+    static NOTES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Shows a compiler note at the given name. On stable toolchains, the note is printed to stderr,
+/// where Cargo passes it through, with the location of the name in the format of the compiler.
+pub(crate) fn note(ident: &Ident, message: String) {
+    if !proc_macro::is_available() {
+        NOTES.with(|notes| notes.borrow_mut().push(format!("note: {}", message)));
+        return;
+    }
+    #[cfg(feature = "nightly")]
+    ident.span().unwrap().note(message).emit();
+    #[cfg(not(feature = "nightly"))]
+    eprint!("{}", located("note", ident.span(), &message));
+}
+
+/// Shows a compiler warning at the given span, like [`note`].
+pub(crate) fn warning(span: Span, message: String) {
+    if !proc_macro::is_available() {
+        NOTES.with(|notes| notes.borrow_mut().push(format!("warning: {}", message)));
+        return;
+    }
+    #[cfg(feature = "nightly")]
+    span.unwrap().warning(message).emit();
+    #[cfg(not(feature = "nightly"))]
+    eprint!("{}", located("warning", span, &message));
+}
+
+/// Formats a diagnostic of the given level like the compiler does, with a line like
+/// ` --> src/main.rs:10:5` for the location of the span.
+#[cfg(not(feature = "nightly"))]
+fn located(level: &str, span: Span, message: &str) -> String {
+    let span = span.unwrap().start();
+    format!(
+        "{}: {}\n --> {}:{}:{}\n",
+        level,
+        message,
+        span.file(),
+        span.line(),
+        span.column()
+    )
+}
+
+/// Returns a copy of the function without its attributes, with the given name, to be called by a
+/// function that has the original name and attributes.
+fn inner_fn(input: &ItemFn, name: &str) -> ItemFn {
+    let mut inner = input.clone();
+    inner.attrs.clear();
+    inner.vis = syn::Visibility::Inherited;
+    inner.ident = Ident::new(name, input.ident.span());
+    inner
+}
+
+/// Expands a function annotated with `#[debug_try(...)]`, `#[debug_try::test(...)]` or
+/// `#[debug_try::main(...)]` and renders the result as a line-oriented string, suitable for
+/// snapshot tests of the expansion.
+///
+/// Outside of a procedural macro, source locations are not available and are rendered as
+/// `<unknown>`. Errors are appended to the output as lines starting with `error: `, notes, like
+/// those of `stats`, as lines starting with `note: `, and warnings as lines starting with
+/// `warning: `.
+pub fn test_expand(tokens: TokenStream) -> String {
+    let mut input: Item = match syn::parse2(yeet::hide(tokens)) {
+        Ok(input) => input,
+        Err(err) => return format!("error: {}\n", err),
+    };
+    NOTES.with(|notes| notes.borrow_mut().clear());
+    let render = |output: Result<TokenStream, Vec<Diagnostic>>| {
+        let mut rendered: String = match output {
+            Ok(output) => snapshot::render(output),
+            Err(diags) => diags
+                .into_iter()
+                .map(|diag| format!("error: {}\n", diag.message))
+                .collect(),
+        };
+        NOTES.with(|notes| {
+            for note in notes.borrow_mut().drain(..) {
+                rendered.push_str(&format!("{}\n", note));
+            }
+        });
+        rendered
+    };
+    if let Item::Macro(ref item) = input {
+        let path = &item.mac.path.segments;
+        if path
+            .last()
+            .is_some_and(|name| name.value().ident == "debug_try_all")
+        {
+            return render(expand_all(item.mac.tts.clone()));
+        }
+        if path
+            .last()
+            .is_some_and(|name| name.value().ident == "debug_try_item")
+        {
+            return render(expand_item(item.mac.tts.clone()));
+        }
+        if path
+            .last()
+            .is_some_and(|name| name.value().ident == "configure")
+        {
+            return render(expand_configure(item.mac.tts.clone()));
+        }
+    }
+    let attrs = match input {
+        Item::Fn(ref mut item) => &mut item.attrs,
+        Item::Mod(ref mut item) => &mut item.attrs,
+        _ => return "error: expected a function, a module or `debug_try_all!`\n".to_string(),
+    };
+
+    // take the arguments from the (first) attribute of the crate, and remove that attribute
+    let path = |attr: &syn::Attribute| -> Vec<String> {
+        attr.path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect()
+    };
+    let wrapper = |path: &[String]| match path {
+        [krate, name] if krate == "debug_try" && (name == "test" || name == "main") => {
+            Some(name.clone())
+        }
+        _ => None,
+    };
+    let mut args = TokenStream::new();
+    let mut kind = None;
+    if let Some(index) = attrs.iter().position(|attr| {
+        let path = path(attr);
+        path.last().is_some_and(|name| name == "debug_try") || wrapper(&path).is_some()
+    }) {
+        let attr = attrs.remove(index);
+        kind = wrapper(&path(&attr));
+        let mut tts = attr.tts.into_iter();
+        if let (Some(TokenTree::Group(group)), None) = (tts.next(), tts.next()) {
+            if group.delimiter() == Delimiter::Parenthesis {
+                args = group.stream();
+            }
+        }
+    }
+
+    let output = match kind.as_deref() {
+        Some("test") => expand_test(args, input.into_token_stream()),
+        Some(_) => expand_main(args, input.into_token_stream()),
+        None => expand(args, input.into_token_stream()),
+    };
+    render(output)
+}
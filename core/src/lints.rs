@@ -0,0 +1,237 @@
+use proc_macro2::{Span, TokenStream, TokenTree};
+
+use syn::{
+    visit_mut::{self, VisitMut},
+    Expr, ExprCall, ExprClosure, ExprMethodCall, ExprTry, Ident, Item, ItemFn, Macro, Stmt,
+};
+
+use crate::{
+    args::{DebugTryArgs, Verbosity},
+    visit, yeet,
+};
+
+/// The arguments that have no effect when given together with another one, with the reason.
+const REDUNDANT: &[(&str, &str, &str)] = &[
+    (
+        "sample",
+        "once",
+        "`once` only reports the first error of each site, which is always sampled",
+    ),
+    (
+        "rate_limit",
+        "once",
+        "`once` only reports the first error of each site, which is always allowed",
+    ),
+    (
+        "counts",
+        "site_ids",
+        "`site_ids` already shows how often each site has propagated an error",
+    ),
+];
+
+/// Warns about the arguments given to an attribute of this crate that have no effect with each
+/// other, unless `lints = false`, so that a misconfigured attribute is noticed when it is compiled
+/// rather than when its messages are missing. Only the `given` arguments of the attribute itself
+/// are checked, since the defaults of a crate apply to functions that they may not fit.
+pub(crate) fn check_args(args: &DebugTryArgs, given: &[Ident]) {
+    if args.lints == Some(false) {
+        return;
+    }
+    let is_given = |key: &str| given.iter().any(|ident| ident == key);
+
+    for &(key, other, reason) in REDUNDANT {
+        if is_given(key) && is_given(other) {
+            crate::warning(
+                span(given, key),
+                format!("`{}` has no effect with `{}`: {}", key, other, reason),
+            );
+        }
+    }
+    if is_given("pause_per_site") && args.pause_on_error.is_none() {
+        crate::warning(
+            span(given, "pause_per_site"),
+            "`pause_per_site` has no effect without `pause_on_error`".to_owned(),
+        );
+    }
+    if is_given("sample") && args.sample == Some(1) {
+        crate::warning(
+            span(given, "sample"),
+            "`sample = 1` reports every error, like no `sample` at all".to_owned(),
+        );
+    }
+    if is_given("args") && args.fn_args == Some(true) && args.verbosity == Some(Verbosity::Full) {
+        crate::warning(
+            span(given, "args"),
+            "`args = true` is already implied by `verbosity = \"full\"`".to_owned(),
+        );
+    }
+}
+
+/// Warns about an attribute of this crate that has no effect on the function that it instruments,
+/// like [`check_args`], which is `given` no arguments if it is instrumented by the attribute of a
+/// module. Whether the attribute has any effect at all is only checked for `debug_try` without
+/// `ffi`, since the other attributes also report the error that the function returns.
+pub(crate) fn check(args: &DebugTryArgs, given: &[Ident], input: &ItemFn, attr: &str) {
+    if args.lints == Some(false) {
+        return;
+    }
+    let mut contents = Contents {
+        args,
+        instrumented: true,
+        tries: 0,
+        nested: false,
+    };
+    contents.visit_block_mut(&mut (*input.block).clone());
+
+    let other_sites = [args.unwraps, args.discards, args.ignored, args.span]
+        .iter()
+        .any(|arg| arg.unwrap_or(false));
+    let reports_result = attr != "debug_try" || args.ffi.is_some();
+    if !reports_result && contents.tries == 0 && !other_sites {
+        crate::warning(
+            input.ident.span(),
+            format!(
+                "`#[{}]` has no effect on `{}`, which has no `?` operators that are instrumented",
+                attr, input.ident
+            ),
+        );
+    }
+
+    let nested = given.iter().any(|ident| ident == "nested");
+    if nested && args.nested == Some(true) && !contents.nested {
+        crate::warning(
+            span(given, "nested"),
+            format!(
+                "`nested` has no effect on `{}`, which has no closures or inner items",
+                input.ident
+            ),
+        );
+    }
+}
+
+/// Returns the span of the given argument, to show its warning at.
+fn span(given: &[Ident], key: &str) -> Span {
+    given
+        .iter()
+        .find(|ident| *ident == key)
+        .map_or_else(Span::call_site, Ident::span)
+}
+
+/// What a function has that its arguments may apply to.
+struct Contents<'a> {
+    args: &'a DebugTryArgs,
+    /// Whether the code being visited is instrumented, which closures and inner items are only
+    /// with `nested`, or for the closures passed to combinators, with `combinators`.
+    instrumented: bool,
+    /// The number of `?` operators and `do yeet` expressions that are instrumented, including
+    /// those of macros, which may be.
+    tries: usize,
+    /// Whether it has closures or inner items, which `nested` applies to, or macros that may have
+    /// them.
+    nested: bool,
+}
+
+impl VisitMut for Contents<'_> {
+    fn visit_expr_try_mut(&mut self, i: &mut ExprTry) {
+        if self.instrumented {
+            self.tries += 1;
+        }
+        visit_mut::visit_expr_try_mut(self, i);
+    }
+
+    fn visit_expr_closure_mut(&mut self, i: &mut ExprClosure) {
+        self.nested = true;
+        let nested = self.args.nested.unwrap_or(false);
+        self.visit_nested(nested, |contents| {
+            visit_mut::visit_expr_closure_mut(contents, i)
+        });
+    }
+
+    fn visit_expr_method_call_mut(&mut self, i: &mut ExprMethodCall) {
+        if !self.args.combinators.unwrap_or(true) || !visit::is_combinator(i) {
+            visit_mut::visit_expr_method_call_mut(self, i);
+            return;
+        }
+        // like in `Visitor::visit_expr_method_call_mut`
+        self.visit_expr_mut(&mut i.receiver);
+        let nested = self.args.nested.unwrap_or(false);
+        for arg in i.args.iter_mut() {
+            match arg {
+                Expr::Closure(closure) => {
+                    self.nested = true;
+                    let nested = nested || !visit::returns_option(closure);
+                    self.visit_nested(nested, |contents| {
+                        visit_mut::visit_expr_closure_mut(contents, closure)
+                    });
+                }
+                arg => self.visit_expr_mut(arg),
+            }
+        }
+    }
+
+    fn visit_expr_call_mut(&mut self, i: &mut ExprCall) {
+        // a closure that is called right away is instrumented like the function itself
+        if i.args.is_empty() {
+            if let Some(closure) = visit::immediate_closure(&mut i.func) {
+                if closure.inputs.is_empty() {
+                    self.visit_expr_mut(&mut closure.body);
+                    return;
+                }
+            }
+        }
+        visit_mut::visit_expr_call_mut(self, i);
+    }
+
+    fn visit_stmt_mut(&mut self, i: &mut Stmt) {
+        match i {
+            Stmt::Item(Item::Use(_)) | Stmt::Item(Item::Macro(_)) => {
+                visit_mut::visit_stmt_mut(self, i)
+            }
+            Stmt::Item(_) => {
+                self.nested = true;
+                let nested = self.args.nested.unwrap_or(false);
+                self.visit_nested(nested, |contents| visit_mut::visit_stmt_mut(contents, i));
+            }
+            _ => visit_mut::visit_stmt_mut(self, i),
+        }
+    }
+
+    fn visit_macro_mut(&mut self, i: &mut Macro) {
+        if i.path.is_ident(yeet::MACRO) && self.instrumented {
+            self.tries += 1;
+        }
+        self.scan(&i.tts);
+    }
+}
+
+impl Contents<'_> {
+    /// Visits closures or inner items, which are only instrumented if `nested` is true.
+    fn visit_nested<F: FnOnce(&mut Self)>(&mut self, nested: bool, f: F) {
+        let instrumented = self.instrumented;
+        self.instrumented = instrumented && nested;
+        f(self);
+        self.instrumented = instrumented;
+    }
+
+    /// Counts the `?` tokens of a macro, and whether it may have closures or inner items, from
+    /// the `|`, `fn` and `impl` tokens, since its arguments are not parsed. A `?` token counts as
+    /// instrumented even inside a closure, since it is not known where closures start.
+    fn scan(&mut self, tokens: &TokenStream) {
+        for token in tokens.clone() {
+            match token {
+                TokenTree::Punct(ref punct) if punct.as_char() == '?' && self.instrumented => {
+                    self.tries += 1
+                }
+                TokenTree::Punct(ref punct) if punct.as_char() == '|' => self.nested = true,
+                TokenTree::Ident(ref ident) if ident == "fn" || ident == "impl" => {
+                    self.nested = true
+                }
+                TokenTree::Ident(ref ident) if ident == yeet::MACRO && self.instrumented => {
+                    self.tries += 1
+                }
+                TokenTree::Group(ref group) => self.scan(&group.stream()),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,3586 @@
+use std::{env, path::PathBuf};
+
+use proc_macro2::{Ident, Span, TokenStream};
+
+use syn::{Expr, LitByteStr, Type};
+
+use quote::{quote, quote_spanned, ToTokens};
+
+use crate::args::{
+    Backend, Bound, BuildInfo, Color, DebugTryArgs, Format, Hyperlinks, Level, Location, OnPanic,
+    Paths, Pause, Repropagated, Sink, Summary, Verbosity, BUILD_VARS,
+};
+
+/// An instrumented `?` operator.
+#[derive(Clone)]
+pub(crate) struct Site {
+    /// The source location of the operator, formatted as `file:line:column`, or as `file:line`
+    /// if the column is left out.
+    pub location: String,
+    /// The source file, line and column of the operator, where the column starts at 1 and counts
+    /// characters, like in the messages of rustc.
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    /// The name of the function that contains the operator.
+    pub function: String,
+    /// The label of the function given with `name`, which the messages carry along with the name
+    /// of the function.
+    pub name: Option<String>,
+    /// The subsystem given with `group`, whose sites share their summaries.
+    pub group: Option<String>,
+    /// An identifier for the site, which is stable across builds as long as the location and the
+    /// expression do not change.
+    pub id: u32,
+    /// A human-readable label for the site.
+    pub label: Option<String>,
+    /// The source line of the site, with the failing expression underlined.
+    pub snippet: Option<String>,
+    /// The step of the chain of `?` operators that the site ends, e.g. `.b()?` in `a()?.b()?`.
+    pub step: Option<String>,
+    /// The span of the operator.
+    pub span: Span,
+    /// Whether the location is looked up at run time, with `Location::caller`.
+    pub runtime: bool,
+    /// The URL that the location links to in colored messages, with `hyperlinks`.
+    pub link: Option<String>,
+    /// Whether text messages leave out the column of the location, with `verbosity = "compact"`.
+    pub short: bool,
+    /// The error type that the operator converts the error to, if the return type of the function
+    /// or closure names it.
+    pub target: Option<Type>,
+    /// What the site does with the error.
+    pub kind: SiteKind,
+}
+
+/// What a site does with an error.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SiteKind {
+    /// A `?` operator, which propagates it.
+    Try,
+    /// An `unwrap` or `expect` call, which panics with it.
+    Unwrap,
+    /// A call like `ok()`, which throws it away.
+    Discard,
+    /// A result that is ignored, with `let _ = ...` or as a statement.
+    Ignore,
+    /// A failed attempt of a `?` operator with `retry`, which evaluates the expression again.
+    Retry,
+    /// A `do yeet` expression, which returns it.
+    Yeet,
+    /// A `?` operator with `or_default`, which evaluates to the default value instead.
+    Default,
+}
+
+impl Site {
+    pub fn new(
+        args: &DebugTryArgs,
+        span: Span,
+        expr: &Expr,
+        function: &str,
+        label: Option<String>,
+    ) -> Site {
+        let (location, file, line, column) = match location(args, span) {
+            Some((file, line, column)) => {
+                (format!("{}:{}:{}", file, line, column), file, line, column)
+            }
+            None => ("<unknown>".to_owned(), "<unknown>".to_owned(), 0, 0),
+        };
+        let id = fnv1a(format!("{} {}", location, expr.into_token_stream()).as_bytes());
+        let short = args.verbosity() == Verbosity::Compact;
+        let location = match short {
+            true if line != 0 => format!("{}:{}", file, line),
+            _ => location,
+        };
+        let runtime = match args.location {
+            Some(Location::Runtime) => true,
+            Some(Location::Span) => false,
+            // the compiler resolves a span from a macro expansion to the macro invocation
+            None => cfg!(not(feature = "nightly")) && from_expansion(span),
+        };
+        let link = match args.hyperlinks {
+            Some(Hyperlinks::Url(ref url)) if !runtime && line != 0 => {
+                Some(link(url, span, line, column))
+            }
+            _ => None,
+        };
+        Site {
+            location,
+            file,
+            line,
+            column,
+            function: function.to_owned(),
+            name: args.name.clone(),
+            group: args.group.clone(),
+            id,
+            label,
+            snippet: None,
+            step: None,
+            span,
+            runtime,
+            link,
+            short,
+            target: None,
+            kind: SiteKind::Try,
+        }
+    }
+
+    /// Returns the start of the messages of the site.
+    fn headline(&self) -> &'static str {
+        match self.kind {
+            SiteKind::Try => "Error propagated",
+            SiteKind::Unwrap => "Unwrap failed",
+            SiteKind::Discard => "Error discarded",
+            SiteKind::Ignore => "Error ignored",
+            SiteKind::Retry => "Attempt failed",
+            SiteKind::Yeet => "Error returned",
+            SiteKind::Default => "Error replaced with default",
+        }
+    }
+
+    /// Returns an expression for the location of the operator, if it is looked up at run time.
+    ///
+    /// The call has the span of the operator, which `Location::caller` resolves to the outermost
+    /// macro invocation, if the operator was generated by one.
+    fn caller(&self) -> Option<TokenStream> {
+        if self.runtime {
+            Some(quote_spanned!(self.span=> ::core::panic::Location::caller()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns expressions for the source file, line and column of the operator.
+    pub fn location_tokens(&self) -> (TokenStream, TokenStream, TokenStream) {
+        match self.caller() {
+            Some(caller) => (
+                quote!(#caller.file()),
+                quote!(#caller.line()),
+                quote!(#caller.column()),
+            ),
+            None => {
+                let (file, line, column) = (&self.file, self.line, self.column);
+                (quote!(#file), quote!(#line), quote!(#column))
+            }
+        }
+    }
+
+    /// Returns the location as it appears in a format string, `file:line:column`.
+    fn location_str(&self) -> String {
+        if self.runtime && self.short {
+            "{}:{}".to_owned()
+        } else if self.runtime {
+            "{}:{}:{}".to_owned()
+        } else {
+            escape_format(&self.location)
+        }
+    }
+
+    /// Returns the location as it appears in a format string for messages like those of rustc,
+    /// `file:line:column`.
+    fn rustc_location_str(&self) -> String {
+        if self.runtime {
+            "{}:{}:{}".to_owned()
+        } else if self.line == 0 {
+            escape_format(&self.file)
+        } else {
+            escape_format(&format!("{}:{}:{}", self.file, self.line, self.column))
+        }
+    }
+
+    /// Returns the values for the placeholders of the location in a format string, if any. In a
+    /// JSON object or a logfmt line, the file is formatted inside a quoted string, and is escaped.
+    fn location_values(&self, format: Format) -> Vec<TokenStream> {
+        if !self.runtime {
+            return Vec::new();
+        }
+
+        let (file, line, column) = self.location_tokens();
+        let file = match format {
+            Format::Text | Format::Rustc | Format::Auto => file,
+            Format::Json | Format::Logfmt => quote!(::debug_try::__private::Json(#file)),
+        };
+        match format {
+            Format::Text if self.short => vec![file, line],
+            _ => vec![file, line, column],
+        }
+    }
+}
+
+/// Computes the 32-bit FNV-1a hash of the given bytes.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Returns the source file, line and column of the given span, or `None` when not running inside
+/// a procedural macro.
+fn location(args: &DebugTryArgs, span: Span) -> Option<(String, u32, u32)> {
+    if !proc_macro::is_available() {
+        return None;
+    }
+
+    let span = resolve_span(span.unwrap());
+    let start = span.start();
+    Some((
+        source_path(args.paths.unwrap_or(Paths::Relative), &span),
+        start.line() as u32,
+        start.column() as u32,
+    ))
+}
+
+/// Returns the URL that the location of the given span links to, from a URL with `{path}`,
+/// `{line}` and `{column}` placeholders. The path is absolute, with `/` as separator and the
+/// characters that can not appear in a URL percent-encoded.
+fn link(url: &str, span: Span, line: u32, column: u32) -> String {
+    let path = source_path(Paths::Absolute, &resolve_span(span.unwrap())).replace('\\', "/");
+    let mut encoded = String::new();
+    if !path.starts_with('/') {
+        encoded.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    url.replace("{path}", &encoded)
+        .replace("{line}", &line.to_string())
+        .replace("{column}", &column.to_string())
+}
+
+/// Returns a span with the location of the given span, but which resolves names like the spans of
+/// the generated code, so that generated code can point at a `?` operator and still refer to the
+/// generated bindings, even in code generated by `macro_rules!`.
+fn located_at(span: Span) -> Span {
+    if !proc_macro::is_available() {
+        return span;
+    }
+
+    proc_macro::Span::call_site()
+        .located_at(span.unwrap())
+        .into()
+}
+
+/// Returns whether the span comes from a macro expansion, e.g. a `?` operator in the definition of
+/// a `macro_rules!` macro, rather than from the source code.
+///
+/// Without the `nightly` feature, this is only known from the `Debug` representation of the span,
+/// which starts with its syntax context, e.g. `#0 bytes(10..11)`, where `#0` is the context of the
+/// source code.
+fn from_expansion(span: Span) -> bool {
+    if !proc_macro::is_available() {
+        return false;
+    }
+
+    !format!("{:?}", span.unwrap()).starts_with("#0 ")
+}
+
+/// Resolves the span that should be used for the location of a `?` operator.
+///
+/// If the token comes from a macro expansion, e.g. because a function was generated by a
+/// `macro_rules!` macro or another procedural macro, the outermost macro invocation that produced
+/// it is used, which is in code that the user can find. That needs the `nightly` feature; on
+/// stable, the span of the token is used, and the location of such a token is looked up at run
+/// time instead.
+#[cfg(feature = "nightly")]
+pub(crate) fn resolve_span(span: proc_macro::Span) -> proc_macro::Span {
+    let mut current = span;
+    while let Some(parent) = current.parent() {
+        current = parent;
+    }
+    current
+}
+
+#[cfg(not(feature = "nightly"))]
+pub(crate) fn resolve_span(span: proc_macro::Span) -> proc_macro::Span {
+    span
+}
+
+/// Returns the path of the source file of the given span, either as an absolute path, relative to
+/// the workspace root (the working directory of the compiler) or the crate root, or as the compiler
+/// displays it.
+///
+/// The path that the compiler displays is the one remapped with `--remap-path-prefix`, if it is
+/// remapped, which `"relative"` prints instead of the path on disk, so that the messages of a
+/// reproducible build do not depend on where it was built.
+fn source_path(paths: Paths, span: &proc_macro::Span) -> String {
+    let path = match span.local_file() {
+        Some(path) => path,
+        None => return span.file(),
+    };
+    let remapped = span.file() != path.to_string_lossy();
+    match paths {
+        Paths::Remapped => return span.file(),
+        Paths::Relative if remapped => return span.file(),
+        _ => {}
+    }
+    let path = match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path,
+    };
+
+    let workspace_dir = env::current_dir().ok();
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+    let roots = match paths {
+        Paths::Relative => [workspace_dir, manifest_dir],
+        Paths::Crate => [manifest_dir, workspace_dir],
+        Paths::Absolute | Paths::Remapped => [None, None],
+    };
+    let path = roots
+        .iter()
+        .flatten()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .next()
+        .unwrap_or(&path);
+    path.to_string_lossy().into_owned()
+}
+
+/// The name of the variable that holds the time at which the function was entered.
+pub(crate) const START_IDENT: &str = "__debug_try_start";
+
+/// The name of the static that holds the messages waiting to be aggregated.
+const PENDING_IDENT: &str = "__DEBUG_TRY_PENDING";
+
+/// The name of the static that counts the errors of a site since its previous summary.
+const SUMMARY_IDENT: &str = "__DEBUG_TRY_SUMMARY";
+
+/// The name of the static that holds the log file of the `"file"` sink, once it has been opened.
+const FILE_IDENT: &str = "__DEBUG_TRY_FILE";
+
+/// The name of the static that counts the errors propagated in the function, for `max_errors`.
+const ERRORS_IDENT: &str = "__DEBUG_TRY_ERRORS";
+
+/// The environment variable that overrides the path of the log file of the `"file"` sink.
+const FILE_ENV: &str = "DEBUG_TRY_FILE";
+
+/// The path of the log file of the `"file"` sink, if neither `sink_path` nor the environment
+/// variable is set.
+const DEFAULT_FILE_PATH: &str = "debug_try.log";
+
+/// The path of the trace file of the `"chrome_trace"` sink, if neither `sink_path` nor the
+/// environment variable is set.
+const DEFAULT_TRACE_PATH: &str = "debug_try.trace.json";
+
+/// Generates the statements that run when the instrumented function with the given name is
+/// entered, which is `async` if `frame` is false.
+pub(crate) fn entry_tokens(args: &DebugTryArgs, function: &str, frame: bool) -> TokenStream {
+    let mut tokens = TokenStream::new();
+
+    // an `async` function is not entered as a frame, since other tasks run on the thread while it
+    // waits
+    if args.callers.unwrap_or(false) && frame {
+        tokens.extend(quote! {
+            let __debug_try_frame = ::debug_try::__private::enter_frame(#function);
+        });
+    }
+
+    if args.elapsed.unwrap_or(false) {
+        let start = Ident::new(START_IDENT, Span::call_site());
+        tokens.extend(quote! {
+            let #start = ::std::time::Instant::now();
+        });
+    }
+
+    if args.max_errors.is_some() {
+        let errors = Ident::new(ERRORS_IDENT, Span::call_site());
+        tokens.extend(quote! {
+            static #errors: ::core::sync::atomic::AtomicUsize =
+                ::core::sync::atomic::AtomicUsize::new(0);
+        });
+    }
+
+    if args.handler.is_some() {
+        return tokens;
+    }
+
+    if args.indent.unwrap_or(false) {
+        tokens.extend(quote! {
+            let __debug_try_depth = ::debug_try::__private::enter();
+        });
+    }
+
+    if args.aggregate_ms.is_some() {
+        let pending = Ident::new(PENDING_IDENT, Span::call_site());
+        tokens.extend(quote! {
+            static #pending: ::debug_try::__private::Aggregated =
+                ::debug_try::__private::Aggregated::new();
+        });
+    }
+
+    if let (Backend::Stderr, Sink::ChromeTrace, true) = (
+        args.backend(),
+        args.sink.unwrap_or(Sink::Stderr),
+        args.elapsed.unwrap_or(false),
+    ) {
+        let path = trace_path(args);
+        tokens.extend(quote! {
+            let __debug_try_trace_span = ::debug_try::__private::trace_span(#path, #function);
+        });
+    }
+
+    if let (Backend::Stderr, Sink::File) = (args.backend(), args.sink.unwrap_or(Sink::Stderr)) {
+        let file = Ident::new(FILE_IDENT, Span::call_site());
+        tokens.extend(quote! {
+            static #file: ::std::sync::OnceLock<
+                ::std::option::Option<::std::sync::Mutex<::std::fs::File>>,
+            > = ::std::sync::OnceLock::new();
+        });
+    }
+
+    tokens
+}
+
+/// Generates the statements that report an error, which is bound to `__debug_try_err`, propagated at
+/// the given site.
+pub(crate) fn report_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    // with `format = "auto"`, the format is chosen at run time, so both messages are generated
+    if let Some(Format::Auto) = args.format {
+        let with_format = |format| DebugTryArgs {
+            format: Some(format),
+            ..args.clone()
+        };
+        let text = report_tokens(&with_format(Format::Text), site);
+        let json = report_tokens(&with_format(Format::Json), site);
+        return quote! {
+            if ::std::io::IsTerminal::is_terminal(&::std::io::stderr()) {
+                #text
+            } else {
+                #json
+            }
+        };
+    }
+
+    let (setup, mut report) = message_tokens(args, site);
+
+    // checked at the operator, so that an error type without `Display` is reported there rather
+    // than in the generated code that formats it
+    let setup = match (&args.handler, args.backend()) {
+        _ if args.bound == Some(Bound::None) => setup,
+        (Some(_), _) | (None, Backend::Defmt) => setup,
+        (None, _) => {
+            let require_display = quote_spanned! {located_at(site.span)=>
+                ::debug_try::__private::require_display(&__debug_try_err);
+            };
+            quote! {
+                #require_display
+                #setup
+            }
+        }
+    };
+
+    // the trap comes after the message, so that it is visible when the debugger stops, unless the
+    // message is a panic
+    if args.break_on_error.unwrap_or(false) {
+        let breakpoint = quote! { ::debug_try::__private::breakpoint(); };
+        report = match args.panic_on_error {
+            Some(true) => quote! { #breakpoint #report },
+            _ => quote! { #report #breakpoint },
+        };
+    }
+    if let Some(pause) = args.pause_on_error {
+        let pause = pause_tokens(args, site, pause);
+        report = match args.panic_on_error {
+            Some(true) => quote! { #pause #report },
+            _ => quote! { #report #pause },
+        };
+    }
+
+    // an error that a callee has reported is only reported again in full if asked to
+    report = match args.repropagated.unwrap_or(Repropagated::Report) {
+        Repropagated::Report => report,
+        Repropagated::Compact => {
+            let compact = compact_tokens(args, site);
+            quote! {
+                if ::debug_try::__private::mark_reported() {
+                    #compact
+                } else {
+                    #report
+                }
+            }
+        }
+        Repropagated::Quiet => quote! {
+            if !::debug_try::__private::mark_reported() {
+                #report
+            }
+        },
+    };
+
+    // the hook and the messages, including the compact line, are emitted under the same lock
+    if args.lock_output.unwrap_or(false) {
+        report = quote! {
+            let __debug_try_output = ::debug_try::__private::lock_output();
+            #report
+        };
+    }
+
+    // the verbosity set at run time adds to the messages, or turns them off, except for panics
+    if runtime_verbosity(args) {
+        // the multi-line layout already lists the sources
+        let error = match args.multiline() {
+            true => quote!(::core::option::Option::None),
+            false => chained_tokens(),
+        };
+        report = quote! {
+            let __debug_try_verbose = ::debug_try::__private::Verbose::new(__debug_try_verbosity, #error);
+            #report
+        };
+    }
+    if !args.no_std.unwrap_or(false) {
+        report = match args.panic_on_error {
+            Some(true) => quote! {
+                let __debug_try_verbosity = ::debug_try::__private::verbosity();
+                #report
+            },
+            _ => quote! {
+                let __debug_try_verbosity = ::debug_try::__private::verbosity();
+                if __debug_try_verbosity != ::core::option::Option::Some(0) {
+                    #report
+                }
+            },
+        };
+    }
+
+    // the rate limit and `once` are checked last, so that they only count errors that pass the
+    // filters
+    if args.once.unwrap_or(false) {
+        report = quote! {
+            static __DEBUG_TRY_REPORTED: ::core::sync::atomic::AtomicBool =
+                ::core::sync::atomic::AtomicBool::new(false);
+            if !__DEBUG_TRY_REPORTED.swap(true, ::core::sync::atomic::Ordering::Relaxed) {
+                #report
+            }
+        };
+    }
+
+    // the other propagations of the site are only counted, so that the message can tell how many
+    // were left out since the previous one
+    if let Some(n) = args.sample {
+        report = quote! {
+            static __DEBUG_TRY_SAMPLED: ::core::sync::atomic::AtomicUsize =
+                ::core::sync::atomic::AtomicUsize::new(0);
+            let __debug_try_sampled = __DEBUG_TRY_SAMPLED.fetch_add(1, ::core::sync::atomic::Ordering::Relaxed);
+            if __debug_try_sampled % #n == 0 {
+                let __debug_try_suppressed = ::core::cmp::min(__debug_try_sampled, #n - 1);
+                #report
+            }
+        };
+    }
+
+    // a handler or a panic gets every error
+    if args.handler.is_none() && !args.panic_on_error.unwrap_or(false) {
+        report = summary_tokens(args, site, &report);
+    }
+
+    if let Some((count, period_ms)) = args.rate_limit {
+        report = quote! {
+            static __DEBUG_TRY_RATE_LIMIT: ::debug_try::__private::RateLimit =
+                ::debug_try::__private::RateLimit::new();
+            if __DEBUG_TRY_RATE_LIMIT.allow(#count, #period_ms) {
+                #report
+            }
+        };
+    }
+
+    if let Some(ref filter) = args.filter {
+        let (file, line, column) = site.location_tokens();
+        report = quote! {
+            if #filter(#file, #line, #column, &__debug_try_err) {
+                #report
+            }
+        };
+    }
+
+    if let Some(ref types) = args.only {
+        let matches = only_tokens(types);
+        report = quote! {
+            if #matches {
+                #report
+            }
+        };
+    }
+
+    // the error is recorded on the span of the function even if it is filtered out, like the
+    // hits are counted
+    let setup = match args.span {
+        Some(true) => {
+            let printed = printed_tokens(args);
+            quote! {
+                #setup
+                ::tracing::Span::current().record("error", ::tracing::field::display(&#printed));
+            }
+        }
+        _ => setup,
+    };
+
+    // hits are counted even for errors that are filtered out
+    let report = quote! {
+        #setup
+        #report
+    };
+
+    // the environment is only read once per site, so that a disabled site costs a single branch
+    let report = match args.env_switch {
+        Some(ref name) => quote! {
+            static __DEBUG_TRY_ENABLED: ::std::sync::OnceLock<bool> = ::std::sync::OnceLock::new();
+            let __debug_try_enabled = *__DEBUG_TRY_ENABLED.get_or_init(|| {
+                ::std::env::var_os(#name).map_or(true, |value| value != "0")
+            });
+            if __debug_try_enabled {
+                #report
+            }
+        },
+        None => report,
+    };
+
+    let report = if args.site_lists.unwrap_or(false) {
+        let (file, line, _) = site.location_tokens();
+        let id = site.id;
+        quote! {
+            if ::debug_try::__private::site_enabled(#file, #line, #id) {
+                #report
+            }
+        }
+    } else {
+        report
+    };
+
+    let report = if args.module_filter.unwrap_or(false) {
+        quote! {
+            if ::debug_try::__private::module_enabled(::core::module_path!()) {
+                #report
+            }
+        }
+    } else {
+        report
+    };
+
+    let report = if args.thread_switch.unwrap_or(false) {
+        quote! {
+            if ::debug_try::__private::thread_enabled() {
+                #report
+            }
+        }
+    } else {
+        report
+    };
+
+    // the site is recorded even if the message is turned off
+    let report = if args.record_site.unwrap_or(false) {
+        let (file, line, column) = site.location_tokens();
+        quote! {
+            ::debug_try::__private::record_site(#file, #line, #column);
+            #report
+        }
+    } else {
+        report
+    };
+
+    // the limit counts every propagation, and is checked after the error has been reported
+    match args.max_errors {
+        Some(max) => {
+            let errors = Ident::new(ERRORS_IDENT, Span::call_site());
+            let function = &site.function;
+            quote! {
+                #report
+                if #errors.fetch_add(1, ::core::sync::atomic::Ordering::Relaxed) >= #max {
+                    panic!("More than {} errors propagated in `{}`", #max, #function);
+                }
+            }
+        }
+        None => report,
+    }
+}
+
+/// Generates the expression that wraps the error of the result of `expr` in an `eyre::Report` with
+/// the location of the site, so that the locations of all the sites that propagate an error are
+/// listed in the report.
+pub(crate) fn eyre_tokens(site: &Site, expr: &TokenStream) -> TokenStream {
+    let message = if site.runtime {
+        let (file, line, column) = site.location_tokens();
+        let message = format!("Propagated at {{}}:{{}}:{{}} in `{}`", site.function);
+        quote!(format!(#message, #file, #line, #column))
+    } else {
+        let message = format!("Propagated at {} in `{}`", site.location, site.function);
+        quote!(#message)
+    };
+    quote! {
+        ::eyre::WrapErr::wrap_err_with(#expr, || #message)
+    }
+}
+
+/// Generates the expression that wraps the error in a `Located` with the location of the site.
+pub(crate) fn locate_tokens(site: &Site, err: &TokenStream) -> TokenStream {
+    let (file, line, column) = site.location_tokens();
+    quote! {{
+        use ::debug_try::__private::{AlreadyLocated as _, NotLocated as _};
+        (&&::debug_try::__private::Locate(::core::cell::Cell::new(::core::option::Option::Some(#err))))
+            .locate(#file, #line, #column)
+    }}
+}
+
+/// Generates the statements that emit the short message of an error that has already been
+/// reported by a callee. Only the `"stderr"`, `"log"` and `"tracing"` backends emit one.
+/// Moves the statements that report an error at a site into a closure that is run out of line, by
+/// a `#[cold]` function that is never inlined, so that they do not bloat the function or keep it
+/// from being inlined.
+pub(crate) fn cold_tokens(report: &TokenStream) -> TokenStream {
+    if report.is_empty() {
+        return TokenStream::new();
+    }
+    quote! {
+        ::debug_try::__private::cold(|| {
+            #report
+        });
+    }
+}
+
+fn compact_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    if args.handler.is_some() || args.aggregate_ms.is_some() {
+        return TokenStream::new();
+    }
+
+    let format = args.format.unwrap_or(Format::Text);
+    let compact_str = match format {
+        Format::Text | Format::Auto => format!(
+            "{}{}Error re-propagated ({})",
+            indent_str(args),
+            prefix_str(args),
+            site.location_str()
+        ),
+        Format::Json => {
+            let string = |text: &str| escape_format(&json_string(text));
+            let (file, line, column) = json_location(site);
+            format!(
+                "{{{{{}\"file\":{},\"line\":{},\"column\":{},\"fn\":{},\"repropagated\":true}}}}",
+                json_prefix(args),
+                file,
+                line,
+                column,
+                string(&site.function),
+            )
+        }
+        Format::Logfmt => {
+            let (file, line, column) = logfmt_location(site);
+            format!(
+                "{}level={} msg=re-propagated file={} line={} column={} fn={}",
+                logfmt_prefix(args),
+                level_name(compact_level(args)),
+                file,
+                line,
+                column,
+                escape_format(&logfmt_value(&site.function)),
+            )
+        }
+        Format::Rustc => format!(
+            "{}{}note: error re-propagated in `{}`\n  --> {}",
+            indent_str(args),
+            prefix_str(args),
+            escape_format(&site.function),
+            site.rustc_location_str()
+        ),
+    };
+    let mut values = site.location_values(format);
+    if let Format::Text | Format::Rustc = format {
+        values.splice(0..0, indent_value(args));
+    }
+    let values = quote!(#(#values),*);
+    let args_tokens = if values.is_empty() {
+        TokenStream::new()
+    } else {
+        quote!(, #values)
+    };
+
+    let compact_macro = level_macro(compact_level(args));
+    let target = target_tokens(args);
+    match args.backend() {
+        Backend::Stderr => match console_tokens(args) {
+            Some((print_macro, stream)) => {
+                print_tokens(&print_macro, stream.as_ref(), &compact_str, &values)
+            }
+            None => sink_tokens(args, Some(site), &compact_str, &values),
+        },
+        Backend::Log => quote! {
+            ::log::#compact_macro!(#target #compact_str #args_tokens);
+        },
+        Backend::Tracing => quote! {
+            ::tracing::#compact_macro!(#target #compact_str #args_tokens);
+        },
+        Backend::Slog => {
+            let logger = &args.slog;
+            quote! {
+                ::slog::#compact_macro!(#logger, #compact_str #args_tokens);
+            }
+        }
+        Backend::Wasm => wasm_tokens(
+            compact_level(args),
+            &quote!(&format!(#compact_str #args_tokens)),
+        ),
+        Backend::Defmt
+        | Backend::Sentry
+        | Backend::OpenTelemetry
+        | Backend::Usdt
+        | Backend::None => TokenStream::new(),
+    }
+}
+
+/// Generates an expression that tells whether the error is one of the given error types.
+///
+/// Boxed errors and similar types that can be borrowed as a `dyn Error` are downcast, and other
+/// errors are compared by their type, if it is `'static`. Like in [`chain_tokens`], the first of
+/// these that applies is chosen with autoderef-based specialization.
+fn only_tokens(types: &[Type]) -> TokenStream {
+    let is = types.iter().map(|ty| quote!(|| err.is::<#ty>()));
+    let is = quote!(#(#is)*);
+    let ids = types
+        .iter()
+        .map(|ty| quote!(|| id == ::std::any::TypeId::of::<#ty>()));
+    let ids = quote!(#(#ids)*);
+
+    quote! {{
+        struct Only<'a, __E>(&'a __E);
+        trait ViaAsRef {
+            fn matches(&self) -> bool;
+        }
+        impl<__E: ::std::convert::AsRef<dyn ::std::error::Error>> ViaAsRef for &&&Only<'_, __E> {
+            fn matches(&self) -> bool {
+                let err: &dyn ::std::error::Error = self.0.as_ref();
+                false #is
+            }
+        }
+        trait ViaAsRefSendSync {
+            fn matches(&self) -> bool;
+        }
+        impl<__E> ViaAsRefSendSync for &&Only<'_, __E>
+        where
+            __E: ::std::convert::AsRef<dyn ::std::error::Error + ::std::marker::Send + ::std::marker::Sync>,
+        {
+            fn matches(&self) -> bool {
+                let err: &(dyn ::std::error::Error + ::std::marker::Send + ::std::marker::Sync) =
+                    self.0.as_ref();
+                false #is
+            }
+        }
+        trait ViaTypeId {
+            fn matches(&self) -> bool;
+        }
+        impl<__E: 'static> ViaTypeId for &Only<'_, __E> {
+            fn matches(&self) -> bool {
+                let id = ::std::any::TypeId::of::<__E>();
+                false #ids
+            }
+        }
+        trait Fallback {
+            fn matches(&self) -> bool;
+        }
+        impl<__E> Fallback for Only<'_, __E> {
+            fn matches(&self) -> bool {
+                false
+            }
+        }
+
+        (&&&&Only(&__debug_try_err)).matches()
+    }}
+}
+
+/// Generates the statements that emit the message of an error propagated at the given site, and
+/// the statements that run before, whether or not the message is emitted.
+fn message_tokens(args: &DebugTryArgs, site: &Site) -> (TokenStream, TokenStream) {
+    if let Some(report) = shared_tokens(args, site) {
+        return (TokenStream::new(), report);
+    }
+
+    let plain_str = format_str(args, site, Style::Plain);
+    let colored_str = format_str(args, site, Style::Colored);
+
+    // statements that run before the message is formatted, and the values for the placeholders,
+    // in the same order as in the format string
+    let mut setup = TokenStream::new();
+    // messages like those of rustc have the location after the error and the fields
+    let format = args.format.unwrap_or(Format::Text);
+    let mut values = match format {
+        Format::Rustc => Vec::new(),
+        _ => site.location_values(format),
+    };
+    if let Format::Text | Format::Rustc = format {
+        values.splice(0..0, indent_value(args));
+    }
+
+    let counts = args.counts.unwrap_or(false);
+    if args.site_ids.unwrap_or(false) || counts {
+        setup.extend(quote! {
+            static __DEBUG_TRY_HITS: ::core::sync::atomic::AtomicUsize =
+                ::core::sync::atomic::AtomicUsize::new(0);
+            let __debug_try_hits = __DEBUG_TRY_HITS.fetch_add(1, ::core::sync::atomic::Ordering::Relaxed) + 1;
+        });
+    }
+    if args.site_ids.unwrap_or(false) {
+        values.push(quote!(__debug_try_hits));
+    }
+    if args.sample.is_some() {
+        values.push(quote!(__debug_try_suppressed));
+    }
+    if counts {
+        values.push(quote!(__debug_try_hits));
+    }
+    if args.error_ids.unwrap_or(false) {
+        setup.extend(quote! {
+            let __debug_try_error_id = ::debug_try::__private::error_id();
+        });
+        values.push(quote!(__debug_try_error_id));
+    }
+    if args.host_info.unwrap_or(false) {
+        setup.extend(quote! {
+            let __debug_try_host = ::debug_try::__private::host();
+        });
+        values.push(match format {
+            Format::Json | Format::Logfmt => {
+                quote!(::debug_try::__private::Json(__debug_try_host.0))
+            }
+            _ => quote!(__debug_try_host.0),
+        });
+        values.push(quote!(__debug_try_host.1));
+    }
+    if args.task_ids.unwrap_or(false) {
+        setup.extend(quote! {
+            let __debug_try_task = ::debug_try::__private::task_id();
+        });
+        values.push(match format {
+            Format::Json => quote!(__debug_try_task.json()),
+            _ => quote!(__debug_try_task),
+        });
+    }
+    if args.callers.unwrap_or(false) {
+        let function = &site.function;
+        setup.extend(quote! {
+            let __debug_try_callers = ::debug_try::__private::callers(#function);
+        });
+        values.push(match format {
+            Format::Json => quote!(__debug_try_callers.json()),
+            _ => quote!(__debug_try_callers),
+        });
+    }
+    if args.context.unwrap_or(false) {
+        setup.extend(quote! {
+            let __debug_try_context = ::debug_try::__private::context();
+        });
+        values.push(match format {
+            Format::Json => quote!(__debug_try_context.json()),
+            Format::Logfmt => quote!(::debug_try::__private::Json(&__debug_try_context)),
+            _ => quote!(__debug_try_context),
+        });
+    }
+    if args.delta.unwrap_or(false) {
+        setup.extend(quote! {
+            let __debug_try_delta = ::debug_try::__private::delta();
+        });
+    }
+    if args.metrics.unwrap_or(false) {
+        let (file, line, _) = site.location_tokens();
+        let line = match site.runtime {
+            true => quote!(::std::string::ToString::to_string(&#line)),
+            false => site.line.to_string().into_token_stream(),
+        };
+        let function = &site.function;
+        let group = site.group.iter();
+        setup.extend(quote! {
+            ::metrics::counter!(
+                "debug_try_propagations_total",
+                "file" => #file,
+                "line" => #line,
+                "function" => #function,
+                #("group" => #group,)*
+            )
+            .increment(1);
+        });
+    }
+    if args.elapsed.unwrap_or(false) {
+        let start = Ident::new(START_IDENT, Span::call_site());
+        values.push(quote!(#start.elapsed()));
+    }
+    if args.timing.unwrap_or(false) {
+        values.push(quote!(__debug_try_duration));
+    }
+    if args.delta.unwrap_or(false) {
+        values.push(quote!(__debug_try_delta));
+    }
+    match format {
+        Format::Text | Format::Rustc | Format::Auto => {
+            values.push(display_tokens(args));
+            if args.io_details.unwrap_or(false) {
+                let io_error = static_error_tokens();
+                values.push(quote!(::debug_try::__private::IoText(#io_error)));
+            }
+            values.extend(type_values(args, site));
+            // the multi-line layout lists the sources right below the error, before the fields
+            let chain = sources_tokens(args);
+            if args.multiline() {
+                values.push(quote!(::debug_try::__private::CausedBy(#chain)));
+            }
+            for field in args.all_fields() {
+                values.push(quote!(&(#field)));
+            }
+            if let Format::Rustc = format {
+                values.extend(site.location_values(format));
+            }
+            if args.verbosity() == Verbosity::Full && !args.multiline() {
+                values.push(quote!(::debug_try::__private::Causes(#chain)));
+            }
+            if args.debug_repr.unwrap_or(false) {
+                values.push(quote!(__debug_try_err));
+            }
+            if args.verbosity() == Verbosity::Full {
+                values.push(quote!(::std::backtrace::Backtrace::force_capture()));
+            }
+            if runtime_verbosity(args) {
+                values.push(quote!(__debug_try_verbose));
+            }
+        }
+        Format::Json | Format::Logfmt => {
+            // the values are formatted first, and then escaped as the contents of JSON strings,
+            // which is also how logfmt escapes quoted values
+            let (display, debug) = placeholders(args);
+            let err = unsanitized_display_tokens(args);
+            values.push(quote!(::debug_try::__private::Json(
+                format_args!(#display, #err)
+            )));
+            if args.io_details.unwrap_or(false) {
+                let io_error = static_error_tokens();
+                values.push(match format {
+                    Format::Logfmt => quote!(::debug_try::__private::IoLogfmt(#io_error)),
+                    _ => quote!(::debug_try::__private::IoJson(#io_error)),
+                });
+            }
+            values.extend(type_values(args, site));
+            for field in args.all_fields() {
+                values.push(quote!(::debug_try::__private::Json(
+                    format_args!("{:?}", &(#field))
+                )));
+            }
+            if args.debug_repr.unwrap_or(false) {
+                values.push(quote!(::debug_try::__private::Json(
+                    format_args!(#debug, __debug_try_err)
+                )));
+            }
+        }
+    }
+    let values = quote!(#(#values),*);
+
+    // with `redact`, the message is formatted first and then passed through the redactor, so that
+    // every backend emits the redacted message
+    let (plain_str, colored_str, values, colored_values) = if args.redact.unwrap_or(false) {
+        let plain = quote!(::debug_try::__private::Redacted(
+            format_args!(#plain_str, #values)
+        ));
+        let colored = quote!(::debug_try::__private::Redacted(
+            format_args!(#colored_str, #values)
+        ));
+        ("{}".to_owned(), "{}".to_owned(), plain, colored)
+    } else {
+        (plain_str, colored_str, values.clone(), values)
+    };
+
+    let print = match console_tokens(args) {
+        Some((print_macro, stream)) => {
+            let colored =
+                print_tokens(&print_macro, stream.as_ref(), &colored_str, &colored_values);
+            let plain = print_tokens(&print_macro, stream.as_ref(), &plain_str, &values);
+            match (args.color.unwrap_or(Color::Auto), stream) {
+                (Color::Always, _) => colored,
+                (Color::Auto, Some(stream)) => quote! {
+                    if ::std::io::IsTerminal::is_terminal(&#stream)
+                        && ::std::env::var_os("NO_COLOR").map_or(true, |value| value.is_empty())
+                    {
+                        #colored
+                    } else {
+                        #plain
+                    }
+                },
+                // a custom print macro may not print to a terminal
+                (Color::Auto, None) | (Color::Never, _) => plain,
+            }
+        }
+        None => sink_tokens(args, Some(site), &plain_str, &values),
+    };
+
+    let console = match args.wasi_logging {
+        Some(ref logging) => {
+            let wasi_level = match backend_level(args) {
+                Level::Error => "Error",
+                Level::Warn => "Warn",
+                Level::Info => "Info",
+                Level::Debug => "Debug",
+                Level::Trace => "Trace",
+            };
+            let wasi_level = Ident::new(wasi_level, Span::call_site());
+            let context = args.target.as_ref().map_or("debug_try", String::as_str);
+            quote! {
+            #[cfg(all(target_os = "wasi", target_env = "p2"))]
+            {
+                #logging::log(
+                    #logging::Level::#wasi_level,
+                    #context,
+                    &format!(#plain_str, #values),
+                );
+            }
+            #[cfg(not(all(target_os = "wasi", target_env = "p2")))]
+            {
+                #print
+            }
+            }
+        }
+        None => print,
+    };
+
+    let panics = args.panic_on_error.unwrap_or(false);
+    let report = match (&args.handler, args.aggregate_ms) {
+        _ if panics => quote! {
+            panic!(#plain_str, #values);
+        },
+        (Some(handler), _) => {
+            let (file, line, column) = site.location_tokens();
+            quote! {
+                #handler(#file, #line, #column, &__debug_try_err);
+            }
+        }
+        (None, Some(ms)) => aggregate_tokens(args, ms, &plain_str, &values),
+        (None, None) => match args.backend() {
+            Backend::Stderr => console,
+            Backend::Log if args.log_kv.unwrap_or(false) => log_kv_tokens(args, site),
+            Backend::Log => {
+                let level = level_macro(backend_level(args));
+                let target = target_tokens(args);
+                quote! {
+                    ::log::#level!(#target #plain_str, #values);
+                }
+            }
+            Backend::Tracing => tracing_tokens(args, site),
+            Backend::Sentry => sentry_tokens(args, site, &plain_str, &values),
+            Backend::OpenTelemetry => opentelemetry_tokens(args, site),
+            Backend::Usdt => usdt_tokens(args, site),
+            Backend::Slog => slog_tokens(args, site),
+            Backend::Wasm => {
+                wasm_tokens(backend_level(args), &quote!(&format!(#plain_str, #values)))
+            }
+            Backend::None => TokenStream::new(),
+            Backend::Defmt => {
+                // embedded targets have neither the runtime nor `std::thread::panicking`
+                let defmt = defmt_tokens(args, site);
+                return (setup, defmt);
+            }
+        },
+    };
+
+    // a hook installed at run time takes precedence over the backend, but not over a handler or
+    // a panic
+    let report = match args.handler {
+        _ if panics => report,
+        Some(_) => report,
+        None => {
+            let (file, line, column) = site.location_tokens();
+            let function = &site.function;
+            let printed = printed_tokens(args);
+            quote! {
+                let __debug_try_handled = ::debug_try::__private::dispatch(&::debug_try::__private::Event::new(
+                    #file,
+                    #line,
+                    #column,
+                    #function,
+                    ::core::module_path!(),
+                    &#printed,
+                    ::core::any::type_name_of_val(&__debug_try_err),
+                    format_args!(#plain_str, #values),
+                ));
+                if !__debug_try_handled {
+                    #report
+                }
+            }
+        }
+    };
+
+    // while panicking, a failing sink would cause a double panic and abort the process, so
+    // either skip the message or write it with a plain, non-panicking write to stderr
+    let report = match args.on_panic.unwrap_or(OnPanic::Emergency) {
+        // without `std`, there is no way to tell whether the thread is panicking
+        _ if args.no_std.unwrap_or(false) => report,
+        OnPanic::Emergency => {
+            let emergency_str = format!("{}\n", plain_str);
+            quote! {
+                if ::std::thread::panicking() {
+                    let _ = ::std::io::Write::write_fmt(
+                        &mut ::std::io::stderr(),
+                        format_args!(#emergency_str, #values),
+                    );
+                } else {
+                    #report
+                }
+            }
+        }
+        OnPanic::Skip => quote! {
+            if !::std::thread::panicking() {
+                #report
+            }
+        },
+    };
+
+    (setup, report)
+}
+
+/// Generates a call to the shared function of the runtime that reports an error, if the message
+/// has the default form, i.e. a text message on standard error with nothing but the location and
+/// the error, so that the site does not format the message itself.
+fn shared_tokens(args: &DebugTryArgs, site: &Site) -> Option<TokenStream> {
+    let default = matches!(args.format, None | Some(Format::Text))
+        && matches!(args.backend(), Backend::Stderr)
+        && matches!(args.sink, None | Some(Sink::Stderr))
+        && matches!(args.on_panic, None | Some(OnPanic::Emergency))
+        && args.verbosity() == Verbosity::Normal
+        && !args.multiline()
+        && args.all_fields().is_empty()
+        && args.handler.is_none()
+        && args.aggregate_ms.is_none()
+        && args.print_macro.is_none()
+        && args.wasi_logging.is_none()
+        && args.prefix.is_none()
+        && args.name.is_none()
+        && args.group.is_none()
+        && args.sample.is_none()
+        && args.max_len.is_none()
+        && args.sanitize()
+        && package(args).is_none()
+        && build(args).is_none()
+        && [
+            args.no_std,
+            args.indent,
+            args.panic_on_error,
+            args.site_ids,
+            args.counts,
+            args.error_ids,
+            args.host_info,
+            args.task_ids,
+            args.callers,
+            args.context,
+            args.metrics,
+            args.elapsed,
+            args.timing,
+            args.delta,
+            args.debug_repr,
+            args.io_details,
+            args.formatters,
+            args.redact,
+            args.type_names,
+            args.pretty,
+        ]
+        .iter()
+        .all(|arg| !arg.unwrap_or(false))
+        && site.kind == SiteKind::Try
+        && site.label.is_none()
+        && site.step.is_none()
+        && site.snippet.is_none()
+        && site.link.is_none();
+    if !default {
+        return None;
+    }
+
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+    let color = match args.color.unwrap_or(Color::Auto) {
+        Color::Always => quote!(::core::option::Option::Some(true)),
+        Color::Auto => quote!(::core::option::Option::None),
+        Color::Never => quote!(::core::option::Option::Some(false)),
+    };
+    let printed = printed_tokens(args);
+    Some(quote! {
+        ::debug_try::__private::report(
+            #file,
+            #line,
+            #column,
+            #function,
+            ::core::module_path!(),
+            #color,
+            &#printed,
+            ::core::any::type_name_of_val(&__debug_try_err),
+            &__debug_try_verbose,
+            cfg!(test),
+        );
+    })
+}
+
+/// Returns whether text messages get the sources and the backtrace that the verbosity set at run
+/// time with `DEBUG_TRY_VERBOSITY` asks for, which needs `std`, and which `verbosity = "full"`
+/// already includes. The backends that do not emit the formatted message do not get them.
+fn runtime_verbosity(args: &DebugTryArgs) -> bool {
+    matches!(args.format, None | Some(Format::Text) | Some(Format::Rustc))
+        && !args.no_std.unwrap_or(false)
+        && args.verbosity() != Verbosity::Full
+        && args.handler.is_none()
+        && !(matches!(args.backend(), Backend::Log) && args.log_kv.unwrap_or(false))
+        && !matches!(
+            args.backend(),
+            Backend::Tracing
+                | Backend::OpenTelemetry
+                | Backend::Usdt
+                | Backend::Slog
+                | Backend::Defmt
+                | Backend::None
+        )
+}
+
+/// Generates the expression for the error as an `Option<&dyn std::error::Error>`, which is `None`
+/// if its type is not an error and can not be referenced as one.
+fn chained_tokens() -> TokenStream {
+    quote! {{
+        use ::debug_try::__private::{
+            ChainAsRef as _, ChainAsRefSendSync as _, ChainError as _, ChainOther as _,
+        };
+        (&&&&::debug_try::__private::Chained(&__debug_try_err)).as_error()
+    }}
+}
+
+/// Generates the expression for the error as an `Option<&(dyn std::error::Error + 'static)>`,
+/// which can be downcast, for `io_details` and `formatters`.
+fn static_error_tokens() -> TokenStream {
+    quote! {{
+        use ::debug_try::__private::{
+            StaticAsRef as _, StaticAsRefSendSync as _, StaticError as _, StaticOther as _,
+        };
+        (&&&&::debug_try::__private::Chained(&__debug_try_err)).as_static_error()
+    }}
+}
+
+/// Returns the placeholder for the kind and the code of an I/O error, with `io_details`, which
+/// follows the error in every format.
+fn io_placeholder(args: &DebugTryArgs) -> &'static str {
+    if args.io_details.unwrap_or(false) {
+        "{}"
+    } else {
+        ""
+    }
+}
+
+/// Returns the placeholders for the type of the error and the type that it is converted to, with
+/// `type_names`, which follow those of `io_details`, e.g. ` ({} -> {})` in text messages.
+fn type_placeholder(args: &DebugTryArgs, site: &Site) -> &'static str {
+    if !args.type_names.unwrap_or(false) {
+        return "";
+    }
+    match (args.format.unwrap_or(Format::Text), site.target.is_some()) {
+        (Format::Text, false) | (Format::Rustc, false) | (Format::Auto, false) => " ({})",
+        (Format::Text, true) | (Format::Rustc, true) | (Format::Auto, true) => " ({} -> {})",
+        (Format::Json, false) => ",\"error_type\":\"{}\"",
+        (Format::Json, true) => ",\"error_type\":\"{}\",\"target_type\":\"{}\"",
+        (Format::Logfmt, false) => " error_type=\"{}\"",
+        (Format::Logfmt, true) => " error_type=\"{}\" target_type=\"{}\"",
+    }
+}
+
+/// Generates the values for the placeholders of `type_placeholder`. The names of types have no
+/// quotes or backslashes, so they need no escaping in JSON and logfmt messages.
+fn type_values(args: &DebugTryArgs, site: &Site) -> Vec<TokenStream> {
+    if !args.type_names.unwrap_or(false) {
+        return Vec::new();
+    }
+    let mut values = vec![quote!(::core::any::type_name_of_val(&__debug_try_err))];
+    if let Some(ref target) = site.target {
+        values.push(quote!(::core::any::type_name::<#target>()));
+    }
+    values
+}
+
+/// Returns the macro that prints to the sink of the `"stderr"` backend, and an expression for the
+/// stream if it is known, e.g. `eprint` and `::std::io::stderr()`, or `None` if the sink is not a
+/// console. A macro without a stream is a custom one, which prints a line.
+fn console_tokens(args: &DebugTryArgs) -> Option<(TokenStream, Option<TokenStream>)> {
+    if let Some(ref print_macro) = args.print_macro {
+        return Some((print_macro.into_token_stream(), None));
+    }
+
+    match args.sink.unwrap_or(Sink::Stderr) {
+        Sink::Stderr => Some((quote!(eprint), Some(quote!(::std::io::stderr())))),
+        Sink::Stdout => Some((quote!(print), Some(quote!(::std::io::stdout())))),
+        Sink::File
+        | Sink::Capture
+        | Sink::Logcat
+        | Sink::OsLog
+        | Sink::Journald
+        | Sink::Etw
+        | Sink::ChromeTrace => None,
+    }
+}
+
+/// Generates the statement that prints a line with the given print macro and stream, as returned
+/// by `console_tokens`. If the stream is known, the line is formatted into a string first, which
+/// is written to the stream at once, so that the messages of threads that fail at the same time
+/// are not interleaved. Errors writing it are ignored, since the print macros panic when the stream
+/// is closed or a broken pipe, except in unit tests, where the macro is used after all, since that
+/// is what the test harness captures.
+fn print_tokens(
+    print_macro: &TokenStream,
+    stream: Option<&TokenStream>,
+    format: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    let args = if values.is_empty() {
+        TokenStream::new()
+    } else {
+        quote!(, #values)
+    };
+    match stream {
+        Some(stream) => {
+            let line_str = format!("{}\n", format);
+            quote! {
+                let __debug_try_line = format!(#line_str #args);
+                if cfg!(test) {
+                    #print_macro!("{}", __debug_try_line);
+                } else {
+                    let _ = ::std::io::Write::write_all(&mut #stream, __debug_try_line.as_bytes());
+                }
+            }
+        }
+        None => quote! {
+            #print_macro!(#format #args);
+        },
+    }
+}
+
+/// Generates the statements that write a message to a sink of the `"stderr"` backend that is not
+/// a console.
+fn sink_tokens(
+    args: &DebugTryArgs,
+    site: Option<&Site>,
+    format: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    match args.sink.unwrap_or(Sink::Stderr) {
+        Sink::Capture => quote! {
+            ::debug_try::__private::capture(format_args!(#format, #values));
+        },
+        Sink::Logcat => logcat_tokens(args, format, values),
+        Sink::OsLog => os_log_tokens(args, format, values),
+        Sink::Journald => journald_tokens(args, site, format, values),
+        Sink::Etw => etw_tokens(args, site, format, values),
+        Sink::ChromeTrace => chrome_trace_tokens(args, site, format, values),
+        _ => file_tokens(args, format, values),
+    }
+}
+
+/// Generates the statements that append a line to the log file of the `"file"` sink, which is
+/// opened when the first message of the function is written. If the file can not be opened, the
+/// messages are printed to standard error instead.
+fn file_tokens(args: &DebugTryArgs, format: &str, values: &TokenStream) -> TokenStream {
+    let file = Ident::new(FILE_IDENT, Span::call_site());
+    let line_str = format!("{}\n", format);
+    let stderr = print_tokens(
+        &quote!(eprint),
+        Some(&quote!(::std::io::stderr())),
+        format,
+        values,
+    );
+    let path = args
+        .sink_path
+        .as_ref()
+        .map_or(DEFAULT_FILE_PATH, String::as_str);
+
+    quote! {
+        let __debug_try_file = #file.get_or_init(|| {
+            let path = ::std::env::var_os(#FILE_ENV)
+                .unwrap_or_else(|| ::std::ffi::OsString::from(#path));
+            ::std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .ok()
+                .map(::std::sync::Mutex::new)
+        });
+        match __debug_try_file {
+            ::std::option::Option::Some(__debug_try_file) => {
+                // formatted before the lock, since the error may report another error when displayed
+                let __debug_try_line = format!(#line_str, #values);
+                let mut __debug_try_file = __debug_try_file.lock().unwrap_or_else(|err| err.into_inner());
+                let _ = ::std::io::Write::write_all(
+                    &mut *__debug_try_file,
+                    __debug_try_line.as_bytes(),
+                );
+            }
+            ::std::option::Option::None => {
+                #stderr
+            }
+        }
+    }
+}
+
+/// Generates the statements that write a message to the Android log with `__android_log_write`,
+/// with the target as the tag. On other targets, the message is printed to standard error instead.
+fn logcat_tokens(args: &DebugTryArgs, format: &str, values: &TokenStream) -> TokenStream {
+    let priority = match backend_level(args) {
+        Level::Error => "ERROR",
+        Level::Warn => "WARN",
+        Level::Info => "INFO",
+        Level::Debug => "DEBUG",
+        Level::Trace => "VERBOSE",
+    };
+    let priority = Ident::new(priority, Span::call_site());
+    let tag = args.target.as_ref().map_or("debug_try", String::as_str);
+    let tag = LitByteStr::new(
+        format!("{}\0", tag.replace('\0', "")).as_bytes(),
+        Span::call_site(),
+    );
+    let stderr = print_tokens(
+        &quote!(eprint),
+        Some(&quote!(::std::io::stderr())),
+        format,
+        values,
+    );
+
+    quote! {
+        #[cfg(target_os = "android")]
+        {
+            let __debug_try_message = ::std::ffi::CString::new(
+                format!(#format, #values).replace('\0', ""),
+            )
+            .unwrap_or_default();
+            unsafe {
+                ::android_log_sys::__android_log_write(
+                    ::android_log_sys::LogPriority::#priority as ::std::os::raw::c_int,
+                    #tag.as_ptr() as *const ::std::os::raw::c_char,
+                    __debug_try_message.as_ptr(),
+                );
+            }
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            #stderr
+        }
+    }
+}
+
+/// Generates the statements that write a message to the unified log of Apple platforms with
+/// `os_log`, with the target as the subsystem. On other targets, the message is printed to standard
+/// error instead.
+fn os_log_tokens(args: &DebugTryArgs, format: &str, values: &TokenStream) -> TokenStream {
+    // the unified log has no warning level, and `Default` is the one between info and error
+    let level = match backend_level(args) {
+        Level::Error => "Error",
+        Level::Warn => "Default",
+        Level::Info => "Info",
+        Level::Debug | Level::Trace => "Debug",
+    };
+    let level = Ident::new(level, Span::call_site());
+    let subsystem = args.target.as_ref().map_or("debug_try", String::as_str);
+    let stderr = print_tokens(
+        &quote!(eprint),
+        Some(&quote!(::std::io::stderr())),
+        format,
+        values,
+    );
+
+    quote! {
+        #[cfg(target_vendor = "apple")]
+        {
+            ::oslog::OsLog::new(#subsystem, "debug_try")
+                .with_level(::oslog::Level::#level, &format!(#format, #values));
+        }
+        #[cfg(not(target_vendor = "apple"))]
+        {
+            #stderr
+        }
+    }
+}
+
+/// Generates the statements that write a message to the systemd journal with its native protocol,
+/// with the level as the `PRIORITY`, the location of the site, if the message is about a single
+/// site, as `CODE_FILE`, `CODE_LINE` and `CODE_FUNC`, the target as the `SYSLOG_IDENTIFIER`, and
+/// the fields as fields of their own. If there is no journal, e.g. on other targets, or when the
+/// program does not run under systemd, the message is printed to standard error instead.
+fn journald_tokens(
+    args: &DebugTryArgs,
+    site: Option<&Site>,
+    format: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    let priority: u8 = match backend_level(args) {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    };
+    let mut fields = Vec::new();
+    if let Some(site) = site {
+        let (file, line, column) = site.location_tokens();
+        let function = &site.function;
+        fields.push(quote!(("CODE_FILE", &#file)));
+        fields.push(quote!(("CODE_LINE", &#line)));
+        fields.push(quote!(("CODE_COLUMN", &#column)));
+        fields.push(quote!(("CODE_FUNC", &#function)));
+        for field in args.all_fields() {
+            let name = journal_field_name(&expr_text(field));
+            fields.push(quote!((#name, &format_args!("{:?}", &(#field)))));
+        }
+    }
+    if let Some(ref target) = args.target {
+        fields.push(quote!(("SYSLOG_IDENTIFIER", &#target)));
+    }
+    let stderr = print_tokens(
+        &quote!(eprint),
+        Some(&quote!(::std::io::stderr())),
+        format,
+        values,
+    );
+
+    quote! {
+        #[cfg(target_os = "linux")]
+        {
+            let __debug_try_journal = ::debug_try::__private::journal(
+                #priority,
+                format_args!(#format, #values),
+                &[#(#fields),*],
+            );
+            if __debug_try_journal.is_err() {
+                #stderr
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            #stderr
+        }
+    }
+}
+
+/// Returns the name of the journal field of an expression of `fields(...)`, e.g. `ORDER_ID` for
+/// `order_id` and `CFG_PATH__` for `cfg.path()`, since journal fields are made of uppercase
+/// letters, digits and underscores, and can not start with an underscore or a digit.
+fn journal_field_name(text: &str) -> String {
+    let name: String = text
+        .chars()
+        .map(|ch| match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => ch.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    match name.trim_start_matches(|ch: char| ch == '_' || ch.is_ascii_digit()) {
+        "" => "FIELD".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+/// Generates the statements that write a message as an event of the `debug_try` provider of
+/// Event Tracing for Windows, with the level, the location of the site, if the message is about a
+/// single site, the target and the fields as fields of the event. On other targets, the message is
+/// printed to standard error instead.
+fn etw_tokens(
+    args: &DebugTryArgs,
+    site: Option<&Site>,
+    format: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    let level: u8 = match backend_level(args) {
+        Level::Error => 2,
+        Level::Warn => 3,
+        Level::Info => 4,
+        Level::Debug | Level::Trace => 5,
+    };
+    let site = match site {
+        Some(site) => {
+            let (file, line, column) = site.location_tokens();
+            let function = &site.function;
+            quote!(::std::option::Option::Some((#file, #line, #column, #function)))
+        }
+        None => quote!(::std::option::Option::None),
+    };
+    let target = args.target.as_ref().map_or("debug_try", String::as_str);
+    let fields = args.all_fields().into_iter().map(|field| {
+        let name = expr_text(field);
+        quote!((#name, &format_args!("{:?}", &(#field))))
+    });
+    let stderr = print_tokens(
+        &quote!(eprint),
+        Some(&quote!(::std::io::stderr())),
+        format,
+        values,
+    );
+
+    quote! {
+        #[cfg(windows)]
+        {
+            ::debug_try::__private::etw(
+                #level,
+                format_args!(#format, #values),
+                #site,
+                #target,
+                &[#(#fields),*],
+            );
+        }
+        #[cfg(not(windows))]
+        {
+            #stderr
+        }
+    }
+}
+
+/// Generates the statements that write a message as an instant event to the trace file of the
+/// `"chrome_trace"` sink, with the location of the site, if the message is about a single site, as
+/// its arguments. If the file can not be created, the message is printed to standard error
+/// instead.
+fn chrome_trace_tokens(
+    args: &DebugTryArgs,
+    site: Option<&Site>,
+    format: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    let path = trace_path(args);
+    let (name, site) = match site {
+        Some(site) => {
+            let (file, line, column) = site.location_tokens();
+            let function = &site.function;
+            (
+                site.headline(),
+                quote!(::std::option::Option::Some((#file, #line, #column, #function))),
+            )
+        }
+        None => ("debug_try", quote!(::std::option::Option::None)),
+    };
+    let stderr = print_tokens(
+        &quote!(eprint),
+        Some(&quote!(::std::io::stderr())),
+        format,
+        values,
+    );
+
+    quote! {
+        if !::debug_try::__private::trace_event(#path, #name, format_args!(#format, #values), #site) {
+            #stderr
+        }
+    }
+}
+
+/// Returns the path of the trace file of the `"chrome_trace"` sink, given with `sink_path`.
+fn trace_path(args: &DebugTryArgs) -> &str {
+    args.sink_path
+        .as_ref()
+        .map_or(DEFAULT_TRACE_PATH, String::as_str)
+}
+
+/// Generates the statement that queues a message, to be emitted with all messages queued by the
+/// function within `ms` milliseconds of the first one as a single event.
+fn aggregate_tokens(
+    args: &DebugTryArgs,
+    ms: u64,
+    plain_str: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    let pending = Ident::new(PENDING_IDENT, Span::call_site());
+    let emit = emit_tokens(args);
+
+    quote! {
+        ::debug_try::__private::aggregate(
+            &#pending,
+            format!(#plain_str, #values),
+            #ms,
+            |__debug_try_message: ::std::string::String| {
+                #emit;
+            },
+        );
+    }
+}
+
+/// Generates the statement that emits a `__debug_try_message` that has already been formatted
+/// with the backend, for `aggregate_ms` and `summary`.
+fn emit_tokens(args: &DebugTryArgs) -> TokenStream {
+    let level = level_macro(backend_level(args));
+    let sentry_level = sentry_level(backend_level(args));
+    let target = target_tokens(args);
+    match args.backend() {
+        Backend::Stderr => match console_tokens(args) {
+            Some((print_macro, stream)) => print_tokens(
+                &print_macro,
+                stream.as_ref(),
+                "{}",
+                &quote!(__debug_try_message),
+            ),
+            None => sink_tokens(args, None, "{}", &quote!(__debug_try_message)),
+        },
+        Backend::Log => quote!(::log::#level!(#target "{}", __debug_try_message)),
+        Backend::Tracing => quote!(::tracing::#level!(#target "{}", __debug_try_message)),
+        Backend::Defmt => quote!(::defmt::#level!("{=str}", __debug_try_message.as_str())),
+        Backend::Sentry => quote! {
+            ::sentry::capture_message(&__debug_try_message, ::sentry::Level::#sentry_level);
+        },
+        Backend::OpenTelemetry => quote! {
+            ::opentelemetry::trace::get_active_span(|__debug_try_span| {
+                __debug_try_span.add_event(
+                    "debug_try.message",
+                    ::std::vec![::opentelemetry::KeyValue::new("message", __debug_try_message)],
+                );
+            });
+        },
+        Backend::Slog => {
+            let logger = &args.slog;
+            quote!(::slog::#level!(#logger, "{}", __debug_try_message))
+        }
+        Backend::Usdt => quote!(::debug_try::__private::probe_message(&__debug_try_message)),
+        Backend::Wasm => wasm_tokens(backend_level(args), &quote!(&__debug_try_message)),
+        Backend::None => quote!(let _ = __debug_try_message),
+    }
+}
+
+/// Generates the statements that report the first error of the site as usual, and then only
+/// count the errors, emitting a summary of them when one is due, without a message of its own.
+fn summary_tokens(args: &DebugTryArgs, site: &Site, report: &TokenStream) -> TokenStream {
+    let (events, period_ms) = match args.summary {
+        Some(Summary::Events(events)) => (events, 0),
+        Some(Summary::Period(period_ms)) => (0, period_ms),
+        None => return report.clone(),
+    };
+    let group = match site.group {
+        Some(ref group) => format!(" [group={}]", escape_format(group)),
+        None => String::new(),
+    };
+    let format = format!(
+        "{}{} {{}} times in {{:.1?}}{} ({}), last: {{}}",
+        prefix_str(args),
+        site.headline(),
+        group,
+        site.location_str()
+    );
+    let mut values = vec![quote!(__debug_try_count), quote!(__debug_try_elapsed)];
+    values.extend(site.location_values(Format::Text));
+    values.push(display_tokens(args));
+    let emit = emit_tokens(args);
+
+    // the sites of a group count their errors together, in a summary that the runtime keeps
+    let (setup, summary) = match site.group {
+        Some(ref group) => (
+            TokenStream::new(),
+            quote!(::debug_try::__private::group_summary(#group)),
+        ),
+        None => {
+            let summary = Ident::new(SUMMARY_IDENT, Span::call_site());
+            (
+                quote! {
+                    static #summary: ::debug_try::__private::Summary =
+                        ::debug_try::__private::Summary::new();
+                },
+                quote!(#summary),
+            )
+        }
+    };
+
+    quote! {
+        #setup
+        match #summary.record(#events, #period_ms) {
+            ::debug_try::__private::Tally::Report => {
+                #report
+            }
+            ::debug_try::__private::Tally::Summary(__debug_try_count, __debug_try_elapsed) => {
+                if ::debug_try::__private::verbosity() != ::core::option::Option::Some(0) {
+                    let __debug_try_message = format!(#format, #(#values),*);
+                    #emit;
+                }
+            }
+            ::debug_try::__private::Tally::Skip => {}
+        }
+    }
+}
+
+/// Generates the statements that emit a `tracing` event for the error, with the details of the
+/// site as structured fields rather than as part of the message.
+fn tracing_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    let chain = chain_tokens();
+    let printed = printed_tokens(args);
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+    let level = level_macro(backend_level(args)).to_string().to_uppercase();
+    let level = Ident::new(&level, Span::call_site());
+    let target = target_tokens(args);
+
+    let printed = match args.redact {
+        Some(true) => quote!(::debug_try::__private::Redacted(&#printed)),
+        _ => printed,
+    };
+    let printed = sanitized_tokens(args, truncated_tokens(args, printed));
+
+    let mut fields = vec![
+        quote!(error = %#printed),
+        quote!(error.chain = ?__debug_try_chain),
+        quote!(file = #file),
+        quote!(line = #line),
+        quote!(column = #column),
+        quote!(function = #function),
+    ];
+    if let Some(ref prefix) = args.prefix {
+        fields.push(quote!(prefix = #prefix));
+    }
+    if let Some(ref name) = site.name {
+        fields.push(quote!(name = #name));
+    }
+    if let Some(ref group) = site.group {
+        fields.push(quote!(group = #group));
+    }
+    if let Some((name, version)) = package(args) {
+        fields.push(quote!(package = #name));
+        fields.push(quote!(package.version = #version));
+    }
+    if let Some(build) = build(args) {
+        fields.push(quote!(build = #build));
+    }
+    if let Some(ref label) = site.label {
+        fields.push(quote!(label = #label));
+    }
+    if let Some(ref step) = site.step {
+        fields.push(quote!(step = #step));
+    }
+    if args.site_ids.unwrap_or(false) {
+        let id = format!("{:08x}", site.id);
+        fields.push(quote!(site = #id));
+        fields.push(quote!(hits = __debug_try_hits));
+    }
+    if let Some(n) = args.sample {
+        fields.push(quote!(sample = #n));
+        fields.push(quote!(suppressed = __debug_try_suppressed));
+    }
+    if args.counts.unwrap_or(false) {
+        fields.push(quote!(seen = __debug_try_hits));
+    }
+    if args.error_ids.unwrap_or(false) {
+        fields.push(quote!(error_id = __debug_try_error_id));
+    }
+    if args.host_info.unwrap_or(false) {
+        fields.push(quote!(host = __debug_try_host.0));
+        fields.push(quote!(pid = __debug_try_host.1));
+    }
+    if args.task_ids.unwrap_or(false) {
+        fields.push(quote!(task = %__debug_try_task));
+    }
+    if args.callers.unwrap_or(false) {
+        fields.push(quote!(callers = %__debug_try_callers));
+    }
+    if args.context.unwrap_or(false) {
+        fields.push(quote!(context = %__debug_try_context));
+    }
+    if args.elapsed.unwrap_or(false) {
+        let start = Ident::new(START_IDENT, Span::call_site());
+        fields.push(quote!(elapsed = ?#start.elapsed()));
+    }
+    if args.timing.unwrap_or(false) {
+        fields.push(quote!(duration = ?__debug_try_duration));
+    }
+    if args.delta.unwrap_or(false) {
+        fields.push(quote!(delta = ?__debug_try_delta));
+    }
+    for field in args.all_fields() {
+        // quoted names allow arbitrary expressions, e.g. `"cfg.path()"`
+        let name = expr_text(field);
+        fields.push(quote!(#name = ?&(#field)));
+    }
+    if args.debug_repr.unwrap_or(false) {
+        fields.push(match pretty_debug_tokens(args) {
+            Some(debug) => quote!(error.debug = %#debug),
+            None => quote!(error.debug = ?__debug_try_err),
+        });
+    }
+    if let Some(ref snippet) = site.snippet {
+        fields.push(quote!(snippet = #snippet));
+    }
+    // the spans that are entered at the site, which the `ErrorLayer` of the subscriber records
+    let mut setup = TokenStream::new();
+    if args.span_trace.unwrap_or(false) {
+        setup.extend(quote! {
+            let __debug_try_span_trace = ::tracing_error::SpanTrace::capture();
+        });
+        fields.push(quote!(span_trace = %__debug_try_span_trace));
+    }
+
+    let headline = site.headline();
+    quote! {
+        let __debug_try_chain = #chain;
+        #setup
+        ::tracing::event!(#target ::tracing::Level::#level, #(#fields,)* #headline);
+    }
+}
+
+/// Generates the statements that log the error with the key-value API of `log`, with the details of
+/// the site as key-values rather than as part of the message, for `log_kv`.
+fn log_kv_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    let chain = chain_tokens();
+    let printed = printed_tokens(args);
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+    let level = level_macro(backend_level(args));
+    let target = target_tokens(args);
+
+    let printed = match args.redact {
+        Some(true) => quote!(::debug_try::__private::Redacted(&#printed)),
+        _ => printed,
+    };
+    let printed = sanitized_tokens(args, truncated_tokens(args, printed));
+
+    let mut kvs = vec![
+        quote!("error":% = #printed),
+        quote!("error.chain":? = __debug_try_chain),
+        quote!("file" = #file),
+        quote!("line" = #line),
+        quote!("column" = #column),
+        quote!("function" = #function),
+    ];
+    if let Some(ref prefix) = args.prefix {
+        kvs.push(quote!("prefix" = #prefix));
+    }
+    if let Some(ref name) = site.name {
+        kvs.push(quote!("name" = #name));
+    }
+    if let Some(ref group) = site.group {
+        kvs.push(quote!("group" = #group));
+    }
+    if let Some((name, version)) = package(args) {
+        kvs.push(quote!("package" = #name));
+        kvs.push(quote!("package.version" = #version));
+    }
+    if let Some(build) = build(args) {
+        kvs.push(quote!("build" = #build));
+    }
+    if let Some(ref label) = site.label {
+        kvs.push(quote!("label" = #label));
+    }
+    if let Some(ref step) = site.step {
+        kvs.push(quote!("step" = #step));
+    }
+    if args.site_ids.unwrap_or(false) {
+        let id = format!("{:08x}", site.id);
+        kvs.push(quote!("site" = #id));
+        kvs.push(quote!("hits" = __debug_try_hits));
+    }
+    if let Some(n) = args.sample {
+        kvs.push(quote!("sample" = #n));
+        kvs.push(quote!("suppressed" = __debug_try_suppressed));
+    }
+    if args.counts.unwrap_or(false) {
+        kvs.push(quote!("seen" = __debug_try_hits));
+    }
+    if args.error_ids.unwrap_or(false) {
+        kvs.push(quote!("error_id":% = __debug_try_error_id));
+    }
+    if args.host_info.unwrap_or(false) {
+        kvs.push(quote!("host":% = __debug_try_host.0));
+        kvs.push(quote!("pid" = __debug_try_host.1));
+    }
+    if args.task_ids.unwrap_or(false) {
+        kvs.push(quote!("task":% = __debug_try_task));
+    }
+    if args.callers.unwrap_or(false) {
+        kvs.push(quote!("callers":% = __debug_try_callers));
+    }
+    if args.context.unwrap_or(false) {
+        kvs.push(quote!("context":% = __debug_try_context));
+    }
+    if args.elapsed.unwrap_or(false) {
+        let start = Ident::new(START_IDENT, Span::call_site());
+        kvs.push(quote!("elapsed":? = #start.elapsed()));
+    }
+    if args.timing.unwrap_or(false) {
+        kvs.push(quote!("duration":? = __debug_try_duration));
+    }
+    if args.delta.unwrap_or(false) {
+        kvs.push(quote!("delta":? = __debug_try_delta));
+    }
+    for field in args.all_fields() {
+        let name = expr_text(field);
+        kvs.push(quote!(#name:? = &(#field)));
+    }
+    if args.debug_repr.unwrap_or(false) {
+        kvs.push(match pretty_debug_tokens(args) {
+            Some(debug) => quote!("error.debug":% = #debug),
+            None => quote!("error.debug":? = __debug_try_err),
+        });
+    }
+    if let Some(ref snippet) = site.snippet {
+        kvs.push(quote!("snippet" = #snippet));
+    }
+
+    let headline = site.headline();
+    quote! {
+        let __debug_try_chain = #chain;
+        ::log::#level!(#target #(#kvs),*; #headline);
+    }
+}
+
+/// Generates the statements that log the error with the `slog` logger of `slog = ...`, with the
+/// details of the site as key-value pairs rather than as part of the message.
+fn slog_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    let chain = chain_tokens();
+    let printed = printed_tokens(args);
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+    let level = level_macro(backend_level(args));
+    let logger = &args.slog;
+
+    let printed = match args.redact {
+        Some(true) => quote!(::debug_try::__private::Redacted(&#printed)),
+        _ => printed,
+    };
+    let printed = sanitized_tokens(args, truncated_tokens(args, printed));
+
+    let mut pairs = vec![
+        quote!("error" => %#printed),
+        quote!("error.chain" => ?__debug_try_chain),
+        quote!("file" => #file),
+        quote!("line" => #line),
+        quote!("column" => #column),
+        quote!("function" => #function),
+    ];
+    if let Some(ref prefix) = args.prefix {
+        pairs.push(quote!("prefix" => #prefix));
+    }
+    if let Some(ref name) = site.name {
+        pairs.push(quote!("name" => #name));
+    }
+    if let Some(ref group) = site.group {
+        pairs.push(quote!("group" => #group));
+    }
+    if let Some((name, version)) = package(args) {
+        pairs.push(quote!("package" => #name));
+        pairs.push(quote!("package.version" => #version));
+    }
+    if let Some(build) = build(args) {
+        pairs.push(quote!("build" => #build));
+    }
+    if let Some(ref label) = site.label {
+        pairs.push(quote!("label" => #label));
+    }
+    if let Some(ref step) = site.step {
+        pairs.push(quote!("step" => #step));
+    }
+    if args.site_ids.unwrap_or(false) {
+        let id = format!("{:08x}", site.id);
+        pairs.push(quote!("site" => #id));
+        pairs.push(quote!("hits" => __debug_try_hits));
+    }
+    if let Some(n) = args.sample {
+        pairs.push(quote!("sample" => #n));
+        pairs.push(quote!("suppressed" => __debug_try_suppressed));
+    }
+    if args.counts.unwrap_or(false) {
+        pairs.push(quote!("seen" => __debug_try_hits));
+    }
+    if args.error_ids.unwrap_or(false) {
+        pairs.push(quote!("error_id" => %__debug_try_error_id));
+    }
+    if args.host_info.unwrap_or(false) {
+        pairs.push(quote!("host" => %__debug_try_host.0));
+        pairs.push(quote!("pid" => __debug_try_host.1));
+    }
+    if args.task_ids.unwrap_or(false) {
+        pairs.push(quote!("task" => %__debug_try_task));
+    }
+    if args.callers.unwrap_or(false) {
+        pairs.push(quote!("callers" => %__debug_try_callers));
+    }
+    if args.context.unwrap_or(false) {
+        pairs.push(quote!("context" => %__debug_try_context));
+    }
+    if args.elapsed.unwrap_or(false) {
+        let start = Ident::new(START_IDENT, Span::call_site());
+        pairs.push(quote!("elapsed" => ?#start.elapsed()));
+    }
+    if args.timing.unwrap_or(false) {
+        pairs.push(quote!("duration" => ?__debug_try_duration));
+    }
+    if args.delta.unwrap_or(false) {
+        pairs.push(quote!("delta" => ?__debug_try_delta));
+    }
+    for field in args.all_fields() {
+        let name = expr_text(field);
+        pairs.push(quote!(#name => ?&(#field)));
+    }
+    if args.debug_repr.unwrap_or(false) {
+        pairs.push(match pretty_debug_tokens(args) {
+            Some(debug) => quote!("error.debug" => %#debug),
+            None => quote!("error.debug" => ?__debug_try_err),
+        });
+    }
+    if let Some(ref snippet) = site.snippet {
+        pairs.push(quote!("snippet" => #snippet));
+    }
+
+    let headline = site.headline();
+    quote! {
+        let __debug_try_chain = #chain;
+        ::slog::#level!(#logger, #headline; #(#pairs),*);
+    }
+}
+
+/// Generates the expression that creates the `tracing` span of the function with `span`, named
+/// after the function, with the `span_fields(...)` and an empty `error` field that the sites record
+/// the error in.
+pub(crate) fn span_tokens(args: &DebugTryArgs, function: &str) -> TokenStream {
+    let level = level_macro(backend_level(args)).to_string().to_uppercase();
+    let level = Ident::new(&level, Span::call_site());
+    let target = target_tokens(args);
+    let fields = args.span_fields.iter().flatten().map(|field| {
+        let name = expr_text(field);
+        quote!(#name = ?&(#field))
+    });
+    quote! {
+        ::tracing::span!(
+            #target ::tracing::Level::#level,
+            #function,
+            #(#fields,)*
+            error = ::tracing::field::Empty
+        )
+    }
+}
+
+/// Generates the statements that send the message to Sentry, with the details of the site and the
+/// fields as extra data of the event.
+fn sentry_tokens(
+    args: &DebugTryArgs,
+    site: &Site,
+    plain_str: &str,
+    values: &TokenStream,
+) -> TokenStream {
+    let chain = chain_tokens();
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+    let level = sentry_level(backend_level(args));
+
+    let mut extras = vec![
+        quote!(__debug_try_scope.set_extra("file", #file.into())),
+        quote!(__debug_try_scope.set_extra("line", #line.into())),
+        quote!(__debug_try_scope.set_extra("column", #column.into())),
+        quote!(__debug_try_scope.set_extra("function", #function.into())),
+        quote!(__debug_try_scope.set_extra("error.chain", __debug_try_chain.into())),
+    ];
+    if let Some(ref prefix) = args.prefix {
+        extras.push(quote!(__debug_try_scope.set_tag("debug_try.prefix", #prefix)));
+    }
+    if let Some(ref name) = site.name {
+        extras.push(quote!(__debug_try_scope.set_tag("debug_try.name", #name)));
+    }
+    if let Some(ref group) = site.group {
+        extras.push(quote!(__debug_try_scope.set_tag("debug_try.group", #group)));
+    }
+    if let Some((name, version)) = package(args) {
+        extras.push(quote!(__debug_try_scope.set_tag("debug_try.package", #name)));
+        extras.push(quote!(__debug_try_scope.set_extra("package.version", #version.into())));
+    }
+    if let Some(build) = build(args) {
+        extras.push(quote!(__debug_try_scope.set_tag("debug_try.build", #build)));
+    }
+    if let Some(ref label) = site.label {
+        extras.push(quote!(__debug_try_scope.set_extra("label", #label.into())));
+    }
+    if let Some(ref step) = site.step {
+        extras.push(quote!(__debug_try_scope.set_extra("step", #step.into())));
+    }
+    if args.counts.unwrap_or(false) {
+        extras.push(quote!(
+            __debug_try_scope.set_extra("seen", __debug_try_hits.into())
+        ));
+    }
+    if args.error_ids.unwrap_or(false) {
+        extras.push(quote!(
+            __debug_try_scope.set_extra("error_id", __debug_try_error_id.into())
+        ));
+    }
+    if args.host_info.unwrap_or(false) {
+        extras.push(quote!(
+            __debug_try_scope.set_extra("pid", __debug_try_host.1.into())
+        ));
+    }
+    if args.task_ids.unwrap_or(false) {
+        extras.push(quote!(
+            __debug_try_scope.set_tag("debug_try.task", __debug_try_task)
+        ));
+    }
+    if args.callers.unwrap_or(false) {
+        extras.push(quote!(
+            __debug_try_scope.set_extra("callers", __debug_try_callers.to_string().into())
+        ));
+    }
+    if args.context.unwrap_or(false) {
+        extras.push(quote!(
+            __debug_try_scope.set_extra("context", __debug_try_context.to_string().into())
+        ));
+    }
+    for field in args.all_fields() {
+        let name = expr_text(field);
+        extras.push(quote!(__debug_try_scope.set_extra(#name, format!("{:?}", &(#field)).into())));
+    }
+
+    quote! {
+        let __debug_try_chain = #chain;
+        let __debug_try_message = format!(#plain_str, #values);
+        ::sentry::with_scope(
+            |__debug_try_scope| {
+                __debug_try_scope.set_tag("debug_try.function", #function);
+                #(#extras;)*
+            },
+            || ::sentry::capture_message(&__debug_try_message, ::sentry::Level::#level),
+        );
+    }
+}
+
+/// Generates the statement that pauses the process with `pause_on_error`, at the first error of
+/// the process, or at the first error of the site with `pause_per_site`.
+fn pause_tokens(args: &DebugTryArgs, site: &Site, pause: Pause) -> TokenStream {
+    let (file, line, column) = site.location_tokens();
+    let mode = match pause {
+        Pause::Seconds(secs) => quote!(::debug_try::__private::Pause::Seconds(#secs)),
+        Pause::Enter => quote!(::debug_try::__private::Pause::Enter),
+        Pause::Signal => quote!(::debug_try::__private::Pause::Signal),
+    };
+    let paused = match args.pause_per_site {
+        Some(true) => quote! {
+            ::core::option::Option::Some({
+                static __DEBUG_TRY_PAUSED: ::core::sync::atomic::AtomicBool =
+                    ::core::sync::atomic::AtomicBool::new(false);
+                &__DEBUG_TRY_PAUSED
+            })
+        },
+        _ => quote!(::core::option::Option::None),
+    };
+
+    quote! {
+        ::debug_try::__private::pause(#mode, #paused, #file, #line, #column);
+    }
+}
+
+/// Generates the statement that fires the `debug_try:propagated` probe of the runtime with the
+/// details of the site, with `backend = "usdt"`.
+fn usdt_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    let printed = printed_tokens(args);
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+
+    let printed = match args.redact {
+        Some(true) => quote!(::debug_try::__private::Redacted(&#printed)),
+        _ => printed,
+    };
+    let printed = sanitized_tokens(args, truncated_tokens(args, printed));
+
+    quote! {
+        ::debug_try::__private::probe(#file, #line, #column, #function, &#printed);
+    }
+}
+
+/// Generates the statements that record the error as an `exception` event on the active
+/// OpenTelemetry span, with the attributes of the semantic conventions for exceptions and code
+/// locations, and the details of the site and the fields as attributes of their own.
+fn opentelemetry_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    let chain = chain_tokens();
+    let printed = printed_tokens(args);
+    let (file, line, column) = site.location_tokens();
+    let function = &site.function;
+
+    let printed = match args.redact {
+        Some(true) => quote!(::debug_try::__private::Redacted(&#printed)),
+        _ => printed,
+    };
+    let printed = truncated_tokens(args, printed);
+
+    let attribute =
+        |key: &str, value: TokenStream| quote!(::opentelemetry::KeyValue::new(#key, #value));
+    let mut attributes = vec![
+        attribute(
+            "exception.type",
+            quote!(::core::any::type_name_of_val(&__debug_try_err)),
+        ),
+        attribute(
+            "exception.message",
+            quote!(::std::string::ToString::to_string(&#printed)),
+        ),
+        attribute("code.file.path", file),
+        attribute("code.line.number", quote!(i64::from(#line))),
+        attribute("code.column.number", quote!(i64::from(#column))),
+        attribute("code.function.name", quote!(#function)),
+        attribute(
+            "debug_try.error.chain",
+            quote! {
+                ::opentelemetry::Value::Array(::opentelemetry::Array::String(
+                    ::std::iter::Iterator::collect(::std::iter::Iterator::map(
+                        ::std::iter::IntoIterator::into_iter(__debug_try_chain),
+                        ::std::convert::Into::into,
+                    )),
+                ))
+            },
+        ),
+    ];
+    if let Some(ref prefix) = args.prefix {
+        attributes.push(attribute("debug_try.prefix", quote!(#prefix)));
+    }
+    if let Some(ref name) = site.name {
+        attributes.push(attribute("debug_try.name", quote!(#name)));
+    }
+    if let Some(ref group) = site.group {
+        attributes.push(attribute("debug_try.group", quote!(#group)));
+    }
+    if let Some((name, version)) = package(args) {
+        attributes.push(attribute("debug_try.package", quote!(#name)));
+        attributes.push(attribute("debug_try.package.version", quote!(#version)));
+    }
+    if let Some(build) = build(args) {
+        attributes.push(attribute("debug_try.build", quote!(#build)));
+    }
+    if let Some(ref label) = site.label {
+        attributes.push(attribute("debug_try.label", quote!(#label)));
+    }
+    if let Some(ref step) = site.step {
+        attributes.push(attribute("debug_try.step", quote!(#step)));
+    }
+    if args.site_ids.unwrap_or(false) {
+        let id = format!("{:08x}", site.id);
+        attributes.push(attribute("debug_try.site", quote!(#id)));
+    }
+    if args.counts.unwrap_or(false) {
+        attributes.push(attribute("debug_try.seen", quote!(__debug_try_hits as i64)));
+    }
+    if args.error_ids.unwrap_or(false) {
+        attributes.push(attribute(
+            "debug_try.error_id",
+            quote!(::std::string::ToString::to_string(&__debug_try_error_id)),
+        ));
+    }
+    if args.task_ids.unwrap_or(false) {
+        attributes.push(attribute(
+            "debug_try.task",
+            quote!(::std::string::ToString::to_string(&__debug_try_task)),
+        ));
+    }
+    if args.callers.unwrap_or(false) {
+        attributes.push(attribute(
+            "debug_try.callers",
+            quote!(::std::string::ToString::to_string(&__debug_try_callers)),
+        ));
+    }
+    if args.context.unwrap_or(false) {
+        attributes.push(attribute(
+            "debug_try.context",
+            quote!(::std::string::ToString::to_string(&__debug_try_context)),
+        ));
+    }
+    for field in args.all_fields() {
+        let name = expr_text(field);
+        attributes.push(attribute(&name, quote!(format!("{:?}", &(#field)))));
+    }
+
+    quote! {
+        let __debug_try_chain = #chain;
+        ::opentelemetry::trace::get_active_span(|__debug_try_span| {
+            __debug_try_span.add_event("exception", ::std::vec![#(#attributes),*]);
+        });
+    }
+}
+
+/// Generates the statement that logs a message to the browser console at the given level, e.g. with
+/// `console.warn` for `Level::Warn`.
+fn wasm_tokens(level: Level, message: &TokenStream) -> TokenStream {
+    let function = match level {
+        Level::Error => "error_1",
+        Level::Warn => "warn_1",
+        Level::Info => "info_1",
+        Level::Debug | Level::Trace => "debug_1",
+    };
+    let function = Ident::new(function, Span::call_site());
+    quote! {
+        ::web_sys::console::#function(&::web_sys::wasm_bindgen::JsValue::from_str(#message));
+    }
+}
+
+/// Returns the level that the backends other than `"stderr"` emit messages at.
+fn backend_level(args: &DebugTryArgs) -> Level {
+    args.level.unwrap_or(Level::Error)
+}
+
+/// Returns the level of the compact line for re-propagated errors, which is the debug level, or
+/// the trace level if messages are emitted at the trace level.
+fn compact_level(args: &DebugTryArgs) -> Level {
+    match backend_level(args) {
+        Level::Trace => Level::Trace,
+        _ => Level::Debug,
+    }
+}
+
+/// Returns the name of the macro of `log`, `tracing` and `defmt` that emits an event at the given
+/// level, e.g. `warn`.
+fn level_macro(level: Level) -> Ident {
+    Ident::new(level_name(level), Span::call_site())
+}
+
+/// Returns the name of the given level as in the `level` argument, e.g. `warn`.
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warn",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    }
+}
+
+/// Returns the `target: "...",` that starts the arguments of the `log` and `tracing` macros if a
+/// target is given, so that the module path of the function is the target otherwise.
+fn target_tokens(args: &DebugTryArgs) -> TokenStream {
+    match args.target {
+        Some(ref target) => quote!(target: #target,),
+        None => TokenStream::new(),
+    }
+}
+
+/// Returns the `sentry::Level` of the given level, where the trace level is the debug level.
+fn sentry_level(level: Level) -> Ident {
+    let name = match level {
+        Level::Error => "Error",
+        Level::Warn => "Warning",
+        Level::Info => "Info",
+        Level::Debug | Level::Trace => "Debug",
+    };
+    Ident::new(name, Span::call_site())
+}
+
+/// Generates the statements that emit a message with `defmt::error!`. The values are formatted
+/// with their `Display` and `Debug` implementations, through the adapters provided by `defmt`.
+fn defmt_tokens(args: &DebugTryArgs, site: &Site) -> TokenStream {
+    let format = format_str(args, site, Style::Defmt);
+    let level = level_macro(backend_level(args));
+
+    let mut values = site.location_values(Format::Text);
+    if args.site_ids.unwrap_or(false) {
+        values.push(quote!(__debug_try_hits));
+    }
+    if args.sample.is_some() {
+        values.push(quote!(__debug_try_suppressed));
+    }
+    if args.counts.unwrap_or(false) {
+        values.push(quote!(__debug_try_hits));
+    }
+    if args.error_ids.unwrap_or(false) {
+        values.push(quote!(__debug_try_error_id));
+    }
+    if args.host_info.unwrap_or(false) {
+        values.push(quote!(__debug_try_host.0));
+        values.push(quote!(__debug_try_host.1));
+    }
+    if args.task_ids.unwrap_or(false) {
+        values.push(quote!(::defmt::Display2Format(&__debug_try_task)));
+    }
+    if args.callers.unwrap_or(false) {
+        values.push(quote!(::defmt::Display2Format(&__debug_try_callers)));
+    }
+    if args.context.unwrap_or(false) {
+        values.push(quote!(::defmt::Display2Format(&__debug_try_context)));
+    }
+    if args.elapsed.unwrap_or(false) {
+        let start = Ident::new(START_IDENT, Span::call_site());
+        values.push(quote!(::defmt::Debug2Format(&#start.elapsed())));
+    }
+    if args.timing.unwrap_or(false) {
+        values.push(quote!(::defmt::Debug2Format(&__debug_try_duration)));
+    }
+    if args.delta.unwrap_or(false) {
+        values.push(quote!(::defmt::Debug2Format(&__debug_try_delta)));
+    }
+    let err = display_tokens(args);
+    values.push(quote!(::defmt::Display2Format(&#err)));
+    for field in args.all_fields() {
+        values.push(quote!(::defmt::Debug2Format(&(#field))));
+    }
+    if args.debug_repr.unwrap_or(false) {
+        values.push(match pretty_debug_tokens(args) {
+            Some(debug) => quote!(::defmt::Display2Format(&#debug)),
+            None => quote!(::defmt::Debug2Format(&__debug_try_err)),
+        });
+    }
+
+    quote! {
+        ::defmt::#level!(#format, #(#values),*);
+    }
+}
+
+/// Generates the statements that print the error returned by `main`, or by an `extern` function
+/// with `ffi`, after the given headline, with its sources.
+pub(crate) fn main_failure_tokens(args: &DebugTryArgs, headline: &str) -> TokenStream {
+    if args.rich.unwrap_or(false) {
+        return rich_failure_tokens(args, headline);
+    }
+    let (chain, err) = failure_tokens(args, quote!(&__debug_try_err));
+    quote! {
+        ::debug_try::__private::require_display(&__debug_try_err);
+        let __debug_try_chain = #chain;
+        ::debug_try::__private::report_main(#headline, #err, &__debug_try_chain);
+    }
+}
+
+/// Generates the statement that writes the crash report of the error returned by `main`, with
+/// `crash_report`, to `__debug_try_crash`, before the error is printed.
+pub(crate) fn crash_report_tokens(args: &DebugTryArgs) -> TokenStream {
+    let (chain, err) = failure_tokens(args, quote!(&__debug_try_err));
+    quote! {
+        let __debug_try_crash = ::debug_try::__private::write_crash_report(
+            ::core::env!("CARGO_PKG_NAME"),
+            ::core::env!("CARGO_PKG_VERSION"),
+            #err,
+            &#chain,
+        );
+    }
+}
+
+/// Generates the statements that print the error returned by `main` or a failing test with `rich`,
+/// after the given headline, in sections with its sources and the sites it was propagated through.
+fn rich_failure_tokens(args: &DebugTryArgs, headline: &str) -> TokenStream {
+    let (chain, err) = failure_tokens(args, quote!(&__debug_try_err));
+    let color = match args.color.unwrap_or(Color::Auto) {
+        Color::Always => quote!(::core::option::Option::Some(true)),
+        Color::Auto => quote!(::core::option::Option::None),
+        Color::Never => quote!(::core::option::Option::Some(false)),
+    };
+    quote! {
+        ::debug_try::__private::require_display(&__debug_try_err);
+        let __debug_try_chain = #chain;
+        ::debug_try::__private::report_rich(#headline, #color, #err, &__debug_try_chain);
+    }
+}
+
+/// Returns the expression that collects the sources of the error returned by `main` or a failing
+/// test, and the error as it is to be printed, both passed through the redactor with `redact`, and
+/// with their control characters escaped unless `sanitize = false` is given.
+fn failure_tokens(args: &DebugTryArgs, err: TokenStream) -> (TokenStream, TokenStream) {
+    let redact = args.redact.unwrap_or(false);
+    let err = match redact {
+        true => quote!(&::debug_try::__private::Redacted(#err)),
+        false => err,
+    };
+    let err = match args.sanitize() {
+        true => quote!(&::debug_try::__private::Sanitized(#err)),
+        false => err,
+    };
+    let chain = chain_tokens();
+    let source = match redact {
+        true => quote!(::debug_try::__private::redact(source)),
+        false => quote!(source),
+    };
+    let source = match args.sanitize() {
+        true => quote!(::std::string::ToString::to_string(
+            &::debug_try::__private::Sanitized(#source)
+        )),
+        false if redact => source,
+        false => return (chain, err),
+    };
+    let chain = quote! {
+        ::std::iter::Iterator::collect::<::std::vec::Vec<_>>(
+            ::std::iter::Iterator::map(#chain.iter(), |source| #source),
+        )
+    };
+    (chain, err)
+}
+
+/// Returns the expression that collects the messages of the sources of the error as they are
+/// listed in text messages, with their control characters escaped unless `sanitize = false` is
+/// given.
+fn sources_tokens(args: &DebugTryArgs) -> TokenStream {
+    let chain = chain_tokens();
+    match args.sanitize() {
+        true => quote! {
+            ::std::iter::Iterator::collect::<::std::vec::Vec<_>>(
+                ::std::iter::Iterator::map(#chain.iter(), |source| {
+                    ::std::string::ToString::to_string(&::debug_try::__private::Sanitized(source))
+                }),
+            )
+        },
+        false => chain,
+    }
+}
+
+/// Generates the statements that print the error returned by a failing test, and its sources.
+pub(crate) fn test_failure_tokens(args: &DebugTryArgs, function: &str) -> TokenStream {
+    if args.rich.unwrap_or(false) {
+        return rich_failure_tokens(args, &format!("Test `{}` failed", function));
+    }
+    let (chain, err) = failure_tokens(args, quote!(__debug_try_err));
+    let failed_str = format!("Test `{}` failed: {{}}", function);
+    quote! {
+        ::debug_try::__private::require_display(__debug_try_err);
+        let __debug_try_chain = #chain;
+        eprintln!(#failed_str, #err);
+        for __debug_try_source in &__debug_try_chain {
+            eprintln!("    Caused by: {}", __debug_try_source);
+        }
+    }
+}
+
+/// Generates an expression that collects the messages of the sources of the error, if it is an error
+/// type, or a boxed error and similar types that can be borrowed as one, and is empty otherwise.
+///
+/// This uses autoderef-based specialization: the method is looked up on a reference with more
+/// levels of indirection than any of the impls, so the impl that needs the fewest dereferences,
+/// and whose bounds are satisfied, is chosen.
+fn chain_tokens() -> TokenStream {
+    quote! {{
+        type Sources = ::std::vec::Vec<::std::string::String>;
+        fn sources(err: &dyn ::std::error::Error) -> Sources {
+            let mut sources = Sources::new();
+            let mut source = err.source();
+            while let ::std::option::Option::Some(err) = source {
+                sources.push(::std::string::ToString::to_string(err));
+                source = err.source();
+            }
+            sources
+        }
+
+        struct Chain<'a, T>(&'a T);
+        trait ViaError {
+            fn sources(&self) -> Sources;
+        }
+        impl<T: ::std::error::Error> ViaError for &&&Chain<'_, T> {
+            fn sources(&self) -> Sources {
+                sources(self.0)
+            }
+        }
+        trait ViaAsRef {
+            fn sources(&self) -> Sources;
+        }
+        impl<T: ::std::convert::AsRef<dyn ::std::error::Error>> ViaAsRef for &&Chain<'_, T> {
+            fn sources(&self) -> Sources {
+                sources(self.0.as_ref())
+            }
+        }
+        trait ViaAsRefSendSync {
+            fn sources(&self) -> Sources;
+        }
+        impl<T> ViaAsRefSendSync for &Chain<'_, T>
+        where
+            T: ::std::convert::AsRef<dyn ::std::error::Error + ::std::marker::Send + ::std::marker::Sync>,
+        {
+            fn sources(&self) -> Sources {
+                sources(self.0.as_ref())
+            }
+        }
+        trait Fallback {
+            fn sources(&self) -> Sources;
+        }
+        impl<T> Fallback for Chain<'_, T> {
+            fn sources(&self) -> Sources {
+                Sources::new()
+            }
+        }
+
+        (&&&&Chain(&__debug_try_err)).sources()
+    }}
+}
+
+/// Returns the value that is formatted with the `Display` placeholder of the error, which is cut off
+/// after the first line with `verbosity = "compact"`, and has its control characters escaped
+/// unless `sanitize = false` is given.
+fn display_tokens(args: &DebugTryArgs) -> TokenStream {
+    sanitized_tokens(args, unsanitized_display_tokens(args))
+}
+
+/// Returns the value that is formatted with the `Display` placeholder of the error in messages that
+/// escape it themselves, like JSON and logfmt messages.
+fn unsanitized_display_tokens(args: &DebugTryArgs) -> TokenStream {
+    // with `formatters`, the error goes through the formatters registered at run time
+    let err = if args.formatters.unwrap_or(false) {
+        let error = static_error_tokens();
+        let printed = printed_tokens(args);
+        quote!(::debug_try::__private::Formatted(&#printed, #error))
+    } else {
+        printed_tokens(args)
+    };
+    let err = match args.verbosity() {
+        Verbosity::Compact => quote!(::debug_try::__private::FirstLine(&#err)),
+        Verbosity::Normal | Verbosity::Full => err,
+    };
+    truncated_tokens(args, err)
+}
+
+/// Returns the error as it is printed with its control characters escaped, unless
+/// `sanitize = false` is given.
+fn sanitized_tokens(args: &DebugTryArgs, err: TokenStream) -> TokenStream {
+    match args.sanitize() {
+        true => quote!(::debug_try::__private::Sanitized(&#err)),
+        false => err,
+    }
+}
+
+/// Returns the error as it is printed with `max_len`, truncated to that many bytes.
+fn truncated_tokens(args: &DebugTryArgs, err: TokenStream) -> TokenStream {
+    match args.max_len {
+        Some(len) => quote!(::debug_try::__private::Truncated(&#err, #len)),
+        None => err,
+    }
+}
+
+/// Returns the error as it is formatted with `Display`, which with `bound = "none"` is the name of
+/// its type if it is not known to implement `Display`.
+fn printed_tokens(args: &DebugTryArgs) -> TokenStream {
+    match args.bound {
+        Some(Bound::None) => quote! {{
+            use ::debug_try::__private::{PrintDisplay as _, PrintOther as _};
+            (&&::debug_try::__private::Printable(&__debug_try_err)).printed()
+        }},
+        Some(Bound::Display) | None => quote!(__debug_try_err),
+    }
+}
+
+/// Returns the error as a value that displays its pretty `Debug` representation, for the fields
+/// of the structured backends with `debug_repr`, or `None` without `pretty`, since the backends
+/// format the plain one themselves.
+fn pretty_debug_tokens(args: &DebugTryArgs) -> Option<TokenStream> {
+    match args.pretty {
+        Some(true) => Some(quote!(::debug_try::__private::PrettyDebug(
+            &__debug_try_err
+        ))),
+        _ => None,
+    }
+}
+
+/// Returns the placeholders for the `Display` and `Debug` representations of the error.
+fn placeholders(args: &DebugTryArgs) -> (&'static str, &'static str) {
+    if args.pretty.unwrap_or(false) {
+        ("{:#}", "{:#?}")
+    } else {
+        ("{}", "{:?}")
+    }
+}
+
+/// The flavor of a format string.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Style {
+    /// A format string for the `format!` family of macros.
+    Plain,
+    /// The same, with ANSI color codes.
+    Colored,
+    /// A format string for the `defmt` macros, where every placeholder is `{}`, except for the hit
+    /// count and the error ID, which are `{=usize}` and `{=u64}`.
+    Defmt,
+}
+
+/// Builds the format string for a message.
+fn format_str(args: &DebugTryArgs, site: &Site, style: Style) -> String {
+    match (args.format.unwrap_or(Format::Text), style) {
+        (Format::Json, Style::Plain) | (Format::Json, Style::Colored) => {
+            return json_format_str(args, site)
+        }
+        (Format::Logfmt, Style::Plain) | (Format::Logfmt, Style::Colored) => {
+            return logfmt_format_str(args, site)
+        }
+        (Format::Rustc, Style::Plain) | (Format::Rustc, Style::Colored) => {
+            return rustc_format_str(args, site, style)
+        }
+        _ => {}
+    }
+
+    let (placeholder, debug_placeholder, hits_placeholder, debug_value_placeholder) = match style {
+        Style::Plain | Style::Colored => {
+            let (display, debug) = placeholders(args);
+            (display, debug, "{}", "{:?}")
+        }
+        Style::Defmt => ("{}", "{}", "{=usize}", "{}"),
+    };
+    let prefix = match style {
+        Style::Plain | Style::Colored => format!("{}{}", indent_str(args), prefix_str(args)),
+        Style::Defmt => prefix_str(args),
+    };
+    let location = site.location_str();
+    let mut package = match package(args) {
+        Some((name, version)) => {
+            format!(" [{} v{}]", escape_format(&name), escape_format(&version))
+        }
+        None => String::new(),
+    };
+    if let Some(build) = build(args) {
+        package.push_str(&format!(" [build={}]", escape_format(&build)));
+    }
+    let mut label = match site.name {
+        Some(ref name) => format!(" [name={}]", escape_format(name)),
+        None => String::new(),
+    };
+    if let Some(ref group) = site.group {
+        label.push_str(&format!(" [group={}]", escape_format(group)));
+    }
+    if let Some(ref site_label) = site.label {
+        label.push_str(&format!(" [{}]", escape_format(site_label)));
+    }
+    if let Some(ref step) = site.step {
+        label.push_str(&format!(" in `{}`", escape_format(step)));
+    }
+    let mut site_id = if args.site_ids.unwrap_or(false) {
+        format!(" [site={:08x} hits={}]", site.id, hits_placeholder)
+    } else {
+        String::new()
+    };
+    if let Some(n) = args.sample {
+        site_id.push_str(&format!(
+            " [sample=1/{} suppressed={}]",
+            n, hits_placeholder
+        ));
+    }
+    if args.counts.unwrap_or(false) {
+        site_id.push_str(&format!(" (seen {}\u{d7})", hits_placeholder));
+    }
+    let mut error_id = if args.error_ids.unwrap_or(false) {
+        match style {
+            Style::Plain | Style::Colored => " [error={}]",
+            Style::Defmt => " [error={=u64}]",
+        }
+    } else {
+        ""
+    }
+    .to_owned();
+    if args.host_info.unwrap_or(false) {
+        error_id.push_str(match style {
+            Style::Plain | Style::Colored => " [host={} pid={}]",
+            Style::Defmt => " [host={=str} pid={=u32}]",
+        });
+    }
+    if args.task_ids.unwrap_or(false) {
+        error_id.push_str(" [task={}]");
+    }
+    if args.callers.unwrap_or(false) {
+        error_id.push_str(" [callers={}]");
+    }
+    if args.context.unwrap_or(false) {
+        error_id.push_str(" [context={}]");
+    }
+    let mut elapsed = if args.elapsed.unwrap_or(false) {
+        format!(" after {}", debug_value_placeholder)
+    } else {
+        String::new()
+    };
+    if args.timing.unwrap_or(false) {
+        elapsed.push_str(&format!(" took {}", debug_value_placeholder));
+    }
+    if args.delta.unwrap_or(false) {
+        elapsed.push_str(&format!(" +{}", debug_value_placeholder));
+    }
+
+    // the multi-line layout is only used for text messages, which have the `std` types it needs
+    let multiline = args.multiline() && style != Style::Defmt;
+    let fields = args.all_fields();
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let name = escape_format(&expr_text(field));
+            format!("{} = {}", name, debug_value_placeholder)
+        })
+        .collect();
+    let fields = match (fields.is_empty(), multiline) {
+        (true, _) => String::new(),
+        (false, false) => format!(" ({})", fields.join(", ")),
+        (false, true) => format!("\n    Fields:\n        {}", fields.join("\n        ")),
+    };
+    let debug_repr = if args.debug_repr.unwrap_or(false) {
+        format!("\n    Debug: {}", debug_placeholder)
+    } else {
+        String::new()
+    };
+    // the sources and the backtrace need `std`, so they are left out of `defmt` messages
+    let (causes, backtrace) = if args.verbosity() == Verbosity::Full && style != Style::Defmt {
+        ("{}", "\n    Stack backtrace:\n{}")
+    } else {
+        ("", "")
+    };
+    // with the multi-line layout, the error starts a line below the header, and is followed by its
+    // sources rather than by the fields
+    let (separator, before_fields, after_fields) = match multiline {
+        true => (":\n    ", "{}", ""),
+        false => (": ", "", causes),
+    };
+    let before_fields = match style {
+        Style::Plain | Style::Colored => format!(
+            "{}{}{}",
+            io_placeholder(args),
+            type_placeholder(args, site),
+            before_fields
+        ),
+        Style::Defmt => before_fields.to_owned(),
+    };
+
+    let snippet = match site.snippet {
+        Some(ref snippet) => format!("\n{}", escape_format(snippet)),
+        None => String::new(),
+    };
+    let verbose = match style {
+        Style::Plain | Style::Colored if runtime_verbosity(args) => "{}",
+        _ => "",
+    };
+
+    if style == Style::Colored {
+        // an OSC 8 hyperlink, which terminals that do not support it leave out
+        let location = match site.link {
+            Some(ref link) => format!(
+                "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+                escape_format(link),
+                location
+            ),
+            None => location,
+        };
+        format!(
+            "{}\x1b[1;31m{}\x1b[0m (\x1b[36m{}\x1b[0m){}{}{}{}{}{}\x1b[33m{}\x1b[0m{}{}{}{}{}{}{}",
+            prefix,
+            site.headline(),
+            location,
+            package,
+            label,
+            site_id,
+            error_id,
+            elapsed,
+            separator,
+            placeholder,
+            before_fields,
+            fields,
+            after_fields,
+            debug_repr,
+            backtrace,
+            snippet,
+            verbose
+        )
+    } else {
+        format!(
+            "{}{} ({}){}{}{}{}{}{}{}{}{}{}{}{}{}{}",
+            prefix,
+            site.headline(),
+            location,
+            package,
+            label,
+            site_id,
+            error_id,
+            elapsed,
+            separator,
+            placeholder,
+            before_fields,
+            fields,
+            after_fields,
+            debug_repr,
+            backtrace,
+            snippet,
+            verbose
+        )
+    }
+}
+
+/// Builds the format string for a message like the diagnostics of rustc, e.g.
+/// `error: error propagated in `read`: ...` followed by a line like `  --> src/lib.rs:10:31`,
+/// which editors and terminals recognize as a location. The sources, the debug representation,
+/// the backtrace and the snippet follow the location.
+fn rustc_format_str(args: &DebugTryArgs, site: &Site, style: Style) -> String {
+    let (placeholder, debug_placeholder) = placeholders(args);
+
+    let (level, color) = match backend_level(args) {
+        Level::Error => ("error", "\x1b[1;31m"),
+        Level::Warn => ("warning", "\x1b[1;33m"),
+        Level::Info | Level::Debug | Level::Trace => ("note", "\x1b[1;32m"),
+    };
+    let headline = site.headline().to_lowercase();
+    let mut header = format!("{} in `{}`", headline, escape_format(&site.function));
+    if let Some((name, version)) = package(args) {
+        header.push_str(&format!(
+            " [{} v{}]",
+            escape_format(&name),
+            escape_format(&version)
+        ));
+    }
+    if let Some(build) = build(args) {
+        header.push_str(&format!(" [build={}]", escape_format(&build)));
+    }
+    if let Some(ref name) = site.name {
+        header.push_str(&format!(" [name={}]", escape_format(name)));
+    }
+    if let Some(ref group) = site.group {
+        header.push_str(&format!(" [group={}]", escape_format(group)));
+    }
+    if let Some(ref label) = site.label {
+        header.push_str(&format!(" [{}]", escape_format(label)));
+    }
+    if let Some(ref step) = site.step {
+        header.push_str(&format!(" in `{}`", escape_format(step)));
+    }
+    if args.site_ids.unwrap_or(false) {
+        header.push_str(&format!(" [site={:08x} hits={{}}]", site.id));
+    }
+    if let Some(n) = args.sample {
+        header.push_str(&format!(" [sample=1/{} suppressed={{}}]", n));
+    }
+    if args.counts.unwrap_or(false) {
+        header.push_str(" (seen {}\u{d7})");
+    }
+    if args.error_ids.unwrap_or(false) {
+        header.push_str(" [error={}]");
+    }
+    if args.host_info.unwrap_or(false) {
+        header.push_str(" [host={} pid={}]");
+    }
+    if args.task_ids.unwrap_or(false) {
+        header.push_str(" [task={}]");
+    }
+    if args.callers.unwrap_or(false) {
+        header.push_str(" [callers={}]");
+    }
+    if args.context.unwrap_or(false) {
+        header.push_str(" [context={}]");
+    }
+    if args.elapsed.unwrap_or(false) {
+        header.push_str(" after {:?}");
+    }
+    if args.timing.unwrap_or(false) {
+        header.push_str(" took {:?}");
+    }
+    if args.delta.unwrap_or(false) {
+        header.push_str(" +{:?}");
+    }
+
+    let fields: Vec<String> = args
+        .all_fields()
+        .iter()
+        .map(|field| format!("{} = {{:?}}", escape_format(&expr_text(field))))
+        .collect();
+    let fields = match fields.is_empty() {
+        true => String::new(),
+        false => format!(" ({})", fields.join(", ")),
+    };
+
+    let mut details = String::new();
+    if args.verbosity() == Verbosity::Full {
+        details.push_str("{}");
+    }
+    if args.debug_repr.unwrap_or(false) {
+        details.push_str(&format!("\n    Debug: {}", debug_placeholder));
+    }
+    if args.verbosity() == Verbosity::Full {
+        details.push_str("\n    Stack backtrace:\n{}");
+    }
+    if let Some(ref snippet) = site.snippet {
+        details.push_str(&format!("\n{}", escape_format(snippet)));
+    }
+    if runtime_verbosity(args) {
+        details.push_str("{}");
+    }
+
+    let prefix = format!("{}{}", indent_str(args), prefix_str(args));
+    let location = site.rustc_location_str();
+    if style == Style::Colored {
+        let location = match site.link {
+            Some(ref link) => format!(
+                "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+                escape_format(link),
+                location
+            ),
+            None => location,
+        };
+        format!(
+            "{}{}{}\x1b[0m\x1b[1m: {}: {}{}{}{}\x1b[0m\n  \x1b[1;34m-->\x1b[0m {}{}",
+            prefix,
+            color,
+            level,
+            header,
+            placeholder,
+            io_placeholder(args),
+            type_placeholder(args, site),
+            fields,
+            location,
+            details
+        )
+    } else {
+        format!(
+            "{}{}: {}: {}{}{}{}\n  --> {}{}",
+            prefix,
+            level,
+            header,
+            placeholder,
+            io_placeholder(args),
+            type_placeholder(args, site),
+            fields,
+            location,
+            details
+        )
+    }
+}
+
+/// Returns the name and version of the package that is being compiled, if `package` is set. These
+/// are read from the environment that Cargo compiles the package in, when the macro is expanded.
+fn package(args: &DebugTryArgs) -> Option<(String, String)> {
+    if !args.package.unwrap_or(false) {
+        return None;
+    }
+    let name = env::var("CARGO_PKG_NAME").ok()?;
+    let version = env::var("CARGO_PKG_VERSION").unwrap_or_default();
+    Some((name, version))
+}
+
+/// Returns what identifies the build with `build_info`, from the environment that the function is
+/// compiled in, e.g. the commit set by a build script with `cargo:rustc-env=GIT_HASH=...`, or
+/// `None` if the variable is not set.
+fn build(args: &DebugTryArgs) -> Option<String> {
+    match args.build_info {
+        Some(BuildInfo::Default) => BUILD_VARS.iter().find_map(|name| env::var(name).ok()),
+        Some(BuildInfo::Var(ref name)) => env::var(name).ok(),
+        Some(BuildInfo::Off) | None => None,
+    }
+}
+
+/// Returns the placeholder for the indentation that starts a text message with `indent`, or
+/// nothing.
+fn indent_str(args: &DebugTryArgs) -> &'static str {
+    if args.indent.unwrap_or(false) {
+        "{}"
+    } else {
+        ""
+    }
+}
+
+/// Returns the value for the placeholder of `indent_str`, if any.
+fn indent_value(args: &DebugTryArgs) -> Option<TokenStream> {
+    if args.indent.unwrap_or(false) {
+        Some(quote!(::debug_try::__private::indent()))
+    } else {
+        None
+    }
+}
+
+/// Returns the `prefix` argument as it starts a text message, e.g. `[payments] `, or nothing.
+fn prefix_str(args: &DebugTryArgs) -> String {
+    match args.prefix {
+        Some(ref prefix) => format!("[{}] ", escape_format(prefix)),
+        None => String::new(),
+    }
+}
+
+/// Returns the `prefix` argument as the first member of a JSON message, or nothing.
+fn json_prefix(args: &DebugTryArgs) -> String {
+    match args.prefix {
+        Some(ref prefix) => format!("\"prefix\":{},", escape_format(&json_string(prefix))),
+        None => String::new(),
+    }
+}
+
+/// Builds the format string for a message as a JSON object on a single line. The placeholders for
+/// the error and the fields are inside JSON strings, and their values are escaped at run time.
+fn json_format_str(args: &DebugTryArgs, site: &Site) -> String {
+    let string = |text: &str| escape_format(&json_string(text));
+
+    let (file, line, column) = json_location(site);
+    let mut members = vec![
+        format!("\"file\":{}", file),
+        format!("\"line\":{}", line),
+        format!("\"column\":{}", column),
+        format!("\"fn\":{}", string(&site.function)),
+    ];
+    if let Some(ref name) = site.name {
+        members.push(format!("\"name\":{}", string(name)));
+    }
+    if let Some(ref group) = site.group {
+        members.push(format!("\"group\":{}", string(group)));
+    }
+    if let Some(ref prefix) = args.prefix {
+        members.insert(0, format!("\"prefix\":{}", string(prefix)));
+    }
+    if let Some((name, version)) = package(args) {
+        members.push(format!("\"package\":{}", string(&name)));
+        members.push(format!("\"package_version\":{}", string(&version)));
+    }
+    if let Some(build) = build(args) {
+        members.push(format!("\"build\":{}", string(&build)));
+    }
+    if let Some(ref label) = site.label {
+        members.push(format!("\"label\":{}", string(label)));
+    }
+    if let Some(ref step) = site.step {
+        members.push(format!("\"step\":{}", string(step)));
+    }
+    match site.kind {
+        SiteKind::Try => {}
+        SiteKind::Unwrap => members.push("\"kind\":\"unwrap\"".to_owned()),
+        SiteKind::Discard => members.push("\"kind\":\"discard\"".to_owned()),
+        SiteKind::Ignore => members.push("\"kind\":\"ignore\"".to_owned()),
+        SiteKind::Retry => members.push("\"kind\":\"retry\"".to_owned()),
+        SiteKind::Yeet => members.push("\"kind\":\"yeet\"".to_owned()),
+        SiteKind::Default => members.push("\"kind\":\"default\"".to_owned()),
+    }
+    if args.site_ids.unwrap_or(false) {
+        members.push(format!("\"site\":\"{:08x}\"", site.id));
+        members.push("\"hits\":{}".to_owned());
+    }
+    if let Some(n) = args.sample {
+        members.push(format!("\"sample\":{}", n));
+        members.push("\"suppressed\":{}".to_owned());
+    }
+    if args.counts.unwrap_or(false) {
+        members.push("\"seen\":{}".to_owned());
+    }
+    if args.error_ids.unwrap_or(false) {
+        members.push("\"error_id\":{}".to_owned());
+    }
+    if args.host_info.unwrap_or(false) {
+        members.push("\"host\":\"{}\"".to_owned());
+        members.push("\"pid\":{}".to_owned());
+    }
+    if args.task_ids.unwrap_or(false) {
+        members.push("\"task\":{}".to_owned());
+    }
+    if args.callers.unwrap_or(false) {
+        members.push("\"callers\":{}".to_owned());
+    }
+    if args.context.unwrap_or(false) {
+        members.push("\"context\":{}".to_owned());
+    }
+    if args.elapsed.unwrap_or(false) {
+        members.push("\"elapsed\":\"{:?}\"".to_owned());
+    }
+    if args.timing.unwrap_or(false) {
+        members.push("\"duration\":\"{:?}\"".to_owned());
+    }
+    if args.delta.unwrap_or(false) {
+        members.push("\"delta\":\"{:?}\"".to_owned());
+    }
+    // the kind and the code of an I/O error, and the names of the types, are further members
+    members.push(format!(
+        "\"error\":\"{{}}\"{}{}",
+        io_placeholder(args),
+        type_placeholder(args, site)
+    ));
+    let fields = args.all_fields();
+    if !fields.is_empty() {
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| format!("{}:\"{{}}\"", string(&expr_text(field))))
+            .collect();
+        members.push(format!("\"fields\":{{{{{}}}}}", fields.join(",")));
+    }
+    if args.debug_repr.unwrap_or(false) {
+        members.push("\"debug\":\"{}\"".to_owned());
+    }
+    if let Some(ref snippet) = site.snippet {
+        members.push(format!("\"snippet\":{}", string(snippet)));
+    }
+
+    format!("{{{{{}}}}}", members.join(","))
+}
+
+/// Returns the file, line and column of the site as JSON values in a format string, which are
+/// placeholders if the location is looked up at run time.
+fn json_location(site: &Site) -> (String, String, String) {
+    if site.runtime {
+        ("\"{}\"".to_owned(), "{}".to_owned(), "{}".to_owned())
+    } else {
+        (
+            escape_format(&json_string(&site.file)),
+            site.line.to_string(),
+            site.column.to_string(),
+        )
+    }
+}
+
+/// Returns the `prefix` argument as the first pair of a logfmt line, or nothing.
+fn logfmt_prefix(args: &DebugTryArgs) -> String {
+    match args.prefix {
+        Some(ref prefix) => format!("prefix={} ", escape_format(&logfmt_value(prefix))),
+        None => String::new(),
+    }
+}
+
+/// Builds the format string for a message as a line of `key=value` pairs in the logfmt format,
+/// e.g. `level=error msg=propagated file=src/main.rs line=10 column=30 fn=main err="..."`. The
+/// placeholders for the error and the fields are inside quoted values, and their values are
+/// escaped at run time.
+fn logfmt_format_str(args: &DebugTryArgs, site: &Site) -> String {
+    let value = |text: &str| escape_format(&logfmt_value(text));
+
+    let msg = match site.kind {
+        SiteKind::Try => "propagated",
+        SiteKind::Unwrap => "\"unwrap failed\"",
+        SiteKind::Discard => "discarded",
+        SiteKind::Ignore => "ignored",
+        SiteKind::Retry => "\"attempt failed\"",
+        SiteKind::Yeet => "returned",
+        SiteKind::Default => "defaulted",
+    };
+    let (file, line, column) = logfmt_location(site);
+    let mut pairs = vec![
+        format!("level={}", level_name(backend_level(args))),
+        format!("msg={}", msg),
+        format!("file={}", file),
+        format!("line={}", line),
+        format!("column={}", column),
+        format!("fn={}", value(&site.function)),
+    ];
+    if let Some(ref name) = site.name {
+        pairs.push(format!("name={}", value(name)));
+    }
+    if let Some(ref group) = site.group {
+        pairs.push(format!("group={}", value(group)));
+    }
+    if let Some(ref prefix) = args.prefix {
+        pairs.insert(0, format!("prefix={}", value(prefix)));
+    }
+    if let Some((name, version)) = package(args) {
+        pairs.push(format!("package={}", value(&name)));
+        pairs.push(format!("package_version={}", value(&version)));
+    }
+    if let Some(build) = build(args) {
+        pairs.push(format!("build={}", value(&build)));
+    }
+    if let Some(ref label) = site.label {
+        pairs.push(format!("label={}", value(label)));
+    }
+    if let Some(ref step) = site.step {
+        pairs.push(format!("step={}", value(step)));
+    }
+    if args.site_ids.unwrap_or(false) {
+        pairs.push(format!("site={:08x}", site.id));
+        pairs.push("hits={}".to_owned());
+    }
+    if let Some(n) = args.sample {
+        pairs.push(format!("sample={}", n));
+        pairs.push("suppressed={}".to_owned());
+    }
+    if args.counts.unwrap_or(false) {
+        pairs.push("seen={}".to_owned());
+    }
+    if args.error_ids.unwrap_or(false) {
+        pairs.push("error_id={}".to_owned());
+    }
+    if args.host_info.unwrap_or(false) {
+        pairs.push("host=\"{}\"".to_owned());
+        pairs.push("pid={}".to_owned());
+    }
+    if args.task_ids.unwrap_or(false) {
+        pairs.push("task={}".to_owned());
+    }
+    if args.callers.unwrap_or(false) {
+        pairs.push("callers=\"{}\"".to_owned());
+    }
+    if args.context.unwrap_or(false) {
+        pairs.push("context=\"{}\"".to_owned());
+    }
+    if args.elapsed.unwrap_or(false) {
+        pairs.push("elapsed={:?}".to_owned());
+    }
+    if args.timing.unwrap_or(false) {
+        pairs.push("duration={:?}".to_owned());
+    }
+    if args.delta.unwrap_or(false) {
+        pairs.push("delta={:?}".to_owned());
+    }
+    pairs.push(format!(
+        "err=\"{{}}\"{}{}",
+        io_placeholder(args),
+        type_placeholder(args, site)
+    ));
+    for field in args.all_fields() {
+        pairs.push(format!("{}=\"{{}}\"", logfmt_key(&expr_text(field))));
+    }
+    if args.debug_repr.unwrap_or(false) {
+        pairs.push("debug=\"{}\"".to_owned());
+    }
+    if let Some(ref snippet) = site.snippet {
+        pairs.push(format!("snippet={}", value(snippet)));
+    }
+
+    pairs.join(" ")
+}
+
+/// Returns the file, line and column of the site as logfmt values in a format string, which are
+/// placeholders if the location is looked up at run time.
+fn logfmt_location(site: &Site) -> (String, String, String) {
+    if site.runtime {
+        ("\"{}\"".to_owned(), "{}".to_owned(), "{}".to_owned())
+    } else {
+        (
+            escape_format(&logfmt_value(&site.file)),
+            site.line.to_string(),
+            site.column.to_string(),
+        )
+    }
+}
+
+/// Encodes the text as a logfmt value, which is quoted and escaped like a JSON string if it is
+/// empty or contains spaces, quotes, `=` or control characters, and left as it is otherwise.
+fn logfmt_value(text: &str) -> String {
+    let bare = !text.is_empty()
+        && !text
+            .chars()
+            .any(|ch| ch == ' ' || ch == '"' || ch == '=' || ch == '\\' || ch.is_control());
+    if bare {
+        text.to_owned()
+    } else {
+        json_string(text)
+    }
+}
+
+/// Turns the text of a field into a logfmt key, by replacing the characters that can not be part
+/// of a key with underscores, e.g. `user.id` stays as it is and `ids[0]` becomes `ids_0_`.
+fn logfmt_key(text: &str) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-' => ch,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Returns the entry of the site map for the given site, as a JSON object on a single line, e.g.
+/// `{"file":"src/lib.rs","line":10,"column":30,"function":"read","site":"1a2b3c4d","kind":"try"}`.
+pub(crate) fn site_map_entry(site: &Site) -> String {
+    let kind = match site.kind {
+        SiteKind::Try => "try",
+        SiteKind::Unwrap => "unwrap",
+        SiteKind::Discard => "discard",
+        SiteKind::Ignore => "ignore",
+        SiteKind::Retry => "retry",
+        SiteKind::Yeet => "yeet",
+        SiteKind::Default => "default",
+    };
+    let mut members = vec![
+        format!("\"file\":{}", json_string(&site.file)),
+        format!("\"line\":{}", site.line),
+        format!("\"column\":{}", site.column),
+        format!("\"function\":{}", json_string(&site.function)),
+        format!("\"site\":\"{:08x}\"", site.id),
+        format!("\"kind\":\"{}\"", kind),
+    ];
+    if let Some(ref name) = site.name {
+        members.push(format!("\"name\":{}", json_string(name)));
+    }
+    if let Some(ref group) = site.group {
+        members.push(format!("\"group\":{}", json_string(group)));
+    }
+    if let Some(ref label) = site.label {
+        members.push(format!("\"label\":{}", json_string(label)));
+    }
+    if let Some(ref step) = site.step {
+        members.push(format!("\"step\":{}", json_string(step)));
+    }
+    format!("{{{}}}", members.join(","))
+}
+
+/// Encodes the text as a JSON string, with quotes.
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => result.push(ch),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Escapes braces, so that the given text can be used in a format string.
+fn escape_format(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+/// Renders an expression as compact source text, e.g. `cfg.path`.
+pub(crate) fn expr_text(expr: &Expr) -> String {
+    expr.into_token_stream()
+        .to_string()
+        .split_whitespace()
+        .collect()
+}
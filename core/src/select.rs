@@ -0,0 +1,138 @@
+use proc_macro2::{TokenStream, TokenTree};
+
+use syn::{
+    parse::{self, Parse, ParseStream},
+    Expr, Ident, Token,
+};
+
+use quote::{quote, ToTokens};
+
+/// The body of a `select!` macro of `tokio` or `futures`, e.g.
+/// `biased; Some(v) = rx.recv(), if open => handle(v)?, else => break`.
+pub(crate) struct Select {
+    /// The `biased;` before the branches, if any.
+    biased: Option<(Ident, Token![;])>,
+    branches: Vec<Branch>,
+}
+
+/// A branch of a `select!`, `pattern = future, if condition => handler,`, or
+/// `else => handler,` (or `default` and `complete` in `futures`), which has no future.
+struct Branch {
+    /// The pattern, or the keyword of a branch without a future.
+    pattern: TokenStream,
+    future: Option<(Token![=], Expr)>,
+    guard: Option<(Token![,], Token![if], Expr)>,
+    arrow: Token![=>],
+    handler: Expr,
+    comma: Option<Token![,]>,
+}
+
+impl Select {
+    /// Returns the expressions of the branches: the futures, conditions and handlers.
+    pub fn exprs_mut(&mut self) -> impl Iterator<Item = &mut Expr> {
+        self.branches.iter_mut().flat_map(|branch| {
+            let future = branch.future.as_mut().map(|(_, future)| future);
+            let guard = branch.guard.as_mut().map(|(_, _, guard)| guard);
+            future
+                .into_iter()
+                .chain(guard)
+                .chain(Some(&mut branch.handler))
+        })
+    }
+}
+
+impl Parse for Select {
+    fn parse(input: ParseStream) -> parse::Result<Select> {
+        let biased = if input.peek(Ident) && input.peek2(Token![;]) {
+            let ident: Ident = input.parse()?;
+            if ident != "biased" {
+                return Err(parse::Error::new(ident.span(), "Expected `biased`"));
+            }
+            Some((ident, input.parse()?))
+        } else {
+            None
+        };
+
+        let mut branches = Vec::new();
+        while !input.is_empty() {
+            branches.push(input.parse()?);
+        }
+        Ok(Select { biased, branches })
+    }
+}
+
+impl Parse for Branch {
+    fn parse(input: ParseStream) -> parse::Result<Branch> {
+        // the pattern ends at the first `=` that is not part of `=>`, `==` or `..=`
+        let mut pattern = TokenStream::new();
+        while !input.is_empty() {
+            if input.peek(Token![=>]) || (input.peek(Token![=]) && !input.peek(Token![==])) {
+                break;
+            }
+            if input.peek(Token![..=]) {
+                let range: Token![..=] = input.parse()?;
+                range.to_tokens(&mut pattern);
+                continue;
+            }
+            let token: TokenTree = input.parse()?;
+            pattern.extend(Some(token));
+        }
+
+        let future = if input.peek(Token![=>]) {
+            None
+        } else {
+            Some((input.parse()?, input.parse()?))
+        };
+        let guard = if input.peek(Token![,]) && input.peek2(Token![if]) {
+            Some((input.parse()?, input.parse()?, input.parse()?))
+        } else {
+            None
+        };
+        let arrow = input.parse()?;
+        let handler = input.parse()?;
+        let comma = if input.is_empty() {
+            None
+        } else {
+            match handler {
+                // the comma is optional after a block
+                Expr::Block(_) if !input.peek(Token![,]) => None,
+                _ => Some(input.parse()?),
+            }
+        };
+
+        Ok(Branch {
+            pattern,
+            future,
+            guard,
+            arrow,
+            handler,
+            comma,
+        })
+    }
+}
+
+impl ToTokens for Select {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        if let Some((ref ident, ref semi)) = self.biased {
+            tokens.extend(quote!(#ident #semi));
+        }
+        for branch in &self.branches {
+            branch.to_tokens(tokens);
+        }
+    }
+}
+
+impl ToTokens for Branch {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.pattern.to_tokens(tokens);
+        if let Some((ref eq, ref future)) = self.future {
+            tokens.extend(quote!(#eq #future));
+        }
+        if let Some((ref comma, ref if_token, ref guard)) = self.guard {
+            tokens.extend(quote!(#comma #if_token #guard));
+        }
+        self.arrow.to_tokens(tokens);
+        self.handler.to_tokens(tokens);
+        self.comma.to_tokens(tokens);
+    }
+}
@@ -0,0 +1,37 @@
+use std::{env, fs::OpenOptions, io::Write};
+
+use crate::report::{self, Site};
+
+/// The environment variable with the path of the site map, read when the macro is expanded.
+pub(crate) const SITE_MAP_ENV: &str = "DEBUG_TRY_SITE_MAP";
+
+/// The entries of the site map for the sites of a function, one JSON object per line.
+#[derive(Default)]
+pub(crate) struct SiteMap {
+    lines: String,
+}
+
+impl SiteMap {
+    /// Adds an entry for the given site, if a site map is exported.
+    pub fn add(&mut self, site: &Site) {
+        if env::var_os(SITE_MAP_ENV).is_some() {
+            self.lines.push_str(&report::site_map_entry(site));
+            self.lines.push('\n');
+        }
+    }
+
+    /// Appends the entries to the file at the path in `DEBUG_TRY_SITE_MAP`, if it is set.
+    ///
+    /// The entries of a function are written at once, so that the entries of functions that are
+    /// expanded in parallel, by different invocations of the compiler, are not interleaved. Errors
+    /// are ignored, as the site map is not needed for the build.
+    pub fn export(&self) {
+        let path = match env::var_os(SITE_MAP_ENV) {
+            Some(path) if !self.lines.is_empty() => path,
+            _ => return,
+        };
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = file.write_all(self.lines.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Renders a token stream as a string with one statement per line, and with the contents of
+/// braces indented.
+pub(crate) fn render(tokens: TokenStream) -> String {
+    let mut printer = Printer {
+        output: String::new(),
+        indent: 0,
+        line_start: true,
+        joint: false,
+        after_brace: false,
+    };
+    printer.print_stream(tokens);
+    printer.newline();
+    printer.output
+}
+
+struct Printer {
+    output: String,
+    indent: usize,
+    line_start: bool,
+    joint: bool,
+    after_brace: bool,
+}
+
+impl Printer {
+    fn print_stream(&mut self, tokens: TokenStream) {
+        for tt in tokens {
+            self.print_tree(tt);
+        }
+    }
+
+    fn print_tree(&mut self, tt: TokenTree) {
+        match tt {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+
+                if group.delimiter() == Delimiter::Brace && !group.stream().is_empty() {
+                    self.word(open);
+                    self.indent += 1;
+                    self.newline();
+                    self.print_stream(group.stream());
+                    self.indent -= 1;
+                    self.newline();
+                    self.word(close);
+                    self.after_brace = true;
+                } else {
+                    self.word(open);
+                    self.joint = true;
+                    self.print_stream(group.stream());
+                    self.joint = true;
+                    self.word(close);
+                }
+            }
+
+            TokenTree::Punct(punct) => {
+                let ch = punct.as_char();
+                self.word(&ch.to_string());
+                if ch == ';' {
+                    self.newline();
+                } else {
+                    self.joint = punct.spacing() == Spacing::Joint;
+                }
+            }
+
+            tt => self.word(&tt.to_string()),
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        // only a few tokens continue the line after a closing brace, e.g. `} else {` or `})?`
+        if self.after_brace && !["else", ",", ".", ")", "]", "?", ";"].contains(&word) {
+            self.newline();
+        }
+        self.after_brace = false;
+
+        if self.line_start {
+            self.output.extend((0..self.indent).map(|_| "    "));
+        } else if !self.joint {
+            self.output.push(' ');
+        }
+        self.output.push_str(word);
+        self.line_start = false;
+        self.joint = false;
+    }
+
+    fn newline(&mut self) {
+        if !self.line_start {
+            self.output.push('\n');
+            self.line_start = true;
+        }
+    }
+}
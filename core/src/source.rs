@@ -0,0 +1,80 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use proc_macro2::Span;
+
+use crate::report;
+
+/// A cache of the source files read while expanding the macro.
+#[derive(Default)]
+pub(crate) struct Sources {
+    /// Lines of the source files that have been read so far, or `None` if a file could not be
+    /// read.
+    files: HashMap<PathBuf, Option<Vec<String>>>,
+}
+
+impl Sources {
+    /// Returns the lines of the source file of the given span, and the (1-based) line number of
+    /// the start of the span, or `None` when the source is not available.
+    pub fn lines(&mut self, span: Span) -> Option<(&[String], usize)> {
+        if !proc_macro::is_available() {
+            return None;
+        }
+
+        let span = report::resolve_span(span.unwrap());
+        let path = span.local_file()?;
+        let line = span.start().line();
+
+        let lines = self
+            .files
+            .entry(path)
+            .or_insert_with_key(|path| {
+                fs::read_to_string(path)
+                    .ok()
+                    .map(|source| source.lines().map(str::to_owned).collect())
+            })
+            .as_ref()?;
+        Some((lines, line))
+    }
+
+    /// Renders the source line of `end`, underlining the text from `start` to `end` (inclusive)
+    /// with carets, in the style of rustc diagnostics. If `start` is on an earlier line, only
+    /// `end` is underlined.
+    pub fn snippet(&mut self, start: Span, end: Span) -> Option<String> {
+        if !proc_macro::is_available() {
+            return None;
+        }
+
+        let columns = |span: Span| {
+            let span = report::resolve_span(span.unwrap()).start();
+            (span.line(), span.column())
+        };
+        let (start_line, start_column) = columns(start);
+        let (end_line, end_column) = columns(end);
+        let start_column = if start_line == end_line && start_column <= end_column {
+            start_column
+        } else {
+            end_column
+        };
+
+        let (lines, line) = self.lines(end)?;
+        let text = lines.get(line - 1)?;
+
+        // keep tabs in the indentation, so that the carets line up with the text
+        let indent: String = text
+            .chars()
+            .take(start_column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        let carets = "^".repeat(end_column - start_column + 1);
+
+        let gutter = " ".repeat(line.to_string().len());
+        Some(format!(
+            "{gutter} |\n{line} | {text}\n{gutter} | {indent}{carets}",
+            gutter = gutter,
+            line = line,
+            text = text,
+            indent = indent,
+            carets = carets
+        ))
+    }
+}
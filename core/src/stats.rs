@@ -0,0 +1,122 @@
+use proc_macro2::{TokenStream, TokenTree};
+
+use syn::{
+    visit_mut::{self, VisitMut},
+    Block, Expr, ExprAsync, ExprClosure, ExprTry, Ident, ItemFn, Macro,
+};
+
+/// The sites, closures and inner functions of a function that were instrumented, and those that
+/// were left as they are, noted when the function is compiled with `stats`.
+#[derive(Default)]
+pub(crate) struct Stats {
+    /// Whether the `?` operators that are left as they are are counted, which takes a walk over
+    /// the code that is skipped.
+    pub enabled: bool,
+    pub sites: usize,
+    pub closures: usize,
+    pub inner_fns: usize,
+    pub skipped_tries: usize,
+    pub skipped_closures: usize,
+    pub skipped_inner_fns: usize,
+}
+
+impl Stats {
+    /// Counts the `?` operators in the given code as left as they are. With `nested`, those of the
+    /// `async` blocks, closures and inner functions in it are counted too, unless they are visited
+    /// on their own.
+    pub fn skip_block(&mut self, block: &Block, nested: bool) {
+        if self.enabled {
+            let mut tries = Tries { count: 0, nested };
+            tries.visit_block_mut(&mut block.clone());
+            self.skipped_tries += tries.count;
+        }
+    }
+
+    /// Like [`Stats::skip_block`], for an expression like the body of a closure.
+    pub fn skip_expr(&mut self, expr: &Expr, nested: bool) {
+        if self.enabled {
+            let mut tries = Tries { count: 0, nested };
+            tries.visit_expr_mut(&mut expr.clone());
+            self.skipped_tries += tries.count;
+        }
+    }
+
+    /// Counts the `?` operators in the tokens of a macro that is not substituted in.
+    pub fn skip_macro(&mut self, tokens: &TokenStream) {
+        if self.enabled {
+            self.skipped_tries += macro_tries(tokens);
+        }
+    }
+
+    /// Shows the counts of the function with the given name as a compiler note.
+    pub fn note(&self, ident: &Ident) {
+        let message = format!(
+            "`{}`: instrumented {}, {} and {}; left {}, {} and {} as they are",
+            ident,
+            plural(self.sites, "site"),
+            plural(self.closures, "closure"),
+            plural(self.inner_fns, "inner function"),
+            plural(self.skipped_tries, "`?` operator"),
+            plural(self.skipped_closures, "closure"),
+            plural(self.skipped_inner_fns, "inner function"),
+        );
+        crate::note(ident, message);
+    }
+}
+
+/// Returns the count with the noun, e.g. `1 site` or `2 sites`.
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// Counts the `?` operators in code, including those in macros, which are found as `?` tokens.
+struct Tries {
+    count: usize,
+    /// Whether the `?` operators of `async` blocks, closures and inner functions are counted.
+    nested: bool,
+}
+
+impl VisitMut for Tries {
+    fn visit_expr_try_mut(&mut self, i: &mut ExprTry) {
+        self.count += 1;
+        visit_mut::visit_expr_try_mut(self, i);
+    }
+
+    fn visit_expr_async_mut(&mut self, i: &mut ExprAsync) {
+        if self.nested {
+            visit_mut::visit_expr_async_mut(self, i);
+        }
+    }
+
+    fn visit_expr_closure_mut(&mut self, i: &mut ExprClosure) {
+        if self.nested {
+            visit_mut::visit_expr_closure_mut(self, i);
+        }
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        if self.nested {
+            visit_mut::visit_item_fn_mut(self, i);
+        }
+    }
+
+    fn visit_macro_mut(&mut self, i: &mut Macro) {
+        self.count += macro_tries(&i.tts);
+    }
+}
+
+/// Returns the number of `?` tokens in the tokens of a macro.
+fn macro_tries(tokens: &TokenStream) -> usize {
+    tokens
+        .clone()
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == '?' => 1,
+            TokenTree::Group(ref group) => macro_tries(&group.stream()),
+            _ => 0,
+        })
+        .sum()
+}
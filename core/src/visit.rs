@@ -0,0 +1,1281 @@
+use std::mem;
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+
+use syn::{
+    parse::{self, ParseStream, Parser},
+    parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    visit_mut::{self, VisitMut},
+    ArgCaptured, Block, Expr, ExprAsync, ExprCall, ExprClosure, ExprMethodCall, ExprTry,
+    ExprTryBlock, ExprUnary, FnArg, FnDecl, GenericArgument, GenericParam, Ident, Item, ItemFn,
+    Macro, Member, Pat, Path, PathArguments, ReturnType, Stmt, Token, Type, TypeParamBound, UnOp,
+};
+
+use quote::{quote, quote_spanned, ToTokens};
+
+use crate::{
+    args::{Bound, DebugTryArgs, Labels, Repropagated},
+    label,
+    report::{self, Site, SiteKind},
+    select::Select,
+    site_map::SiteMap,
+    source::Sources,
+    stats::Stats,
+    yeet, Diagnostic,
+};
+
+pub(crate) fn instrument(
+    args: &DebugTryArgs,
+    mut input: ItemFn,
+) -> Result<ItemFn, Vec<Diagnostic>> {
+    if let Some(constness) = input.constness {
+        return Err(vec![Diagnostic::error(
+            constness.span,
+            "`debug_try` can not be used on `const` functions, since messages can not be printed \
+             while evaluating constants",
+        )]);
+    }
+    let output = fn_output(&mut input);
+    if return_kind(&output) == ReturnKind::Other {
+        let span = match input.decl.output {
+            ReturnType::Type(_, ref ty) => ty.span(),
+            ReturnType::Default => input.ident.span(),
+        };
+        return Err(vec![Diagnostic::error(
+            span,
+            "`debug_try` can only be used on functions that return `Result`",
+        )]);
+    }
+
+    let mut visitor = Visitor::new(args, error_type(&output));
+    visitor.instrument_fn(&mut input);
+
+    if visitor.diags.is_empty() {
+        visitor.site_map.export();
+        if args.stats.unwrap_or(false) {
+            visitor.stats.note(&input.ident);
+        }
+        Ok(input)
+    } else {
+        Err(visitor.diags)
+    }
+}
+
+struct Visitor<'a> {
+    args: &'a DebugTryArgs,
+    diags: Vec<Diagnostic>,
+    sources: Sources,
+    /// The name of the innermost function being instrumented.
+    function: String,
+    /// The label of the innermost statement that has one.
+    label: Option<String>,
+    /// Whether the next `?` operator is the first one of a chain like `a()?.b()?`.
+    chain_start: bool,
+    /// The entries of the site map for the sites instrumented so far.
+    site_map: SiteMap,
+    /// The error type of the innermost function or closure, if its return type names one.
+    target: Option<Type>,
+    /// Whether the next `async` block is the body of a function that returns a boxed future, see
+    /// [`fn_output`].
+    future_body: bool,
+    /// What was instrumented so far, and what was left as it is, for `stats`.
+    stats: Stats,
+}
+
+/// Instruments the `?` operators of a block, for the block form of `debug_try_item!`, whose
+/// messages name the function as `<block>`, since it is not known. The block starts with the
+/// statements that run when a function is entered, like the counter of `max_errors`.
+pub(crate) fn instrument_block(
+    args: &DebugTryArgs,
+    mut input: Block,
+) -> Result<Block, Vec<Diagnostic>> {
+    let mut visitor = Visitor::new(args, None);
+    visitor.function = "<block>".to_owned();
+    visitor.visit_block_mut(&mut input);
+
+    let entry = report::entry_tokens(args, &visitor.function, true);
+    if !entry.is_empty() {
+        let entry: Block = parse_quote!({ #entry });
+        input.stmts.splice(0..0, entry.stmts);
+    }
+
+    if visitor.diags.is_empty() {
+        visitor.site_map.export();
+        Ok(input)
+    } else {
+        Err(visitor.diags)
+    }
+}
+
+impl<'a> Visitor<'a> {
+    fn new(args: &'a DebugTryArgs, target: Option<Type>) -> Visitor<'a> {
+        Visitor {
+            args,
+            diags: Vec::new(),
+            sources: Default::default(),
+            function: String::new(),
+            label: None,
+            chain_start: false,
+            site_map: Default::default(),
+            target,
+            future_body: false,
+            stats: Stats {
+                enabled: args.stats.unwrap_or(false),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Runs `f` with a visitor that uses different arguments.
+    fn with_args<F: FnOnce(&mut Visitor)>(&mut self, args: &DebugTryArgs, f: F) {
+        let mut visitor = Visitor {
+            args,
+            diags: mem::take(&mut self.diags),
+            sources: mem::take(&mut self.sources),
+            function: self.function.clone(),
+            label: self.label.clone(),
+            chain_start: false,
+            site_map: mem::take(&mut self.site_map),
+            target: self.target.clone(),
+            future_body: self.future_body,
+            stats: mem::take(&mut self.stats),
+        };
+        f(&mut visitor);
+
+        self.diags = visitor.diags;
+        self.sources = visitor.sources;
+        self.site_map = visitor.site_map;
+        self.stats = visitor.stats;
+    }
+
+    fn instrument_fn(&mut self, i: &mut ItemFn) {
+        self.function = i.ident.to_string();
+        let output = fn_output(i);
+        self.future_body = boxed_future(&mut i.block).is_some();
+        if self.args.fn_args.unwrap_or(false) {
+            let args = DebugTryArgs {
+                params: Some(self.params(&i.decl)),
+                ..self.args.clone()
+            };
+            self.with_args(&args, |visitor| visit_mut::visit_item_fn_mut(visitor, i));
+        } else {
+            visit_mut::visit_item_fn_mut(self, i);
+        }
+        self.future_body = false;
+
+        if self.args.bound == Some(Bound::Display) {
+            self.bound_display(&mut i.decl, &output);
+        }
+
+        // the body of a boxed future runs when it is polled, like that of an `async fn`
+        let future = boxed_future(&mut i.block).is_some();
+        let frame = i.asyncness.is_none() && !future;
+        let entry = report::entry_tokens(self.args, &self.function, frame);
+        if !entry.is_empty() {
+            let entry: Block = parse_quote!({ #entry });
+            let block = match boxed_future(&mut i.block) {
+                Some(future) => &mut future.block,
+                None => &mut *i.block,
+            };
+            block.stmts.splice(0..0, entry.stmts);
+        }
+
+        if self.args.span.unwrap_or(false) {
+            self.enter_span(i, &output);
+        }
+    }
+
+    /// Runs the body of the function in its span, with `span`. The span is entered for the body of
+    /// a plain function, and instruments the future of an `async fn` or a boxed future, since a
+    /// span that is entered must not be held across `.await`.
+    fn enter_span(&mut self, i: &mut ItemFn, output: &ReturnType) {
+        let span = report::span_tokens(self.args, &self.function);
+        if let Some(future) = boxed_future_expr(&mut i.block) {
+            *future = parse_quote!(::tracing::Instrument::instrument(#future, #span));
+            return;
+        }
+        let block = &i.block;
+        *i.block = match (i.asyncness, output) {
+            // the output is named, so that `?` in the block converts errors to it
+            (Some(_), ReturnType::Type(_, ty)) => parse_quote!({
+                ::tracing::Instrument::instrument(
+                    async move {
+                        let __debug_try_output: #ty = #block;
+                        __debug_try_output
+                    },
+                    #span,
+                )
+                .await
+            }),
+            _ => parse_quote!({
+                let __debug_try_span = #span.entered();
+                #block
+            }),
+        };
+    }
+
+    /// Adds a `Display` bound for the error type of the function to its `where` clause, with
+    /// `bound = "Display"`, so that errors of a type parameter like `E: From<io::Error>` can be
+    /// reported.
+    fn bound_display(&mut self, decl: &mut FnDecl, output: &ReturnType) {
+        let target = match error_type(output) {
+            Some(target) => target,
+            None => {
+                let span = match decl.output {
+                    ReturnType::Type(_, ref ty) => ty.span(),
+                    ReturnType::Default => decl.fn_token.span,
+                };
+                self.diags.push(Diagnostic::error(
+                    span,
+                    "`bound = \"Display\"` requires a return type like `Result<T, E>`",
+                ));
+                return;
+            }
+        };
+        // a type parameter with bounds of its own gets the bound next to them, since clippy warns
+        // about bounds in more than one place
+        let path = match target {
+            Type::Path(ref ty) if ty.qself.is_none() => Some(&ty.path),
+            _ => None,
+        };
+        let param = decl
+            .generics
+            .params
+            .iter_mut()
+            .find_map(|param| match param {
+                GenericParam::Type(param)
+                    if path.is_some_and(|path| path.is_ident(param.ident.clone()))
+                        && !param.bounds.is_empty() =>
+                {
+                    Some(param)
+                }
+                _ => None,
+            });
+        match param {
+            Some(param) => param.bounds.push(parse_quote!(::core::fmt::Display)),
+            None => decl
+                .generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#target: ::core::fmt::Display)),
+        }
+    }
+
+    /// Returns the parameters of the function that are captured with `args`, i.e. those that are
+    /// bound to a name, except for `self` and those in `skip(...)`.
+    fn params(&mut self, decl: &FnDecl) -> Vec<Expr> {
+        let names: Vec<&Ident> = decl
+            .inputs
+            .iter()
+            .filter_map(|input| match input {
+                FnArg::Captured(ArgCaptured {
+                    pat: Pat::Ident(pat),
+                    ..
+                }) => Some(&pat.ident),
+                _ => None,
+            })
+            .collect();
+
+        let skip = self.args.skip.as_ref().map_or(&[][..], Vec::as_slice);
+        for skipped in skip {
+            if !names.contains(&skipped) {
+                self.diags.push(Diagnostic::error(
+                    skipped.span(),
+                    format!("`{}` is not a parameter of the function", skipped),
+                ));
+            }
+        }
+
+        names
+            .into_iter()
+            .filter(|name| *name != "self" && !skip.contains(name))
+            .map(|name| parse_quote!(#name))
+            .collect()
+    }
+
+    /// Substitutes in a `select!` or `join!` macro of `tokio` or `futures`. These are recognized by
+    /// their name, with or without a path, so the tokens are left as they are if they do not
+    /// parse, in case it is another macro of the same name.
+    fn visit_async_macro(&mut self, i: &mut Macro) {
+        if last_segment_is(&i.path, "select") || last_segment_is(&i.path, "select_biased") {
+            if let Ok(mut select) = syn::parse2::<Select>(i.tts.clone()) {
+                select
+                    .exprs_mut()
+                    .for_each(|expr| self.visit_expr_mut(expr));
+                i.tts = select.into_token_stream();
+            }
+        } else {
+            let parser = Punctuated::<Expr, Token![,]>::parse_terminated;
+            if let Ok(mut tree) = parser.parse2(i.tts.clone()) {
+                tree.iter_mut().for_each(|item| self.visit_expr_mut(item));
+                i.tts = tree.into_token_stream();
+            }
+        }
+    }
+
+    /// Instruments an `unwrap` or `expect` call, or a call that discards an error, like `ok()`,
+    /// whose receiver and arguments have already been visited, so that the error (or for `unwrap`
+    /// and `expect`, the `None`) is reported like a propagated error before the call.
+    fn instrument_call(&mut self, i: &ExprMethodCall, kind: SiteKind) -> Expr {
+        let span = i.method.span();
+        let ExprMethodCall {
+            attrs,
+            receiver,
+            dot_token,
+            method,
+            turbofish,
+            args,
+            ..
+        } = i;
+        // a place like `handle` is only borrowed, and then called as it is, since its type may have
+        // an `unwrap(&self)` of its own, which must not move it; any other receiver is a temporary,
+        // which is called once it is bound
+        let place = is_place(receiver);
+        let value = if place {
+            quote!(#receiver)
+        } else {
+            quote!(__debug_try_value)
+        };
+        // the call keeps the span of the method, so that the panic is reported at the same location
+        let call = quote_spanned!(span=> #value #dot_token #method #turbofish (#args));
+        let expr = Expr::MethodCall(i.clone());
+        let instrumented = self.instrument_value(kind, span, &expr, receiver, place, call);
+        parse_quote! {
+            #(#attrs)*
+            #instrumented
+        }
+    }
+
+    /// Instruments an expression whose result is ignored, with `let _ = ...` or as a statement, so
+    /// that it is reported if it is an error. The expression has already been visited.
+    fn instrument_ignored(&mut self, expr: &Expr) -> Expr {
+        let span = expr.span();
+        let value = quote_spanned!(span=> __debug_try_value);
+        let instrumented = self.instrument_value(SiteKind::Ignore, span, expr, expr, false, value);
+        parse_quote!(#instrumented)
+    }
+
+    /// Generates a `match` that binds the value to `__debug_try_value`, reports it at the site of
+    /// `expr` if it is an error, and then evaluates to `then`. With `place`, the value is a place
+    /// expression, which is only borrowed to report it, and is not bound.
+    fn instrument_value(
+        &mut self,
+        kind: SiteKind,
+        span: Span,
+        expr: &Expr,
+        value: &Expr,
+        place: bool,
+        then: TokenStream,
+    ) -> TokenStream {
+        // the operator-specific arguments do not apply, as nothing is propagated
+        let args = DebugTryArgs {
+            timing: None,
+            ..self.args.clone()
+        };
+        let mut site = Site::new(&args, span, expr, &self.function, self.label.clone());
+        site.kind = kind;
+        if args.snippet.unwrap_or(false) {
+            if let Some(start) = expr.clone().into_token_stream().into_iter().next() {
+                site.snippet = self.sources.snippet(start.span(), span);
+            }
+        }
+        self.site_map.add(&site);
+        self.stats.sites += 1;
+        let report = report::cold_tokens(&report::report_tokens(&args, &site));
+
+        // a `None` is only a failure for `unwrap` and `expect`
+        let traits = match kind {
+            SiteKind::Unwrap => quote!(UnwrapOption as _, UnwrapOther as _, UnwrapResult as _),
+            _ => quote!(UnwrapOther as _, UnwrapResult as _),
+        };
+        if place {
+            return quote_spanned! {span=>
+                {
+                    let __debug_try_failure = {
+                        use ::debug_try::__private::{#traits};
+                        (&&::debug_try::__private::Unwrapped(&#value)).failure()
+                    };
+                    if let ::core::option::Option::Some(__debug_try_err) = __debug_try_failure {
+                        #report
+                    }
+                    #then
+                }
+            };
+        }
+        // a `match` rather than a `let`, so that the temporaries of the value live as long as
+        // before, with the span of the site, so that lints on the value still point at it
+        quote_spanned! {span=>
+            match #value {
+                __debug_try_value => {
+                    let __debug_try_failure = {
+                        use ::debug_try::__private::{#traits};
+                        (&&::debug_try::__private::Unwrapped(&__debug_try_value)).failure()
+                    };
+                    if let ::core::option::Option::Some(__debug_try_err) = __debug_try_failure {
+                        #report
+                    }
+                    #then
+                }
+            }
+        }
+    }
+
+    /// Wraps the expression of a `?` operator in a loop that evaluates it again, up to `retries`
+    /// times, while it fails, reporting each failed attempt, and then evaluates to the last result.
+    fn retry_expr(&mut self, site: &Site, expr: &Expr, retries: u32) -> Expr {
+        // the operator-specific arguments do not apply, as the error is not propagated
+        let args = DebugTryArgs {
+            timing: None,
+            ..self.args.clone()
+        };
+        let mut site = site.clone();
+        site.kind = SiteKind::Retry;
+        let report = report::cold_tokens(&report::report_tokens(&args, &site));
+        let delay = self.args.retry_delay_ms.map(|ms| {
+            quote! {
+                ::std::thread::sleep(::std::time::Duration::from_millis(#ms));
+            }
+        });
+        parse_quote! {{
+            let mut __debug_try_attempts: u32 = 0;
+            loop {
+                match #expr {
+                    ::core::result::Result::Err(__debug_try_err) if __debug_try_attempts < #retries => {
+                        __debug_try_attempts += 1;
+                        #report
+                        #delay
+                    }
+                    __debug_try_result => break __debug_try_result,
+                }
+            }
+        }}
+    }
+
+    /// Returns the site of a `?` operator, which has not been visited yet.
+    fn try_site(&mut self, args: &DebugTryArgs, i: &ExprTry) -> Site {
+        let span: Span = i.question_token.span();
+        let mut site = Site::new(args, span, &i.expr, &self.function, self.label.clone());
+        // in a chain like `a()?.b()?`, each operator is reported with its own step of the chain,
+        // e.g. `.b()?`, since the messages could hardly be told apart otherwise
+        let text = report::expr_text(&i.expr);
+        let chain_start = mem::replace(&mut self.chain_start, false);
+        site.step = match chained_try(&i.expr) {
+            Some(previous) => {
+                let previous = report::expr_text(&Expr::Try(previous.clone()));
+                text.strip_prefix(&previous)
+                    .map(|step| format!("{}?", step))
+            }
+            None if chain_start => Some(format!("{}?", text)),
+            None => None,
+        };
+        if args.snippet.unwrap_or(false) {
+            if let Some(start) = i.expr.clone().into_token_stream().into_iter().next() {
+                site.snippet = self.sources.snippet(start.span(), span);
+            }
+        }
+        site
+    }
+
+    /// Instruments a `?` operator with `or_default`, so that an error is reported and the
+    /// expression evaluates to the default value of its `Ok` type instead of returning it.
+    fn default_expr(&mut self, i: &ExprTry) -> Expr {
+        // the arguments that apply to the propagated error do not apply, as nothing is propagated
+        let args = DebugTryArgs {
+            timing: None,
+            ..self.args.clone()
+        };
+        let mut site = self.try_site(&args, i);
+        site.kind = SiteKind::Default;
+
+        let mut expr = i.expr.clone();
+        self.chain_start = chained_try(&i.expr).is_some();
+        self.visit_expr_mut(&mut expr);
+        self.chain_start = false;
+        if let Some(retries) = self.args.retry.filter(|&retries| retries > 0) {
+            *expr = self.retry_expr(&site, &expr, retries);
+        }
+
+        self.site_map.add(&site);
+        self.stats.sites += 1;
+        let report = report::cold_tokens(&report::report_tokens(&args, &site));
+        // the report may panic unconditionally, which makes the default value unreachable
+        let allow = match args.panic_on_error {
+            Some(true) => quote!(#[allow(unreachable_code)]),
+            _ => TokenStream::new(),
+        };
+        let attrs = &i.attrs;
+        // the parentheses keep the `match` from being parsed as a statement
+        parse_quote! {
+            #(#attrs)*
+            (match #expr {
+                ::core::result::Result::Ok(__debug_try_ok) => __debug_try_ok,
+                ::core::result::Result::Err(__debug_try_err) => {
+                    #report
+                    #allow
+                    let __debug_try_default = ::core::default::Default::default();
+                    __debug_try_default
+                }
+            })
+        }
+    }
+
+    /// Visits the expression of a `do yeet`, as hidden by [`yeet::hide`], and with `yeets`,
+    /// reports the error it returns at the location of `do`.
+    fn visit_yeet(&mut self, i: &mut Macro) {
+        // a `do yeet` without an expression returns `None`
+        if i.tts.is_empty() {
+            return;
+        }
+        let mut expr: Expr = match syn::parse2(i.tts.clone()) {
+            Ok(expr) => expr,
+            Err(err) => return self.push_paser_error(err),
+        };
+        self.visit_expr_mut(&mut expr);
+        if !self.args.yeets.unwrap_or(false) {
+            i.tts = expr.into_token_stream();
+            return;
+        }
+
+        // the operator-specific arguments do not apply, as nothing is evaluated
+        let args = DebugTryArgs {
+            timing: None,
+            ..self.args.clone()
+        };
+        let span = i.path.span();
+        let mut site = Site::new(&args, span, &expr, &self.function, self.label.clone());
+        site.kind = SiteKind::Yeet;
+        site.target = self.target.clone();
+        self.site_map.add(&site);
+        self.stats.sites += 1;
+        let report = report::cold_tokens(&report::report_tokens(&args, &site));
+        i.tts = quote_spanned! {span=>
+            {
+                let __debug_try_err = #expr;
+                #report
+                __debug_try_err
+            }
+        };
+    }
+
+    /// Visits a closure, which is only instrumented if `nested` is true, unless `nested` is given
+    /// as true for a closure passed to a combinator.
+    fn visit_closure(&mut self, i: &mut ExprClosure, nested: bool) {
+        // closures without a return type are assumed to return a result
+        let returns_result = match i.output {
+            ReturnType::Default => true,
+            ref output => return_kind(output) != ReturnKind::Other,
+        };
+        let args = self.args.closure_args();
+        if !nested || args.disabled() {
+            self.stats.skipped_closures += 1;
+            self.stats.skip_expr(&i.body, true);
+            return;
+        }
+        self.with_args(&args, |visitor| {
+            if returns_result {
+                visitor.stats.closures += 1;
+                visitor.target = error_type(&i.output);
+                visit_mut::visit_expr_closure_mut(visitor, i)
+            } else {
+                visitor.stats.skipped_closures += 1;
+                visitor.stats.skip_expr(&i.body, false);
+                AsyncBlocks(visitor).visit_expr_mut(&mut i.body)
+            }
+        });
+    }
+
+    fn push_paser_error(&mut self, err: parse::Error) {
+        self.diags.push(err.into())
+    }
+}
+
+impl<'a> VisitMut for Visitor<'a> {
+    fn visit_expr_mut(&mut self, i: &mut Expr) {
+        if let Expr::Try(ref try_expr) = *i {
+            if self.args.or_default.unwrap_or(false) {
+                *i = self.default_expr(try_expr);
+                return;
+            }
+        }
+        if let Expr::MethodCall(ref mut call) = *i {
+            let kind = if self.args.unwraps.unwrap_or(false) && is_unwrap(call) {
+                Some(SiteKind::Unwrap)
+            } else if self.args.discards.unwrap_or(false) && is_discard(call) {
+                Some(SiteKind::Discard)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                self.visit_expr_method_call_mut(call);
+                *i = self.instrument_call(call, kind);
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        // only reached for inner functions, which are only visited when nested; those that do not
+        // return a result use `?` on something else, e.g. an `Option`, and `const` ones can not
+        // print messages
+        let output = fn_output(i);
+        let args = self.args.inner_fn_args();
+        if i.constness.is_some() || args.disabled() {
+            self.stats.skipped_inner_fns += 1;
+            self.stats.skip_block(&i.block, true);
+            return;
+        }
+
+        self.with_args(&args, |visitor| {
+            visitor.label = None;
+            if return_kind(&output) == ReturnKind::Other {
+                visitor.stats.skipped_inner_fns += 1;
+                visitor.stats.skip_block(&i.block, false);
+                visitor.function = i.ident.to_string();
+                AsyncBlocks(visitor).visit_block_mut(&mut i.block);
+                return;
+            }
+            visitor.stats.inner_fns += 1;
+            visitor.target = error_type(&output);
+            visitor.instrument_fn(i);
+        });
+    }
+
+    fn visit_expr_closure_mut(&mut self, i: &mut ExprClosure) {
+        self.visit_closure(i, self.args.nested.unwrap_or(false));
+    }
+
+    fn visit_expr_method_call_mut(&mut self, i: &mut ExprMethodCall) {
+        if !self.args.combinators.unwrap_or(true) || !is_combinator(i) {
+            visit_mut::visit_expr_method_call_mut(self, i);
+            return;
+        }
+        // the closures passed to combinators are part of the same flow of errors, so they are
+        // instrumented even without `nested`, unless they evidently return an `Option`, e.g. for
+        // `Option::and_then`
+        self.visit_expr_mut(&mut i.receiver);
+        let nested = self.args.nested.unwrap_or(false);
+        for arg in i.args.iter_mut() {
+            match arg {
+                Expr::Closure(closure) => {
+                    let nested = nested || !returns_option(closure);
+                    self.visit_closure(closure, nested)
+                }
+                arg => self.visit_expr_mut(arg),
+            }
+        }
+    }
+
+    fn visit_expr_call_mut(&mut self, i: &mut ExprCall) {
+        // a closure without parameters that is called right away, e.g. `(move || { ... })()`, is
+        // how other attributes like `#[tracing::instrument(err)]` wrap the body of a function, so
+        // it is instrumented like the function itself
+        if i.args.is_empty() {
+            if let Some(closure) = immediate_closure(&mut i.func) {
+                let returns_result = match closure.output {
+                    ReturnType::Default => true,
+                    ref output => return_kind(output) != ReturnKind::Other,
+                };
+                if closure.inputs.is_empty() && returns_result {
+                    let target = mem::replace(&mut self.target, error_type(&closure.output));
+                    self.visit_expr_mut(&mut closure.body);
+                    self.target = target;
+                    return;
+                }
+            }
+        }
+        visit_mut::visit_expr_call_mut(self, i);
+    }
+
+    fn visit_expr_async_mut(&mut self, i: &mut ExprAsync) {
+        if mem::replace(&mut self.future_body, false) {
+            visit_mut::visit_expr_async_mut(self, i);
+            return;
+        }
+        // the `?` operators of an `async` block convert errors to its own output type
+        let target = self.target.take();
+        visit_mut::visit_expr_async_mut(self, i);
+        self.target = target;
+    }
+
+    fn visit_expr_try_block_mut(&mut self, i: &mut ExprTryBlock) {
+        let target = self.target.take();
+        visit_mut::visit_expr_try_block_mut(self, i);
+        self.target = target;
+    }
+
+    fn visit_expr_try_mut(&mut self, i: &mut ExprTry) {
+        let mut site = self.try_site(self.args, i);
+        site.target = self.target.clone();
+        let mut expr = i.expr.clone();
+        // the receivers are visited first, so the previous operator of the chain is the next one
+        self.chain_start = chained_try(&i.expr).is_some();
+        self.visit_expr_mut(&mut expr);
+        self.chain_start = false;
+        // only errors reported while the expression is evaluated count as re-propagated, and keep
+        // their ID
+        let mut resets = TokenStream::new();
+        if let Some(Repropagated::Compact) | Some(Repropagated::Quiet) = self.args.repropagated {
+            resets.extend(quote!(::debug_try::__private::reset_reported();));
+        }
+        if self.args.error_ids.unwrap_or(false) {
+            resets.extend(quote!(::debug_try::__private::reset_error_id();));
+        }
+        if self.args.record_site.unwrap_or(false)
+            && (self.args.rich.unwrap_or(false) || self.args.crash_report.unwrap_or(false))
+        {
+            resets.extend(quote!(::debug_try::__private::reset_trail();));
+        }
+        if !resets.is_empty() {
+            expr = parse_quote! {{
+                #resets
+                #expr
+            }};
+        }
+        if let Some(retries) = self.args.retry.filter(|&retries| retries > 0) {
+            *expr = self.retry_expr(&site, &expr, retries);
+        }
+
+        self.site_map.add(&site);
+        self.stats.sites += 1;
+        let mut report = report::report_tokens(self.args, &site);
+        let timing = self.args.timing.unwrap_or(false);
+        if timing {
+            report = quote! {
+                let __debug_try_duration = __debug_try_started.elapsed();
+                #report
+            };
+        }
+        let report = report::cold_tokens(&report);
+        // the error that is propagated, after it has been reported
+        let mut err = quote!(__debug_try_err);
+        if let Some(ref map) = self.args.map {
+            err = quote!(#map(#err));
+        }
+        if self.args.locate.unwrap_or(false) {
+            err = report::locate_tokens(&site, &err);
+        }
+        let err = if self.args.panic_on_error.unwrap_or(false) {
+            // the report may panic unconditionally, which makes returning the error unreachable
+            quote! {
+                #[allow(unreachable_code)]
+                let __debug_try_err = #err;
+                __debug_try_err
+            }
+        } else {
+            err
+        };
+        // a `match` rather than `map_err`, so that the type of the expression does not need to be
+        // known where it is reported, and the `?` still converts the error with `From` as before
+        let mut matched = quote! {
+            match #expr {
+                ::core::result::Result::Ok(__debug_try_ok) => ::core::result::Result::Ok(__debug_try_ok),
+                ::core::result::Result::Err(__debug_try_err) => ::core::result::Result::Err({
+                    #report
+                    #err
+                }),
+            }
+        };
+        if timing {
+            matched = quote! {{
+                let __debug_try_started = ::std::time::Instant::now();
+                #matched
+            }};
+        }
+        // the parentheses keep the `match` from being parsed as a statement
+        i.expr = parse_quote!((#matched));
+        if self.args.eyre.unwrap_or(false) {
+            let wrapped = report::eyre_tokens(&site, &i.expr.clone().into_token_stream());
+            i.expr = parse_quote!(#wrapped);
+        }
+    }
+
+    fn visit_macro_mut(&mut self, i: &mut Macro) {
+        // only substitute in known macros
+
+        const KNOWN: &[&str] = &[
+            "println",
+            "eprintln",
+            "format",
+            "format_args",
+            "write",
+            "writeln",
+            "assert",
+            "assert_eq",
+            "assert_ne",
+            "debug_assert",
+            "debug_assert_eq",
+            "debug_assert_ne",
+            "panic",
+            "todo",
+            "unimplemented",
+            "vec",
+        ];
+        if ASYNC_KNOWN
+            .iter()
+            .any(|name| last_segment_is(&i.path, name))
+        {
+            self.visit_async_macro(i);
+            return;
+        }
+        if i.path.is_ident(yeet::MACRO) {
+            self.visit_yeet(i);
+            return;
+        }
+
+        let result = if i.path.is_ident("matches") {
+            // `matches!(expr, pattern)`, where only the expression is substituted
+            let parser = |input: ParseStream| {
+                let expr: Expr = input.parse()?;
+                let comma: Token![,] = input.parse()?;
+                let pattern: TokenStream = input.parse()?;
+                Ok((expr, comma, pattern))
+            };
+            parser
+                .parse2(i.tts.clone())
+                .map(|(mut expr, comma, pattern)| {
+                    self.visit_expr_mut(&mut expr);
+                    quote!(#expr #comma #pattern)
+                })
+        } else if i.path.is_ident("vec") && has_semicolon(&i.tts) {
+            // `vec![elem; n]`
+            let parser = |input: ParseStream| {
+                let elem: Expr = input.parse()?;
+                let semi: Token![;] = input.parse()?;
+                let len: Expr = input.parse()?;
+                Ok((elem, semi, len))
+            };
+            parser
+                .parse2(i.tts.clone())
+                .map(|(mut elem, semi, mut len)| {
+                    self.visit_expr_mut(&mut elem);
+                    self.visit_expr_mut(&mut len);
+                    quote!(#elem #semi #len)
+                })
+        } else if KNOWN.iter().any(|name| i.path.is_ident(name)) {
+            let parser = Punctuated::<Expr, Token![,]>::parse_terminated;
+            parser.parse2(i.tts.clone()).map(|mut tree| {
+                tree.iter_mut().for_each(|item| self.visit_expr_mut(item));
+                tree.into_token_stream()
+            })
+        } else {
+            self.stats.skip_macro(&i.tts);
+            return;
+        };
+
+        match result {
+            Ok(tts) => i.tts = tts,
+            Err(err) => self.push_paser_error(err),
+        }
+    }
+
+    fn visit_stmt_mut(&mut self, i: &mut Stmt) {
+        let outer_label = match self.args.labels.unwrap_or(Labels::None) {
+            Labels::Comments => match label::statement_label(&mut self.sources, i) {
+                Some(label) => self.label.replace(label),
+                None => self.label.clone(),
+            },
+            Labels::None => None,
+        };
+
+        match i {
+            // a macro invocation with braces, e.g. `tokio::select! { ... }`, is a statement of its
+            // own, but runs in the function like any other
+            Stmt::Item(Item::Macro(item)) if item.ident.is_none() => {
+                self.visit_macro_mut(&mut item.mac)
+            }
+            Stmt::Item(item) => {
+                if self.args.nested.unwrap_or(false) {
+                    visit_mut::visit_item_mut(self, item);
+                } else if let Item::Fn(item) = item {
+                    self.stats.skipped_inner_fns += 1;
+                    self.stats.skip_block(&item.block, true);
+                }
+            }
+
+            _ => visit_mut::visit_stmt_mut(self, i),
+        }
+
+        if self.args.ignored.unwrap_or(false) {
+            match i {
+                Stmt::Local(local) if is_wild(&local.pats) => {
+                    if let Some((_, ref mut init)) = local.init {
+                        if is_value(init) {
+                            **init = self.instrument_ignored(init);
+                        }
+                    }
+                }
+                Stmt::Semi(expr, _) if is_value(expr) => *expr = self.instrument_ignored(expr),
+                _ => {}
+            }
+        }
+
+        self.label = outer_label;
+    }
+}
+
+/// Macros of `tokio` and `futures` that are substituted in, e.g. `tokio::select!`.
+const ASYNC_KNOWN: &[&str] = &["select", "select_biased", "join", "try_join"];
+
+/// Returns whether the patterns of a `let` statement are just `_`, as in `let _ = ...`.
+fn is_wild(pats: &Punctuated<Pat, Token![|]>) -> bool {
+    pats.len() == 1 && matches!(pats[0], Pat::Wild(_))
+}
+
+/// Returns whether the expression produces a new value, which can be moved without moving out of a
+/// place, i.e. whether it is a call, a method call or an `.await`.
+fn is_value(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Call(_) | Expr::MethodCall(_) => true,
+        Expr::Field(ref field) => match field.member {
+            Member::Named(ref name) => name == "await",
+            Member::Unnamed(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns whether the expression is a place that can be evaluated again without side effects: a
+/// path, or a field or dereference of one.
+fn is_place(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Path(ref path) => path.qself.is_none(),
+        Expr::Field(ref field) => match field.member {
+            Member::Named(ref name) if name == "await" => false,
+            _ => is_place(&field.base),
+        },
+        Expr::Unary(ExprUnary {
+            op: UnOp::Deref(_),
+            ref expr,
+            ..
+        }) => is_place(expr),
+        Expr::Paren(ref paren) => is_place(&paren.expr),
+        _ => false,
+    }
+}
+
+/// Returns whether the method call is an `unwrap()` or `expect(...)` call, as those of `Result`
+/// and `Option`.
+fn is_unwrap(call: &ExprMethodCall) -> bool {
+    match call.args.len() {
+        0 => call.method == "unwrap",
+        1 => call.method == "expect",
+        _ => false,
+    }
+}
+
+/// Returns whether the method call is one that throws away the error of a `Result`: `ok()`,
+/// `unwrap_or(...)` and `unwrap_or_default()`, and `map_err(...)` and `unwrap_or_else(...)` with a
+/// closure that ignores its parameter, like `|_| ...`, or with `drop`.
+fn is_discard(call: &ExprMethodCall) -> bool {
+    let ignores_error = |arg: &Expr| match *arg {
+        Expr::Closure(ref closure) => {
+            closure.inputs.len() == 1
+                && matches!(
+                    closure.inputs[0],
+                    FnArg::Inferred(Pat::Wild(_))
+                        | FnArg::Captured(ArgCaptured {
+                            pat: Pat::Wild(_),
+                            ..
+                        })
+                )
+        }
+        Expr::Path(ref path) => path.path.is_ident("drop"),
+        _ => false,
+    };
+    match call.args.len() {
+        0 => call.method == "ok" || call.method == "unwrap_or_default",
+        1 if call.method == "unwrap_or" => true,
+        1 if call.method == "map_err" || call.method == "unwrap_or_else" => {
+            ignores_error(&call.args[0])
+        }
+        _ => false,
+    }
+}
+
+/// Returns the `?` operator that the chain of method calls, field accesses and indexing of the
+/// expression starts from, if any, e.g. `a()?` in `a()?.b().c`.
+fn chained_try(expr: &Expr) -> Option<&ExprTry> {
+    match expr {
+        Expr::Try(expr) => Some(expr),
+        Expr::MethodCall(call) => chained_try(&call.receiver),
+        Expr::Field(field) => chained_try(&field.base),
+        Expr::Index(index) => chained_try(&index.expr),
+        _ => None,
+    }
+}
+
+/// Returns whether the last segment of the path is the given name, e.g. `tokio::join` for `join`.
+fn last_segment_is(path: &Path, name: &str) -> bool {
+    path.segments
+        .last()
+        .is_some_and(|segment| segment.value().ident == name)
+}
+
+/// Returns whether the tokens contain a `;` outside of any group, e.g. in `vec![0; n]`.
+fn has_semicolon(tokens: &TokenStream) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        TokenTree::Punct(punct) => punct.as_char() == ';',
+        _ => false,
+    })
+}
+
+/// Returns whether a method call is one of the combinators whose closures are instrumented with
+/// `combinators`, e.g. `and_then`.
+pub(crate) fn is_combinator(i: &ExprMethodCall) -> bool {
+    const COMBINATORS: &[&str] = &[
+        "map_err",
+        "and_then",
+        "try_for_each",
+        "try_fold",
+        "ok_or_else",
+    ];
+    COMBINATORS.iter().any(|name| i.method == name)
+}
+
+/// Returns whether a closure without a return type ends with `Some(...)` or `None`, so that its `?`
+/// operators are assumed to be used on `Option`s.
+pub(crate) fn returns_option(closure: &ExprClosure) -> bool {
+    let mut tail = &*closure.body;
+    while let Expr::Block(ref block) = *tail {
+        match block.block.stmts.last() {
+            Some(Stmt::Expr(ref expr)) => tail = expr,
+            _ => return false,
+        }
+    }
+    match tail {
+        Expr::Call(call) => {
+            matches!(*call.func, Expr::Path(ref path) if path.path.is_ident("Some"))
+        }
+        Expr::Path(path) => path.path.is_ident("None"),
+        _ => false,
+    }
+}
+
+/// Returns the closure that the callee of a call is, if it is one, e.g. in `(|| { ... })()`.
+pub(crate) fn immediate_closure(func: &mut Expr) -> Option<&mut ExprClosure> {
+    match func {
+        Expr::Closure(closure) => Some(closure),
+        Expr::Paren(paren) => immediate_closure(&mut paren.expr),
+        Expr::Group(group) => immediate_closure(&mut group.expr),
+        _ => None,
+    }
+}
+
+/// Returns whether a function may return a `Result`, judging by its return type, as required for
+/// instrumenting it.
+pub(crate) fn returns_result(i: &mut ItemFn) -> bool {
+    return_kind(&fn_output(i)) != ReturnKind::Other
+}
+
+/// Visits only the `async` blocks, closures and inner functions in a closure or an inner function
+/// that does not return a result, with `nested`, e.g. the bodies of the tasks that it spawns with
+/// `tokio::spawn(async move { ... })`, since its own `?` operators are used on something else.
+struct AsyncBlocks<'v, 'a>(&'v mut Visitor<'a>);
+
+impl<'v, 'a> VisitMut for AsyncBlocks<'v, 'a> {
+    fn visit_expr_async_mut(&mut self, i: &mut ExprAsync) {
+        self.0.visit_expr_async_mut(i);
+    }
+
+    fn visit_expr_closure_mut(&mut self, i: &mut ExprClosure) {
+        self.0.visit_expr_closure_mut(i);
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        self.0.visit_item_fn_mut(i);
+    }
+}
+
+/// Returns the return type of a function, or for a function that returns a boxed future the output
+/// type of its future, like `Result<T, E>` in
+/// `Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'async_trait>>`.
+///
+/// That is the return type of an `async fn` after `#[async_trait]` or `#[async_recursion]` has
+/// moved its body to a boxed `async` block, whose `?` operators convert errors to the output type.
+fn fn_output(i: &mut ItemFn) -> ReturnType {
+    let output = match i.decl.output {
+        ReturnType::Type(arrow, ref ty) if boxed_future(&mut i.block).is_some() => {
+            future_output(ty).map(|ty| ReturnType::Type(arrow, Box::new(ty.clone())))
+        }
+        _ => None,
+    };
+    output.unwrap_or_else(|| i.decl.output.clone())
+}
+
+/// Returns the `async` block of a body like `Box::pin(async move { ... })`.
+fn boxed_future(block: &mut Block) -> Option<&mut ExprAsync> {
+    match boxed_future_expr(block)? {
+        Expr::Async(future) => Some(future),
+        _ => None,
+    }
+}
+
+/// Returns the `async` block of [`boxed_future`] as an expression, so that it can be replaced.
+fn boxed_future_expr(block: &mut Block) -> Option<&mut Expr> {
+    let call = match block.stmts.as_mut_slice() {
+        [Stmt::Expr(Expr::Call(call))] => call,
+        _ => return None,
+    };
+    match (&*call.func, call.args.len()) {
+        (Expr::Path(path), 1)
+            if path.path.segments.len() >= 2
+                && path.path.segments.last()?.value().ident == "pin" => {}
+        _ => return None,
+    }
+    match call.args.iter_mut().next()? {
+        future @ Expr::Async(_) => Some(future),
+        _ => None,
+    }
+}
+
+/// Returns the `Output` of a type like `Pin<Box<dyn Future<Output = T> + Send>>`.
+fn future_output(ty: &Type) -> Option<&Type> {
+    let pin = single_type_arg(ty, "Pin")?;
+    let bounds = match single_type_arg(pin, "Box")? {
+        Type::TraitObject(object) => &object.bounds,
+        _ => return None,
+    };
+    bounds.iter().find_map(|bound| {
+        let segment = match bound {
+            TypeParamBound::Trait(bound) => bound.path.segments.last()?.into_value(),
+            TypeParamBound::Lifetime(_) => return None,
+        };
+        match segment.arguments {
+            PathArguments::AngleBracketed(ref args) if segment.ident == "Future" => {
+                args.args.iter().find_map(|arg| match arg {
+                    GenericArgument::Binding(binding) if binding.ident == "Output" => {
+                        Some(&binding.ty)
+                    }
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Returns the type argument of a type like `Box<T>`, where the last segment of the path is
+/// `name`.
+fn single_type_arg<'t>(ty: &'t Type, name: &str) -> Option<&'t Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?.into_value(),
+        Type::Paren(paren) => return single_type_arg(&paren.elem, name),
+        Type::Group(group) => return single_type_arg(&group.elem, name),
+        _ => return None,
+    };
+    if segment.ident != name {
+        return None;
+    }
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+            match args.args.first()?.into_value() {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// What a function or closure is known to return, judging only by the syntax of its return type.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReturnKind {
+    /// A `Result`, or a type alias of one that follows the usual naming, e.g. `io::Result<T>`,
+    /// `anyhow::Result<T>` or `ParseResult<T>`.
+    Result,
+    /// Something that is not a `Result`, e.g. `()`, `Option<T>`, `bool` or `&str`.
+    Other,
+    /// A type that may or may not be a `Result`, e.g. an unfamiliar alias, or a closure without
+    /// an explicit return type.
+    Unknown,
+}
+
+fn return_kind(output: &ReturnType) -> ReturnKind {
+    match output {
+        ReturnType::Default => ReturnKind::Other,
+        ReturnType::Type(_, ty) => type_kind(ty),
+    }
+}
+
+/// Returns the error type of a return type like `Result<T, E>`, which the `?` operator converts
+/// errors to, if it names one that can be referred to in the body, i.e. not `impl Trait` or `_`.
+/// Aliases like `io::Result<T>` do not name it.
+fn error_type(output: &ReturnType) -> Option<Type> {
+    match output {
+        ReturnType::Type(_, ty) => result_error_type(ty),
+        ReturnType::Default => None,
+    }
+}
+
+fn result_error_type(ty: &Type) -> Option<Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?.into_value(),
+        Type::Paren(paren) => return result_error_type(&paren.elem),
+        Type::Group(group) => return result_error_type(&group.elem),
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    let args = match segment.arguments {
+        PathArguments::AngleBracketed(ref args) => &args.args,
+        _ => return None,
+    };
+    let target = match (args.len(), args.last()?.into_value()) {
+        (2, GenericArgument::Type(target)) => target,
+        _ => return None,
+    };
+    if names_type(target.clone().into_token_stream()) {
+        Some(target.clone())
+    } else {
+        None
+    }
+}
+
+/// Returns whether the tokens of a type do not contain an `impl Trait` or an `_`, which can not be
+/// used as the type argument of a function.
+fn names_type(tokens: TokenStream) -> bool {
+    tokens.into_iter().all(|tt| match tt {
+        TokenTree::Ident(ref ident) => ident != "impl" && ident != "_",
+        TokenTree::Punct(ref punct) => punct.as_char() != '_',
+        TokenTree::Group(ref group) => names_type(group.stream()),
+        TokenTree::Literal(_) => true,
+    })
+}
+
+/// Types that are not a `Result`, and are common as return types.
+const NOT_RESULT: &[&str] = &[
+    "Option", "bool", "char", "str", "String", "Vec", "u8", "u16", "u32", "u64", "u128", "usize",
+    "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64",
+];
+
+fn type_kind(ty: &Type) -> ReturnKind {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => match path.path.segments.last() {
+            Some(segment) => {
+                let name = segment.value().ident.to_string();
+                if name.ends_with("Result") {
+                    ReturnKind::Result
+                } else if NOT_RESULT.contains(&&*name) {
+                    ReturnKind::Other
+                } else {
+                    ReturnKind::Unknown
+                }
+            }
+            None => ReturnKind::Unknown,
+        },
+        Type::Tuple(_)
+        | Type::Reference(_)
+        | Type::Slice(_)
+        | Type::Array(_)
+        | Type::Ptr(_)
+        | Type::BareFn(_)
+        | Type::Never(_) => ReturnKind::Other,
+        Type::Paren(paren) => type_kind(&paren.elem),
+        Type::Group(group) => type_kind(&group.elem),
+        _ => ReturnKind::Unknown,
+    }
+}
@@ -0,0 +1,76 @@
+use proc_macro2::{Delimiter, Group, Ident, Punct, Spacing, TokenStream, TokenTree};
+
+/// The name of the macro that stands in for `do yeet` while the function is parsed and
+/// transformed, since `syn` can not parse the unstable syntax.
+pub(crate) const MACRO: &str = "__debug_try_yeet";
+
+/// Replaces every `do yeet expr` with `__debug_try_yeet!(expr)`, where the expression extends to
+/// the next `,` or `;`, or to the end of the group, like that of a `return`.
+///
+/// The name of the macro has the span of `do`, which is where the error is reported.
+pub(crate) fn hide(input: TokenStream) -> TokenStream {
+    let mut output = Vec::new();
+    let mut tokens = input.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let token = match token {
+            TokenTree::Ident(ref ident) if ident == "do" => {
+                match tokens.peek() {
+                    Some(TokenTree::Ident(next)) if next == "yeet" => {}
+                    _ => {
+                        output.push(token);
+                        continue;
+                    }
+                }
+                tokens.next();
+                let mut expr = Vec::new();
+                while let Some(next) = tokens.peek() {
+                    match next {
+                        TokenTree::Punct(punct) if matches!(punct.as_char(), ',' | ';') => break,
+                        _ => expr.push(tokens.next().unwrap()),
+                    }
+                }
+                let expr = hide(expr.into_iter().collect());
+                output.push(TokenTree::Ident(Ident::new(MACRO, ident.span())));
+                output.push(TokenTree::Punct(Punct::new('!', Spacing::Alone)));
+                TokenTree::Group(Group::new(Delimiter::Parenthesis, expr))
+            }
+            TokenTree::Group(group) => {
+                let mut hidden = Group::new(group.delimiter(), hide(group.stream()));
+                hidden.set_span(group.span());
+                TokenTree::Group(hidden)
+            }
+            token => token,
+        };
+        output.push(token);
+    }
+    output.into_iter().collect()
+}
+
+/// Turns the `__debug_try_yeet!(expr)` left by [`hide`] back into `do yeet expr`.
+pub(crate) fn restore(input: TokenStream) -> TokenStream {
+    let mut output = Vec::new();
+    let mut tokens = input.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let token = match token {
+            TokenTree::Ident(ref ident) if ident == MACRO => {
+                tokens.next();
+                let expr = match tokens.next() {
+                    Some(TokenTree::Group(group)) => restore(group.stream()),
+                    _ => TokenStream::new(),
+                };
+                output.push(TokenTree::Ident(Ident::new("do", ident.span())));
+                output.push(TokenTree::Ident(Ident::new("yeet", ident.span())));
+                output.extend(expr);
+                continue;
+            }
+            TokenTree::Group(group) => {
+                let mut restored = Group::new(group.delimiter(), restore(group.stream()));
+                restored.set_span(group.span());
+                TokenTree::Group(restored)
+            }
+            token => token,
+        };
+        output.push(token);
+    }
+    output.into_iter().collect()
+}
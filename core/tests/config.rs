@@ -0,0 +1,107 @@
+use debug_try_core::test_expand;
+use quote::quote;
+
+use std::{env, fs};
+
+// the configuration file is found through `CARGO_MANIFEST_DIR`, which applies to every test of
+// this binary, so all cases are in a single test
+#[test]
+fn reads_configuration_file() {
+    let dir = env::temp_dir().join(format!("debug-try-config-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    env::set_var("CARGO_MANIFEST_DIR", &dir);
+    let path = dir.join("debug_try.toml");
+
+    fs::write(
+        &path,
+        "# defaults for the whole crate\nformat = \"json\"\nnested = true # with a comment\n\ncolor = 'never'\n",
+    )
+    .unwrap();
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let read = || Ok(std::fs::read("a.txt")?);
+            read()
+        }
+    });
+    assert!(output.contains("{{\\\"file\\\":"));
+    assert_eq!(output.matches("Err (__debug_try_err) =>").count(), 1);
+    assert!(output.contains(&format!(
+        "const _ : & [u8] = :: core :: include_bytes ! ({:?}) ;",
+        path.to_str().unwrap()
+    )));
+
+    // the arguments of the attribute take precedence
+    let output = test_expand(quote! {
+        #[debug_try(format = "text", nested = false)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let read = || Ok(std::fs::read("a.txt")?);
+            read()
+        }
+    });
+    assert!(!output.contains("{{\\\"file\\\":"));
+    assert!(!output.contains("__debug_try_err"));
+
+    fs::write(&path, "nested = true\nnested = false\n").unwrap();
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert_eq!(
+        output,
+        format!("error: {}: Duplicate argument\n", path.display())
+    );
+
+    fs::write(&path, "nested\n").unwrap();
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert_eq!(
+        output,
+        format!("error: {}:1: Expected `key = value`\n", path.display())
+    );
+
+    // `configure!` sets defaults that take precedence over the file, but not over the attribute,
+    // and only for the attributes expanded after it
+    fs::write(&path, "format = \"json\"\nnested = true\n").unwrap();
+    let earlier = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    let output = test_expand(quote! {
+        debug_try::configure! { format = "logfmt" }
+    });
+    assert_eq!(output, "const __DEBUG_TRY_CONFIGURED : () = () ;\n");
+    assert!(earlier.contains("{{\\\"file\\\":"));
+    assert!(!earlier.contains("file=<unknown>"));
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let read = || Ok(std::fs::read("a.txt")?);
+            read()
+        }
+    });
+    assert!(output.contains("file=<unknown>"));
+    assert_eq!(output.matches("Err (__debug_try_err) =>").count(), 1);
+    let output = test_expand(quote! {
+        #[debug_try(format = "text")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(!output.contains("file=<unknown>"));
+
+    let output = test_expand(quote! {
+        debug_try::configure! { format = "yaml" }
+    });
+    assert!(output.starts_with("error: "), "{}", output);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
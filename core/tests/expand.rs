@@ -0,0 +1,2696 @@
+use debug_try_core::{instrument_item_fn, test_expand, Config};
+use quote::quote;
+
+#[test]
+fn expands_try_operator() {
+    let output = test_expand(quote! {
+        #[debug_try(color = "never")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let data = std::fs::read("file.txt")?;
+            Ok(data)
+        }
+    });
+
+    assert_eq!(
+        output,
+        r#"fn read () -> Result < Vec < u8 > , std :: io :: Error > {
+    let data = (match std :: fs :: read ("file.txt") {
+        :: core :: result :: Result :: Ok (__debug_try_ok) => :: core :: result :: Result :: Ok (__debug_try_ok) , :: core :: result :: Result :: Err (__debug_try_err) => :: core :: result :: Result :: Err ({
+            :: debug_try :: __private :: cold (| | {
+                :: debug_try :: __private :: require_display (& __debug_try_err) ;
+                let __debug_try_verbosity = :: debug_try :: __private :: verbosity () ;
+                if __debug_try_verbosity != :: core :: option :: Option :: Some (0) {
+                    let __debug_try_verbose = :: debug_try :: __private :: Verbose :: new (__debug_try_verbosity , {
+                        use :: debug_try :: __private :: {
+                            ChainAsRef as _ , ChainAsRefSendSync as _ , ChainError as _ , ChainOther as _ ,
+                        } ;
+                        (& & & & :: debug_try :: __private :: Chained (& __debug_try_err)) . as_error ()
+                    }) ;
+                    :: debug_try :: __private :: report ("<unknown>" , 0u32 , 0u32 , "read" , :: core :: module_path ! () , :: core :: option :: Option :: Some (false) , & __debug_try_err , :: core :: any :: type_name_of_val (& __debug_try_err) , & __debug_try_verbose , cfg ! (test) ,) ;
+                }
+            }) ;
+            __debug_try_err
+        }) ,
+    }) ? ;
+    Ok (data)
+}
+"#
+    );
+}
+
+#[test]
+fn skips_closures_unless_nested() {
+    let input = quote! {
+        fn read() -> Result<(), std::io::Error> {
+            let f = || -> Result<(), std::io::Error> { Err(std::io::ErrorKind::Other.into())? };
+            f()
+        }
+    };
+
+    let output = test_expand(quote! { #[debug_try] #input });
+    assert!(!output.contains("Err (__debug_try_err)"));
+
+    let output = test_expand(quote! { #[debug_try(nested = true)] #input });
+    assert!(output.contains("Err (__debug_try_err)"));
+}
+
+#[test]
+fn instruments_closures_of_combinators() {
+    let input = quote! {
+        fn store(items: Vec<Item>) -> Result<(), std::io::Error> {
+            items.into_iter().try_for_each(|item| {
+                std::fs::write(item.path, item.data)?;
+                Ok(())
+            })
+        }
+    };
+
+    let output = test_expand(quote! { #[debug_try] #input });
+    assert!(output.contains("Err (__debug_try_err)"));
+
+    let output = test_expand(quote! { #[debug_try(combinators = false)] #input });
+    assert!(!output.contains("Err (__debug_try_err)"));
+
+    // the `?` operators of closures that return an `Option` are left alone
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn port(config: Option<&Config>) -> Result<u16, Error> {
+            let port = config.and_then(|config| Some(config.server.as_ref()?.port));
+            Ok(port.unwrap_or(80))
+        }
+    });
+    assert!(!output.contains("Err (__debug_try_err)"));
+}
+
+#[test]
+fn instruments_items_in_place() {
+    let config = Config::parse(quote!(color = "never", on_panic = "skip"))
+        .ok()
+        .unwrap();
+    let item = quote! {
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    };
+    let output = instrument_item_fn(item, &config).ok().unwrap().to_string();
+    assert!(output.starts_with("fn read ( ) -> Result < Vec < u8 > , std :: io :: Error >"));
+    assert!(output.contains(":: core :: result :: Result :: Err ( __debug_try_err )"));
+
+    let output = instrument_item_fn(
+        quote!(
+            struct Read;
+        ),
+        &config,
+    );
+    assert!(output.is_err());
+}
+
+#[test]
+fn instruments_items_and_blocks() {
+    let output = test_expand(quote! {
+        debug_try::debug_try_item! {
+            #![debug_try(color = "never", on_panic = "skip")]
+            fn read() -> Result<Vec<u8>, std::io::Error> {
+                Ok(std::fs::read("a.txt")?)
+            }
+        }
+    });
+    assert!(output.starts_with("fn read () -> Result < Vec < u8 > , std :: io :: Error > {"));
+    assert!(output.contains("Err (__debug_try_err)"));
+
+    let output = test_expand(quote! {
+        debug_try::debug_try_item! {
+            #![debug_try(format = "json", on_panic = "skip")]
+            {
+                let data = std::fs::read("a.txt")?;
+                Ok(data)
+            }
+        }
+    });
+    assert!(output.starts_with("{\n"));
+    assert!(output.contains("\\\"fn\\\":\\\"<block>\\\""));
+
+    let output = test_expand(quote! {
+        debug_try::debug_try_item! {
+            fn read() -> Vec<u8> {
+                Vec::new()
+            }
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `debug_try` can only be used on functions that return `Result`\n"
+    );
+}
+
+#[test]
+fn enters_blocks() {
+    let output = test_expand(quote! {
+        debug_try::debug_try_item! {
+            #![debug_try(max_errors = 3, elapsed = true, sink = "file", on_panic = "skip")]
+            {
+                let data = std::fs::read("a.txt")?;
+                Ok(data)
+            }
+        }
+    });
+    assert!(output.contains("static __DEBUG_TRY_ERRORS"), "{}", output);
+    assert!(output.contains("static __DEBUG_TRY_FILE"), "{}", output);
+    let start = output.find("let __debug_try_start").unwrap();
+    assert!(start < output.find("let data").unwrap());
+}
+
+#[test]
+fn reports_unknown_argument() {
+    let output = test_expand(quote! {
+        #[debug_try(unknown = true)]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+
+    assert_eq!(output, "error: Unknown argument\n");
+}
+
+#[test]
+fn applies_nested_opts_to_closures() {
+    let output = test_expand(quote! {
+        #[debug_try(nested = true, color = "never", debug_repr = true, nested_opts(debug_repr = false))]
+        fn read() -> Result<(), std::io::Error> {
+            let f = || -> Result<(), std::io::Error> { std::fs::read("a.txt").map(drop)? };
+            std::fs::read("b.txt")?;
+            f()
+        }
+    });
+
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 2);
+    assert_eq!(output.matches("Debug: {:?}").count(), 3);
+}
+
+#[test]
+fn emits_tracing_events_with_fields() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "tracing", fields(path))]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read(path)?
+        }
+    });
+
+    assert!(output.contains(":: tracing :: event !"));
+    for field in &[
+        "error = % :: debug_try :: __private :: Sanitized (& __debug_try_err)",
+        "error . chain = ? __debug_try_chain",
+        "function = \"read\"",
+        "\"path\" = ? & (path)",
+    ] {
+        assert!(output.contains(field), "missing {}", field);
+    }
+}
+
+#[test]
+fn recognizes_result_aliases() {
+    let output = test_expand(quote! {
+        #[debug_try(nested = true)]
+        fn read() -> io::Result<usize> {
+            fn first(data: &[u8]) -> Option<u8> {
+                Some(*data.first()?)
+            }
+            Ok(first(&std::fs::read("a.txt")?).map_or(0, usize::from))
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 1);
+
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn first(data: &[u8]) -> Option<u8> {
+            Some(*data.first()?)
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `debug_try` can only be used on functions that return `Result`\n"
+    );
+
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn name(names: &[String]) -> &str {
+            &names[0]
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `debug_try` can only be used on functions that return `Result`\n"
+    );
+}
+
+#[test]
+fn opens_file_sink_lazily() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "file", sink_path = "errors.log")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains("static __DEBUG_TRY_FILE"));
+    assert!(output.contains("__DEBUG_TRY_FILE . get_or_init"));
+    assert!(output.contains("\"errors.log\""));
+    assert!(!output.contains("IsTerminal"));
+    // the message is formatted before the file is locked
+    let line = output.find("let __debug_try_line = format !").unwrap();
+    assert!(line < output.find("__debug_try_file . lock ()").unwrap());
+}
+
+#[test]
+fn calls_handler() {
+    let output = test_expand(quote! {
+        #[debug_try(handler = "crate::on_propagation", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output
+        .contains("crate :: on_propagation (\"<unknown>\" , 0u32 , 0u32 , & __debug_try_err) ;"));
+    assert!(!output.contains("eprintln"));
+}
+
+#[test]
+fn formats_messages_as_json() {
+    let output = test_expand(quote! {
+        #[debug_try(format = "json", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        r#"let __debug_try_line = format ! ("{{\"file\":\"<unknown>\",\"line\":0,\"column\":0,\"fn\":\"read\",\"error\":\"{}\"}}\n" , :: debug_try :: __private :: Json (format_args ! ("{}" , __debug_try_err))) ;"#
+    ));
+}
+
+#[test]
+#[cfg(not(feature = "defmt"))]
+fn reports_disabled_defmt_backend() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "defmt")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+
+    assert_eq!(
+        output,
+        "error: The \"defmt\" backend requires the `defmt` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+fn checks_no_std_arguments() {
+    let output = test_expand(quote! {
+        #[debug_try(no_std = true, print_macro = "log", on_panic = "skip")]
+        fn f() -> Result<(), ()> {
+            Err(())?
+        }
+    });
+    assert!(output.contains("log ! (\"Error propagated (<unknown>): {}\" , :: debug_try :: __private :: Sanitized (& __debug_try_err)) ;"));
+    assert!(!output.contains("std"));
+
+    let output = test_expand(quote! {
+        #[debug_try(no_std = true)]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `no_std` requires `print_macro`, `handler` or a backend other than \"stderr\"\n"
+    );
+}
+
+#[test]
+fn counts_propagations_with_metrics() {
+    let output = test_expand(quote! {
+        #[debug_try(metrics = true, backend = "none", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        r#":: metrics :: counter ! ("debug_try_propagations_total" , "file" => "<unknown>" , "line" => "0" , "function" => "read" ,) . increment (1) ;"#
+    ));
+    assert!(!output.contains("eprintln"));
+}
+
+#[test]
+fn strips_instrumentation_from_release_builds() {
+    let output = test_expand(quote! {
+        #[debug_try(strip_release = true, cfg = "unix")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.starts_with("# [cfg (all (debug_assertions , unix))]"));
+    assert!(output.contains("# [cfg (not (all (debug_assertions , unix)))]"));
+}
+
+#[test]
+fn accepts_cfg_predicate_list() {
+    let output = test_expand(quote! {
+        #[debug_try(cfg(feature = "diagnostics"))]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.starts_with("# [cfg (feature = \"diagnostics\")]"));
+
+    let output = test_expand(quote! {
+        #[debug_try(cfg(unix, windows))]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(output, "error: Expected a single configuration predicate\n");
+}
+
+#[test]
+fn filters_by_error_type() {
+    let output = test_expand(quote! {
+        #[debug_try(only = [io::Error, MyError])]
+        fn read() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+
+    assert!(
+        output.contains("false || err . is :: < io :: Error > () || err . is :: < MyError > ()")
+    );
+    assert!(output.contains("(& & & & Only (& __debug_try_err)) . matches ()"));
+}
+
+#[test]
+fn limits_rate_per_site() {
+    let output = test_expand(quote! {
+        #[debug_try(rate_limit = "1/500ms")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("if __DEBUG_TRY_RATE_LIMIT . allow (1u32 , 500u64)"));
+
+    // the static of the site does not shadow an item of the function with the same name
+    let output = test_expand(quote! {
+        #[debug_try(rate_limit = "1/500ms", fields(RATE_LIMIT))]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            const RATE_LIMIT: u32 = 7;
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("const RATE_LIMIT : u32 = 7 ;"));
+    assert!(!output.contains("static RATE_LIMIT"));
+    assert!(output.contains("& (RATE_LIMIT)"));
+
+    let output = test_expand(quote! {
+        #[debug_try(rate_limit = "often")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: Expected a rate limit like \"10/s\" or \"1/500ms\"\n"
+    );
+}
+
+#[test]
+fn panics_on_error() {
+    let output = test_expand(quote! {
+        #[debug_try(panic_on_error = true, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains("panic ! (\"Error propagated (<unknown>): {}{}\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_verbose) ;"));
+    assert!(output.contains("# [allow (unreachable_code)]"));
+    assert!(!output.contains("eprintln"));
+    assert!(!output.contains("dispatch"));
+}
+
+#[test]
+fn breaks_after_message() {
+    let output = test_expand(quote! {
+        #[debug_try(break_on_error = true, backend = "log", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    let message = output.find(":: log :: error !").unwrap();
+    let breakpoint = output
+        .find(":: debug_try :: __private :: breakpoint () ;")
+        .unwrap();
+    assert!(message < breakpoint);
+}
+
+#[test]
+fn reports_once_per_site() {
+    let output = test_expand(quote! {
+        #[debug_try(once = true, no_std = true, backend = "log")]
+        fn read() -> Result<(), ()> {
+            Err(())?
+        }
+    });
+
+    assert!(
+        output.contains("static __DEBUG_TRY_REPORTED : :: core :: sync :: atomic :: AtomicBool")
+    );
+    assert!(output.contains(
+        "if ! __DEBUG_TRY_REPORTED . swap (true , :: core :: sync :: atomic :: Ordering :: Relaxed)"
+    ));
+
+    // the static of the site does not shadow an item of the function with the same name
+    let output = test_expand(quote! {
+        #[debug_try(once = true, fields(REPORTED))]
+        fn read() -> Result<(), std::io::Error> {
+            const REPORTED: u32 = 7;
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+    assert!(output.contains("const REPORTED : u32 = 7 ;"));
+    assert!(!output.contains("static REPORTED"));
+    assert!(output.contains("& (REPORTED)"));
+}
+
+#[test]
+fn limits_errors_per_function() {
+    let output = test_expand(quote! {
+        #[debug_try(max_errors = 3, handler = "crate::on_propagation")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains("static __DEBUG_TRY_ERRORS : :: core :: sync :: atomic :: AtomicUsize"));
+    assert!(output
+        .contains("panic ! (\"More than {} errors propagated in `{}`\" , 3usize , \"read\") ;"));
+}
+
+#[test]
+fn compacts_repropagated_errors() {
+    let output = test_expand(quote! {
+        #[debug_try(repropagated = "compact", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        "(match {\n        :: debug_try :: __private :: reset_reported () ;\n        std :: fs :: read (\"a.txt\")\n    }\n    {"
+    ));
+    assert!(output.contains(
+        "if :: debug_try :: __private :: mark_reported () {\n                        let __debug_try_line = format ! (\"Error re-propagated (<unknown>)\\n\") ;"
+    ));
+}
+
+#[test]
+fn wraps_errors_with_location() {
+    let output = test_expand(quote! {
+        #[debug_try(locate = true, backend = "none")]
+        fn read() -> Result<Vec<u8>, Located<std::io::Error>> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(". locate (\"<unknown>\" , 0u32 , 0u32)"));
+}
+
+#[test]
+fn wraps_errors_with_eyre() {
+    let output = test_expand(quote! {
+        #[debug_try(eyre = true, backend = "none", on_panic = "skip")]
+        fn read() -> eyre::Result<Vec<u8>> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains("(match std :: fs :: read (\"a.txt\") {"));
+    assert!(output.contains("}) , | | \"Propagated at <unknown> in `read`\") ?"));
+    assert!(output.starts_with(
+        "fn read () -> eyre :: Result < Vec < u8 > > {\n    :: eyre :: WrapErr :: wrap_err_with ("
+    ));
+}
+
+#[test]
+fn maps_errors_after_reporting() {
+    let output = test_expand(quote! {
+        #[debug_try(map = "crate::enrich", backend = "none", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, MyError> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains("crate :: enrich (__debug_try_err)\n        }) ,\n    }) ?"));
+}
+
+#[test]
+fn accepts_flags_and_paths() {
+    let output = test_expand(quote! {
+        #[debug_try(nested, handler = crate::on_propagation, color = never, on_panic = skip)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let f = || std::fs::read("a.txt");
+            f()?
+        }
+    });
+    assert!(output
+        .contains("crate :: on_propagation (\"<unknown>\" , 0u32 , 0u32 , & __debug_try_err) ;"));
+
+    let output = test_expand(quote! {
+        #[debug_try(nestd = true)]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(output, "error: Unknown argument, did you mean `nested`?\n");
+
+    let output = test_expand(quote! {
+        #[debug_try(sink_path = errors)]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(output, "error: Expected string literal\n");
+}
+
+#[test]
+fn keeps_user_bindings_visible_to_fields() {
+    let output = test_expand(quote! {
+        #[debug_try(fields(err, hits), site_ids = true, on_panic = "skip")]
+        fn read(err: u32, hits: u32) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains("Err (__debug_try_err) => "));
+    assert!(output.contains("__debug_try_hits , :: debug_try :: __private :: Sanitized (& __debug_try_err) , & (err) , & (hits)"));
+}
+
+#[test]
+fn looks_up_locations_at_runtime() {
+    let output = test_expand(quote! {
+        #[debug_try(location = "runtime", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"Error propagated ({}:{}:{}): {}{}\\n\" , :: core :: panic :: Location :: caller () . file () , :: core :: panic :: Location :: caller () . line () , :: core :: panic :: Location :: caller () . column () , :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_verbose) ;"
+    ));
+}
+
+#[test]
+fn tracks_errors_across_sites() {
+    let output = test_expand(quote! {
+        #[debug_try(error_ids = true, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        "(match {\n        :: debug_try :: __private :: reset_error_id () ;\n        std :: fs :: read (\"a.txt\")\n    }"
+    ));
+    assert!(
+        output.contains("let __debug_try_error_id = :: debug_try :: __private :: error_id () ;")
+    );
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"Error propagated (<unknown>) [error={}]: {}{}\\n\" , __debug_try_error_id , :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_verbose) ;"
+    ));
+}
+
+#[test]
+fn wraps_tests() {
+    let output = test_expand(quote! {
+        #[debug_try::test(backend = "none", on_panic = "skip")]
+        #[ignore]
+        fn reads() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+
+    assert!(output.starts_with(
+        "# [test] # [ignore] fn reads () -> Result < () , std :: io :: Error > {\n    fn __debug_try_test () -> Result < () , std :: io :: Error > {"
+    ));
+    assert!(output.contains("let __debug_try_result = __debug_try_test () ;"));
+    assert!(output.contains("eprintln ! (\"Test `reads` failed: {}\" , & :: debug_try :: __private :: Sanitized (__debug_try_err)) ;"));
+}
+
+#[test]
+fn wraps_main() {
+    let output = test_expand(quote! {
+        #[debug_try::main(backend = "none", on_panic = "skip")]
+        fn main() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+
+    assert!(output.starts_with(
+        "fn main () -> :: std :: process :: ExitCode {\n    fn __debug_try_main () -> Result < () , std :: io :: Error > {"
+    ));
+    assert!(
+        output.contains(":: debug_try :: __private :: record_site (\"<unknown>\" , 0u32 , 0u32) ;")
+    );
+    assert!(output.contains(
+        ":: debug_try :: __private :: report_main (\"Error\" , & :: debug_try :: __private :: Sanitized (& __debug_try_err) , & __debug_try_chain) ;"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try::main]
+        async fn main() -> Result<(), std::io::Error> {
+            Ok(())
+        }
+    });
+    assert!(output.starts_with("error: `debug_try::main` can not be used on `async` functions"));
+}
+
+#[test]
+fn instruments_bodies_wrapped_by_other_attributes() {
+    // the shapes that `#[tracing::instrument(err)]` and `#[tokio::main]` give the body
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            match (move || { std::fs::read("a.txt")? })() {
+                Ok(data) => Ok(data),
+                Err(err) => Err(err),
+            }
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 1);
+
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn main() -> Result<(), std::io::Error> {
+            let body = async { std::fs::read("a.txt")?; Ok(()) };
+            runtime().block_on(body)
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 1);
+
+    // closures that take arguments, or return something else, are still left alone
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<(), std::io::Error> {
+            let _ = (|path: &str| std::fs::read(path)?)("a.txt");
+            let _ = (|| -> Option<u8> { Some(first()?) })();
+            Ok(())
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 0);
+}
+
+#[test]
+fn prefixes_messages() {
+    let output = test_expand(quote! {
+        #[debug_try(prefix = "payments", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"[payments] Error propagated (<unknown>): {}{}\\n\""
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(prefix = "payments", format = "json", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("\"{{\\\"prefix\\\":\\\"payments\\\",\\\"file\\\":"));
+}
+
+#[test]
+fn times_failing_expressions() {
+    let output = test_expand(quote! {
+        #[debug_try(timing = true, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        "({\n        let __debug_try_started = :: std :: time :: Instant :: now () ;\n        match std :: fs :: read (\"a.txt\")"
+    ));
+    assert!(output.contains("let __debug_try_duration = __debug_try_started . elapsed () ;"));
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"Error propagated (<unknown>) took {:?}: {}{}\\n\" , __debug_try_duration , :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_verbose) ;"
+    ));
+}
+
+#[test]
+fn captures_function_arguments() {
+    let output = test_expand(quote! {
+        #[debug_try(args = true, skip(conn), color = "never", on_panic = "skip")]
+        fn load(&self, user_id: u64, conn: Conn) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"Error propagated (<unknown>): {} (user_id = {:?}){}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , & (user_id) , __debug_try_verbose) ;"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(args = true, skip(connection))]
+        fn load(user_id: u64, conn: Conn) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `connection` is not a parameter of the function\n"
+    );
+}
+
+#[test]
+fn leaves_disabled_functions_untouched() {
+    let untouched = "fn read (path : & str) -> Result < Vec < u8 > , std :: io :: Error > {\n    let data = std :: fs :: read (path) ? ;\n    Ok (data)\n}\n";
+
+    let output = test_expand(quote! {
+        #[debug_try(enabled = false, fields(path))]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            let data = std::fs::read(path)?;
+            Ok(data)
+        }
+    });
+    assert_eq!(output, untouched);
+
+    let output = test_expand(quote! {
+        #[debug_try::test(enabled = false)]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            let data = std::fs::read(path)?;
+            Ok(data)
+        }
+    });
+    assert_eq!(output, format!("# [test] {}", untouched));
+
+    // the function is compiled unchanged where the predicate does not hold
+    let output = test_expand(quote! {
+        #[debug_try(cfg(feature = "diagnostics"))]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            let data = std::fs::read(path)?;
+            Ok(data)
+        }
+    });
+    assert!(output.ends_with(&format!(
+        "# [cfg (not (feature = \"diagnostics\"))] {}",
+        untouched
+    )));
+}
+
+#[test]
+fn instruments_known_macros() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "none", on_panic = "skip")]
+        fn check() -> Result<(), std::io::Error> {
+            assert!(std::fs::read("a.txt")?.is_empty());
+            assert_eq!(std::fs::read("a.txt")?, b"", "{}", std::fs::read_to_string("b.txt")?);
+            debug_assert_ne!(std::fs::read("a.txt")?.len(), 1);
+            let sizes = vec![std::fs::read("a.txt")?.len(); std::fs::read("b.txt")?.len()];
+            if matches!(std::fs::read("a.txt")?.first(), Some(&b'#') | None if sizes.is_empty()) {
+                panic!("{}", std::fs::read_to_string("a.txt")?);
+            }
+            Ok(())
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 8);
+    assert!(output.contains(", Some (& b'#') | None if sizes . is_empty ())"));
+}
+
+#[test]
+fn instruments_async_macros() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "none", on_panic = "skip")]
+        async fn serve() -> Result<(), std::io::Error> {
+            let (a, b) = tokio::join!(read("a.txt"), read("b.txt"));
+            tokio::try_join!(read(a?), read(b?))?;
+            tokio::select! {
+                biased;
+                Some(line) = lines.next(), if open => handle(line?)?,
+                _ = shutdown.recv() => {
+                    flush().await?;
+                }
+                else => {}
+            }
+            Ok(())
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 6);
+    assert!(output.contains(
+        "tokio :: select ! {\n        biased ;\n        Some (line) = lines . next () , if open =>"
+    ));
+}
+
+#[test]
+fn emits_events_at_level() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "log", level = "warn", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(":: log :: warn ! (\"Error propagated (<unknown>): {}{}\""));
+
+    let output = test_expand(quote! {
+        #[debug_try(backend = "tracing", level = "info", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(":: tracing :: event ! (:: tracing :: Level :: INFO ,"));
+
+    let output = test_expand(quote! {
+        #[debug_try(backend = "log", target = "my_app::io", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(":: log :: error ! (target : \"my_app::io\" , \"Error propagated"));
+}
+
+#[test]
+fn includes_package() {
+    let output = test_expand(quote! {
+        #[debug_try(package = true, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(&format!(
+        "let __debug_try_line = format ! (\"Error propagated (<unknown>) [debug-try-core v{}]: {{}}{{}}\\n\"",
+        env!("CARGO_PKG_VERSION")
+    )));
+}
+
+#[test]
+fn applies_verbosity_presets() {
+    let output = test_expand(quote! {
+        #[debug_try(verbosity = "compact", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {}{}\\n\" , :: debug_try :: __private :: Sanitized (& :: debug_try :: __private :: FirstLine (& __debug_try_err)) , __debug_try_verbose)"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(verbosity = "full", color = "never", on_panic = "skip")]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read(path)?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {} (path = {:?}){}\\n    Stack backtrace:\\n{}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , & (path) , :: debug_try :: __private :: Causes ("
+    ));
+    assert!(output.contains(":: std :: backtrace :: Backtrace :: force_capture ()"));
+
+    let output = test_expand(quote! {
+        #[debug_try(verbosity = "full", args = false, color = "never", on_panic = "skip")]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read(path)?
+        }
+    });
+    assert!(!output.contains("(path = {:?})"));
+}
+
+#[test]
+fn lays_out_messages_on_multiple_lines() {
+    let output = test_expand(quote! {
+        #[debug_try(layout = "multiline", fields(user_id), color = "never", on_panic = "skip")]
+        fn read(user_id: u32) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>):\\n    {}{}\\n    Fields:\\n        user_id = {:?}{}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , :: debug_try :: __private :: CausedBy ("
+    ));
+}
+
+#[test]
+fn names_steps_of_chains() {
+    let output = test_expand(quote! {
+        #[debug_try(color = "never", on_panic = "skip")]
+        fn read() -> Result<u32, Error> {
+            Ok(open()?.parse(1)?.value?)
+        }
+    });
+    assert!(output.contains("\"Error propagated (<unknown>) in `open()?`: {}{}\\n\""));
+    assert!(output.contains("\"Error propagated (<unknown>) in `.parse(1)?`: {}{}\\n\""));
+    assert!(output.contains("\"Error propagated (<unknown>) in `.value?`: {}{}\\n\""));
+
+    let output = test_expand(quote! {
+        #[debug_try(color = "never", on_panic = "skip")]
+        fn read() -> Result<u32, Error> {
+            Ok(parse(open()?)?)
+        }
+    });
+    assert!(!output.contains(" in `"));
+}
+
+#[test]
+fn instruments_unwraps() {
+    let output = test_expand(quote! {
+        #[debug_try(unwraps = true, color = "never", on_panic = "skip")]
+        fn read() -> Result<u32, Error> {
+            let data = std::fs::read("a.txt").unwrap();
+            let value = parse(&data).expect("invalid data");
+            Ok(value.get(0).unwrap_or_default())
+        }
+    });
+    assert_eq!(output.matches("Unwrapped (& __debug_try_value)").count(), 2);
+    assert!(output.contains("match std :: fs :: read (\"a.txt\") {"));
+    assert!(output.contains("__debug_try_value . expect (\"invalid data\")"));
+    assert!(output.contains("\"Unwrap failed (<unknown>): {}{}\\n\""));
+
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<u32, Error> {
+            Ok(parse("1").unwrap())
+        }
+    });
+    assert!(!output.contains("Unwrapped"));
+}
+
+#[test]
+fn instruments_discarded_errors() {
+    let output = test_expand(quote! {
+        #[debug_try(discards = true, color = "never", on_panic = "skip")]
+        fn read() -> Result<u32, Error> {
+            let data = std::fs::read("a.txt").ok();
+            let value = parse(&data).map_err(|_| Error::Parse);
+            let count = count(&data).unwrap_or_else(|err| err.count());
+            Ok(value.unwrap_or_default() + count)
+        }
+    });
+    assert_eq!(output.matches("Unwrapped (& __debug_try_value)").count(), 2);
+    // a place is borrowed rather than moved, in case its method takes `&self`
+    assert!(output.contains("Unwrapped (& value)"));
+    assert!(output.contains("value . unwrap_or_default ()"));
+    assert!(output.contains("UnwrapOther as _ , UnwrapResult as _"));
+    assert!(!output.contains("UnwrapOption"));
+    assert!(output.contains("\"Error discarded (<unknown>): {}{}\\n\""));
+    assert!(!output.contains("match count (& data)"));
+}
+
+#[test]
+fn reports_ignored_results() {
+    let output = test_expand(quote! {
+        #[debug_try(ignored = true, color = "never", on_panic = "skip")]
+        fn save(path: &Path) -> Result<(), Error> {
+            let _ = std::fs::remove_file(path);
+            flush(path);
+            let _ = path;
+            cleanup(path)?;
+            Ok(())
+        }
+    });
+    assert!(output.contains("match std :: fs :: remove_file (path)"));
+    assert!(output.contains("match flush (path)"));
+    assert!(output.contains("let _ = path ;"));
+    assert_eq!(output.matches("Unwrapped (& __debug_try_value)").count(), 2);
+    assert!(output.contains("\"Error ignored (<unknown>): {}{}\\n\""));
+}
+
+#[test]
+fn exports_site_map() {
+    let path = std::env::temp_dir().join(format!("debug-try-site-map-{}.json", std::process::id()));
+    std::env::set_var("DEBUG_TRY_SITE_MAP", &path);
+    test_expand(quote! {
+        #[debug_try(unwraps = true)]
+        fn export_site_map() -> Result<u32, Error> {
+            let value = parse()?.get()?;
+            Ok(value + count().unwrap())
+        }
+    });
+    std::env::remove_var("DEBUG_TRY_SITE_MAP");
+    let site_map = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // other tests may add entries while the variable is set
+    let entries: Vec<&str> = site_map
+        .lines()
+        .filter(|line| line.contains("\"function\":\"export_site_map\""))
+        .collect();
+    assert_eq!(entries.len(), 3);
+    assert!(entries[0].starts_with(
+        "{\"file\":\"<unknown>\",\"line\":0,\"column\":0,\"function\":\"export_site_map\",\"site\":\""
+    ));
+    assert!(entries[0].ends_with("\"kind\":\"try\",\"step\":\"parse()?\"}"));
+    assert!(entries[1].ends_with("\"kind\":\"try\",\"step\":\".get()?\"}"));
+    assert!(entries[2].ends_with("\"kind\":\"unwrap\"}"));
+}
+
+#[test]
+fn formats_messages_as_logfmt() {
+    let output = test_expand(quote! {
+        #[debug_try(format = "logfmt", fields(ids[0]), color = "never", on_panic = "skip")]
+        fn read(ids: &[u32]) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        r#"let __debug_try_line = format ! ("level=error msg=propagated file=<unknown> line=0 column=0 fn=read err=\"{}\" ids_0_=\"{}\"\n" , :: debug_try :: __private :: Json (format_args ! ("{}" , __debug_try_err)) , :: debug_try :: __private :: Json (format_args ! ("{:?}" , & (ids [0])))) ;"#
+    ));
+}
+
+#[test]
+fn indents_messages_by_depth() {
+    let output = test_expand(quote! {
+        #[debug_try(indent = true, repropagated = "compact", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+
+    assert!(output.contains(
+        "fn read () -> Result < Vec < u8 > , std :: io :: Error > {\n    let __debug_try_depth = :: debug_try :: __private :: enter () ;"
+    ));
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"{}Error propagated (<unknown>): {}{}\\n\" , :: debug_try :: __private :: indent () , :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_verbose) ;"
+    ));
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"{}Error re-propagated (<unknown>)\\n\" , :: debug_try :: __private :: indent ()) ;"
+    ));
+}
+
+#[test]
+fn reads_verbosity_at_runtime() {
+    let output = test_expand(quote! {
+        #[debug_try(fields(user_id), color = "never", on_panic = "skip")]
+        fn read(user_id: u32) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "let __debug_try_verbosity = :: debug_try :: __private :: verbosity () ;\n                if __debug_try_verbosity != :: core :: option :: Option :: Some (0) {"
+    ));
+    assert!(output.contains(
+        "(& & & & :: debug_try :: __private :: Chained (& __debug_try_err)) . as_error ()"
+    ));
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {} (user_id = {:?}){}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , & (user_id) , __debug_try_verbose)"
+    ));
+
+    // the full preset already includes the sources and the backtrace
+    let output = test_expand(quote! {
+        #[debug_try(verbosity = "full", format = "text", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("__debug_try_verbosity != :: core :: option :: Option :: Some (0)"));
+    assert!(!output.contains("__debug_try_verbose "));
+}
+
+#[test]
+fn rejects_const_fn() {
+    let output = test_expand(quote! {
+        #[debug_try]
+        const fn parse(value: u32) -> Result<u32, u32> {
+            if value > 9 {
+                return Err(value);
+            }
+            Ok(value)
+        }
+    });
+    assert!(output.starts_with("error: `debug_try` can not be used on `const` functions"));
+
+    // inner `const` functions are left as they are
+    let output = test_expand(quote! {
+        #[debug_try(nested)]
+        fn parse(value: u32) -> Result<u32, u32> {
+            const fn check(value: u32) -> Result<u32, u32> {
+                match value {
+                    0..=9 => Ok(value),
+                    _ => Err(value),
+                }
+            }
+            let value = check(value)?;
+            Ok(value)
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 1);
+}
+
+#[test]
+fn recurses_into_nested_macros() {
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn render(w: &mut String) -> Result<(), Error> {
+            println!("{}", format!("{}", first()?));
+            println!("{:?}", vec![{ let s = format!("{}", second()?); s }]);
+            assert!(matches!(vec![third()?; 2].first(), Some(_)));
+            Ok(())
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err) =>").count(), 3);
+    assert!(output.contains("format ! (\"{}\" , (match first () {"));
+    assert!(output.contains("format ! (\"{}\" , (match second () {"));
+    assert!(output.contains("vec ! [(match third () {"));
+}
+
+#[test]
+fn checks_hyperlink_urls() {
+    let output = test_expand(quote! {
+        #[debug_try(hyperlinks = "vscode://file{path}:{line}")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("__debug_try_err"));
+
+    let output = test_expand(quote! {
+        #[debug_try(hyperlinks = "vscode://file:{line}")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert_eq!(
+        output,
+        "error: Expected a URL with a `{path}` placeholder\n"
+    );
+}
+
+#[test]
+fn formats_messages_like_rustc() {
+    let output = test_expand(quote! {
+        #[debug_try(format = "rustc", fields(id), color = "never", on_panic = "skip")]
+        fn read(id: u32) -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"error: error propagated in `read`: {} (id = {:?})\\n  --> <unknown>{}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , & (id) , __debug_try_verbose)"
+    ));
+
+    // the location is looked up at run time after the error and the fields
+    let output = test_expand(quote! {
+        #[debug_try(format = "rustc", location = "runtime", level = "warn", color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"warning: error propagated in `read`: {}\\n  --> {}:{}:{}{}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , :: core :: panic :: Location :: caller () . file () , :: core :: panic :: Location :: caller () . line () , :: core :: panic :: Location :: caller () . column () , __debug_try_verbose)"
+    ));
+}
+
+#[test]
+fn prints_io_error_details() {
+    let output = test_expand(quote! {
+        #[debug_try(io_details, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {}{}{}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , :: debug_try :: __private :: IoText ({"
+    ));
+    assert!(output.contains(
+        "(&& && :: debug_try :: __private :: Chained (& __debug_try_err)) . as_static_error ()"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(io_details, format = "json", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("\\\"error\\\":\\\"{}\\\"{}}}"));
+    assert!(output.contains(":: debug_try :: __private :: IoJson ({"));
+
+    let output = test_expand(quote! {
+        #[debug_try(io_details, no_std, handler = on_error)]
+        fn read() -> Result<Vec<u8>, Error> {
+            read_all()?
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `io_details` can not be used with `no_std`\n"
+    );
+}
+
+#[test]
+fn uses_registered_formatters() {
+    let output = test_expand(quote! {
+        #[debug_try(formatters, verbosity = "compact", color = "never", on_panic = "skip")]
+        fn parse(text: &str) -> Result<u32, std::num::ParseIntError> {
+            text.parse()?
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {}{}\\n\" , :: debug_try :: __private :: Sanitized (& :: debug_try :: __private :: FirstLine (& :: debug_try :: __private :: Formatted (& __debug_try_err , {"
+    ));
+    assert!(output.contains(". as_static_error ()"));
+}
+
+#[test]
+fn reports_error_before_conversion() {
+    let output = test_expand(quote! {
+        #[debug_try(on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, MyError> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    // the error is reported in the match on the expression itself, and only converted by the `?`
+    // applied to the result of the match
+    assert!(output.contains("    Ok ((match std :: fs :: read (\"a.txt\") {\n"));
+    assert!(output.contains("            __debug_try_err\n        }) ,\n    }) ?)\n"));
+}
+
+#[test]
+fn prints_type_names() {
+    let output = test_expand(quote! {
+        #[debug_try(type_names, nested, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, MyError> {
+            let read = || -> std::io::Result<Vec<u8>> { Ok(std::fs::read("a.txt")?) };
+            Ok(read()?)
+        }
+    });
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {} ({}){}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , :: core :: any :: type_name_of_val (& __debug_try_err) ,"
+    ));
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>): {} ({} -> {}){}\\n\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , :: core :: any :: type_name_of_val (& __debug_try_err) , :: core :: any :: type_name :: < MyError > () ,"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(type_names, format = "json", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, impl Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("\\\"error\\\":\\\"{}\\\",\\\"error_type\\\":\\\"{}\\\"}}"));
+    assert!(!output.contains("type_name ::"));
+}
+
+#[test]
+fn captures_span_traces() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "tracing", span_trace, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output
+        .contains("let __debug_try_span_trace = :: tracing_error :: SpanTrace :: capture () ;"));
+    assert!(output.contains("span_trace = % __debug_try_span_trace ,"));
+
+    let output = test_expand(quote! {
+        #[debug_try(span_trace, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(!output.contains("tracing_error"));
+}
+
+#[test]
+#[cfg(not(any(feature = "android", feature = "apple")))]
+fn reports_disabled_mobile_sinks() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "logcat")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: The \"logcat\" sink requires the `android` feature of `debug-try`\n"
+    );
+
+    let output = test_expand(quote! {
+        #[debug_try(sink = "os_log")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: The \"os_log\" sink requires the `apple` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "android")]
+fn writes_to_logcat() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "logcat", target = "my_app", level = "warn", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("# [cfg (target_os = \"android\")] {"));
+    assert!(output.contains(
+        ":: android_log_sys :: __android_log_write (:: android_log_sys :: LogPriority :: WARN as :: std :: os :: raw :: c_int , b\"my_app\\0\" . as_ptr ()"
+    ));
+}
+
+#[test]
+fn samples_propagations() {
+    let output = test_expand(quote! {
+        #[debug_try(sample = 10, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("if __debug_try_sampled % 10usize == 0 {"));
+    assert!(
+        output.contains("static __DEBUG_TRY_SAMPLED : :: core :: sync :: atomic :: AtomicUsize")
+    );
+    assert!(output.contains(
+        "format ! (\"Error propagated (<unknown>) [sample=1/10 suppressed={}]: {}{}\\n\" , __debug_try_suppressed , :: debug_try :: __private :: Sanitized (& __debug_try_err) ,"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(sample = 0)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert_eq!(output, "error: Expected a positive integer\n");
+
+    // the static of the site does not shadow an item of the function with the same name
+    let output = test_expand(quote! {
+        #[debug_try(sample = 10, fields(SAMPLED))]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            const SAMPLED: u32 = 7;
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("const SAMPLED : u32 = 7 ;"));
+    assert!(!output.contains("static SAMPLED"));
+    assert!(output.contains("& (SAMPLED)"));
+}
+
+#[test]
+fn summarizes_errors() {
+    let output = test_expand(quote! {
+        #[debug_try(summary = "10s", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("match __DEBUG_TRY_SUMMARY . record (0u64 , 10000u64) {"));
+    assert!(output.contains(
+        "let __debug_try_message = format ! (\"Error propagated {} times in {:.1?} (<unknown>), last: {}\" , __debug_try_count , __debug_try_elapsed , :: debug_try :: __private :: Sanitized (& __debug_try_err)) ;"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(summary = 100, handler = on_error)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(!output.contains("SUMMARY"));
+
+    let output = test_expand(quote! {
+        #[debug_try(summary = "10")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert_eq!(
+        output,
+        "error: Expected a period like \"10s\" or \"500ms\"\n"
+    );
+}
+
+#[test]
+fn retries_failed_attempts() {
+    let output = test_expand(quote! {
+        #[debug_try(retry = 3, retry_delay_ms = 100, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("let mut __debug_try_attempts : u32 = 0 ;"));
+    assert!(output.contains(
+        ":: core :: result :: Result :: Err (__debug_try_err) if __debug_try_attempts < 3u32 => {"
+    ));
+    assert!(output.contains("\"Attempt failed (<unknown>): {}{}\\n\""));
+    assert!(output.contains(
+        ":: std :: thread :: sleep (:: std :: time :: Duration :: from_millis (100u64)) ;"
+    ));
+    assert!(output.contains("__debug_try_result => break __debug_try_result ,"));
+}
+
+#[test]
+fn substitutes_default_values() {
+    let output = test_expand(quote! {
+        #[debug_try(or_default, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, Error> {
+            let text = std::fs::read_to_string("a.txt")?;
+            Ok(text.into_bytes())
+        }
+    });
+    assert!(
+        output.contains(":: core :: result :: Result :: Ok (__debug_try_ok) => __debug_try_ok ,")
+    );
+    assert!(output.contains("\"Error replaced with default (<unknown>): {}{}\\n\""));
+    assert!(
+        output.contains("let __debug_try_default = :: core :: default :: Default :: default () ;")
+    );
+    assert!(!output.contains("? ;"));
+}
+
+#[test]
+fn locks_output() {
+    let output = test_expand(quote! {
+        #[debug_try(lock_output, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(
+        output.contains("let __debug_try_output = :: debug_try :: __private :: lock_output () ;")
+    );
+
+    let output = test_expand(quote! {
+        #[debug_try(lock_output, no_std, handler = on_error)]
+        fn read() -> Result<Vec<u8>, Error> {
+            read_all()?
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `lock_output` can not be used with `no_std`\n"
+    );
+}
+
+#[test]
+fn checks_site_lists() {
+    let output = test_expand(quote! {
+        #[debug_try(site_lists, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("if :: debug_try :: __private :: site_enabled (\"<unknown>\" , 0u32 ,"));
+}
+
+#[test]
+fn bounds_generic_errors() {
+    let output = test_expand(quote! {
+        #[debug_try(bound = "Display")]
+        fn load<E: From<std::io::Error>>() -> Result<Vec<u8>, E> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output
+        .contains("fn load < E : From < std :: io :: Error > + :: core :: fmt :: Display > ()"));
+    assert!(!output.contains("where"));
+
+    // without bounds of its own, or when the error type is not a type parameter
+    let output = test_expand(quote! {
+        #[debug_try(bound = "Display")]
+        fn load<E>() -> Result<Vec<u8>, Wrapper<E>> where Wrapper<E>: From<std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output.contains("where Wrapper < E > : From < std :: io :: Error > , Wrapper < E > : :: core :: fmt :: Display"));
+
+    let output = test_expand(quote! {
+        #[debug_try(bound = "none")]
+        fn load<E: From<std::io::Error>>() -> Result<Vec<u8>, E> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: Printable (& __debug_try_err)"));
+    assert!(!output.contains("require_display"));
+
+    let output = test_expand(quote! {
+        #[debug_try(bound = "Display")]
+        fn load() -> std::io::Result<Vec<u8>> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `bound = \"Display\"` requires a return type like `Result<T, E>`\n"
+    );
+}
+
+#[test]
+fn wraps_ffi_functions() {
+    let output = test_expand(quote! {
+        #[debug_try(ffi = to_code)]
+        #[no_mangle]
+        pub extern "C" fn read_len(mut len: c_int) -> Result<c_int, std::io::Error> {
+            Ok(std::fs::read("a.txt")?.len() as c_int + len)
+        }
+    });
+    assert!(output.contains("pub extern \"C\" fn read_len (len : c_int) -> c_int {"));
+    assert!(output.contains(
+        "fn __debug_try_ffi (mut len : c_int) -> Result < c_int , std :: io :: Error > {"
+    ));
+    assert!(output.contains("match __debug_try_ffi (len) {"));
+    assert!(output.contains("\"Error returned by `read_len`\""));
+    assert!(output.contains(":: core :: result :: Result :: Err (__debug_try_err) => {"));
+    assert!(output.contains("to_code (__debug_try_err)"));
+
+    let output = test_expand(quote! {
+        #[debug_try(ffi = "abort")]
+        unsafe extern "C" fn read() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+    assert!(output.contains("match unsafe {\n        __debug_try_ffi ()\n    }\n"));
+    assert!(output.contains(":: std :: process :: abort ()"));
+
+    let output = test_expand(quote! {
+        #[debug_try(ffi = "abort")]
+        fn read() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `ffi` can only be used on `extern` functions\n"
+    );
+}
+
+#[test]
+fn prints_deltas() {
+    let output = test_expand(quote! {
+        #[debug_try(delta)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("let __debug_try_delta = :: debug_try :: __private :: delta () ;"));
+    assert!(
+        output.contains("\"Error propagated (<unknown>) +{:?}: {}{}\\n\" , __debug_try_delta ,")
+    );
+
+    let output = test_expand(quote! {
+        #[debug_try(delta, format = "json")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("\\\"delta\\\":\\\"{:?}\\\""));
+}
+
+#[test]
+fn selects_format_at_run_time() {
+    let output = test_expand(quote! {
+        #[debug_try(format = "auto")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output
+        .contains("if :: std :: io :: IsTerminal :: is_terminal (& :: std :: io :: stderr ())"));
+    assert!(output.contains(":: debug_try :: __private :: report (\"<unknown>\" , 0u32 , 0u32 ,"));
+    assert!(output.contains("{{\\\"file\\\":"));
+
+    let output = test_expand(quote! {
+        #[debug_try(format = "auto", no_std, handler = on_error)]
+        fn read() -> Result<Vec<u8>, Error> {
+            read_all()?
+        }
+    });
+    assert_eq!(output, "error: `format` can not be used with `no_std`\n");
+}
+
+#[test]
+fn prints_host_info() {
+    let output = test_expand(quote! {
+        #[debug_try(host_info)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("let __debug_try_host = :: debug_try :: __private :: host () ;"));
+    assert!(output.contains(
+        "\"Error propagated (<unknown>) [host={} pid={}]: {}{}\\n\" , __debug_try_host . 0 , __debug_try_host . 1 ,"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(host_info, format = "json")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("\\\"host\\\":\\\"{}\\\",\\\"pid\\\":{}"));
+    assert!(output.contains(":: debug_try :: __private :: Json (__debug_try_host . 0)"));
+}
+
+#[test]
+fn filters_modules() {
+    let output = test_expand(quote! {
+        #[debug_try(module_filter)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        "if :: debug_try :: __private :: module_enabled (:: core :: module_path ! ()) {"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(module_filter, no_std, handler = on_error)]
+        fn read() -> Result<Vec<u8>, Error> {
+            read_all()?
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `module_filter` can not be used with `no_std`\n"
+    );
+}
+
+#[test]
+fn renders_rich_reports() {
+    let output = test_expand(quote! {
+        #[debug_try::main(rich)]
+        fn main() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: reset_trail () ;"));
+    assert!(output.contains(
+        ":: debug_try :: __private :: report_rich (\"Error\" , :: core :: option :: Option :: None , & :: debug_try :: __private :: Sanitized (& __debug_try_err) , & __debug_try_chain) ;"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try::test(rich, color = "never")]
+        fn loads() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+    assert!(
+        output.contains(":: debug_try :: __private :: record_site (\"<unknown>\" , 0u32 , 0u32) ;")
+    );
+    assert!(output.contains(
+        ":: debug_try :: __private :: report_rich (\"Test `loads` failed\" , :: core :: option :: Option :: Some (false) ,"
+    ));
+}
+
+#[test]
+fn writes_crash_reports() {
+    let output = test_expand(quote! {
+        #[debug_try::main(crash_report)]
+        fn main() -> Result<(), std::io::Error> {
+            std::fs::read("a.txt")?;
+            Ok(())
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: enable_crash_reports () ;"));
+    assert!(output.contains(":: debug_try :: __private :: reset_trail () ;"));
+    assert!(output.contains(
+        "let __debug_try_crash = :: debug_try :: __private :: write_crash_report (:: core :: env ! (\"CARGO_PKG_NAME\") , :: core :: env ! (\"CARGO_PKG_VERSION\") ,"
+    ));
+    assert!(output.contains(":: debug_try :: __private :: report_crash (& __debug_try_crash) ;"));
+}
+
+#[test]
+fn passes_events_to_hooks() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "log", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+
+    assert!(output.contains(":: debug_try :: __private :: Event :: new (\"<unknown>\" , 0u32 , 0u32 , \"read\" , :: core :: module_path ! () , & __debug_try_err , :: core :: any :: type_name_of_val (& __debug_try_err) ,"));
+}
+
+#[test]
+fn instruments_boxed_futures() {
+    // as generated by `#[async_trait]` for an `async fn` in an `impl` block
+    let output = test_expand(quote! {
+        #[debug_try(bound = "Display", indent, type_names, nested, color = "never", on_panic = "skip")]
+        fn load<'life0, 'async_trait, E: From<std::io::Error>>(
+            &'life0 self,
+        ) -> ::core::pin::Pin<
+            Box<dyn ::core::future::Future<Output = Result<Vec<u8>, E>> + ::core::marker::Send + 'async_trait>,
+        > {
+            Box::pin(async move {
+                let __self = self;
+                let __ret: Result<Vec<u8>, E> = { Ok(std::fs::read("a.txt")?) };
+                #[allow(unreachable_code)]
+                __ret
+            })
+        }
+    });
+    assert!(output.contains("fn load < 'life0 , 'async_trait , E : From < std :: io :: Error > + :: core :: fmt :: Display > ("));
+    assert!(output.contains("Box :: pin (async move {\n        let __debug_try_depth = :: debug_try :: __private :: enter () ;"));
+    assert!(output.contains(":: core :: any :: type_name :: < E > ()"));
+
+    // other `async` blocks still convert errors to their own output type
+    let output = test_expand(quote! {
+        #[debug_try(type_names, nested, color = "never", on_panic = "skip")]
+        fn load() -> ::core::pin::Pin<Box<dyn ::core::future::Future<Output = Result<(), MyError>>>> {
+            let future = async move { Ok(std::fs::read("a.txt")?) };
+            Box::pin(future)
+        }
+    });
+    assert!(!output.contains("type_name :: < MyError >"));
+}
+
+#[test]
+fn instruments_spawned_tasks() {
+    let output = test_expand(quote! {
+        #[debug_try(nested, backend = "none", on_panic = "skip")]
+        fn serve(paths: Vec<String>) -> Result<(), std::io::Error> {
+            tokio::spawn(async move { std::fs::read("a.txt")?; Ok::<_, std::io::Error>(()) });
+            paths.into_iter().for_each(|path| -> () {
+                tokio::spawn(async move { std::fs::read(path)?; Ok::<_, std::io::Error>(()) });
+            });
+            fn spawn_one() -> tokio::task::JoinHandle<std::io::Result<()>> {
+                tokio::spawn(async { std::fs::read("b.txt")?; Ok(()) })
+            }
+            let first = |lines: &[&str]| -> Option<usize> { Some(lines.first()?.len()) };
+            Ok(())
+        }
+    });
+    assert_eq!(output.matches("Err (__debug_try_err)").count(), 3);
+    assert!(output.contains(" \"spawn_one\" ,"));
+    // the `?` operators of the closures themselves are left alone
+    assert!(output.contains("Some (lines . first () ? . len ())"));
+}
+
+#[test]
+fn instruments_yeets() {
+    let output = test_expand(quote! {
+        #[debug_try(color = "never", on_panic = "skip")]
+        fn check(len: usize) -> Result<usize, std::io::Error> {
+            if len > 10 {
+                do yeet std::fs::read("a.txt").map(|_| other_error())?;
+            }
+            let first = |items: &[u8]| -> Option<u8> { do yeet };
+            Ok(len)
+        }
+    });
+    assert!(output.contains("do yeet (match std :: fs :: read (\"a.txt\")"));
+    assert!(output.contains("-> Option < u8 > {\n        do yeet\n    }"));
+    assert!(!output.contains("Error returned"));
+
+    let output = test_expand(quote! {
+        #[debug_try(yeets, color = "never", on_panic = "skip")]
+        fn check(len: usize) -> Result<usize, std::io::Error> {
+            match len {
+                0 => do yeet other_error(),
+                len => Ok(len),
+            }
+        }
+    });
+    assert!(output.contains("0 => do yeet {\n            let __debug_try_err = other_error () ;"));
+    assert!(output.contains("\"Error returned (<unknown>): {}{}\\n\""));
+}
+
+#[test]
+fn instruments_modules() {
+    let output = test_expand(quote! {
+        #[debug_try]
+        #[cfg(test)]
+        mod tests {
+            #![allow(unused)]
+            fn helper() -> Result<u8, std::io::Error> {
+                Ok(std::fs::read("a.txt")?.len() as u8)
+            }
+            fn plain(data: &[u8]) -> Option<u8> {
+                Some(*data.first()?)
+            }
+            #[test]
+            fn reads() -> Result<(), std::io::Error> {
+                helper()?;
+                Ok(())
+            }
+            #[debug_try(nested)]
+            fn own() -> Result<(), std::io::Error> {
+                helper()?;
+                Ok(())
+            }
+            impl Reader {
+                fn read(&self) -> std::io::Result<u8> {
+                    helper()?;
+                    Ok(0)
+                }
+            }
+        }
+    });
+    assert!(output.starts_with("# [cfg (test)] mod tests {\n    # ! [allow (unused)]"));
+    assert_eq!(output.matches("Err (__debug_try_err) =>").count(), 3);
+    assert!(output.contains("Some (* data . first () ?)"));
+    assert!(output.contains("# [test] fn reads () -> Result < () , std :: io :: Error > {\n        fn __debug_try_test ()"));
+    assert!(output.contains("# [debug_try (nested)] fn own ()"));
+
+    let output = test_expand(quote! {
+        #[debug_try(tests = false)]
+        mod tests {
+            #[test]
+            fn reads() -> Result<(), std::io::Error> {
+                std::fs::read("a.txt")?;
+                Ok(())
+            }
+        }
+    });
+    assert!(!output.contains("__debug_try"));
+
+    let output = test_expand(quote! {
+        #[debug_try(ffi = to_code)]
+        mod tests {}
+    });
+    assert_eq!(output, "error: `ffi` can not be used on modules\n");
+}
+
+#[test]
+fn instruments_all_items() {
+    let output = test_expand(quote! {
+        debug_try::debug_try_all! {
+            #![debug_try(nested)]
+            use std::fs;
+            fn read() -> std::io::Result<usize> {
+                let len = || Ok::<_, std::io::Error>(fs::read("a.txt")?.len());
+                Ok(len()?)
+            }
+            mod parse {
+                fn port(value: &str) -> Result<u16, std::num::ParseIntError> {
+                    Ok(value.parse()?)
+                }
+            }
+        }
+    });
+    assert!(output.starts_with("use std :: fs ;\n"));
+    assert_eq!(output.matches("Err (__debug_try_err) =>").count(), 3);
+    assert!(output.contains("mod parse {\n    fn port ("));
+
+    let output = test_expand(quote! {
+        debug_try_all! {
+            #![allow(unused)]
+        }
+    });
+    assert_eq!(
+        output,
+        "error: only `#![debug_try(...)]` can be used as an inner attribute of `debug_try_all!`\n"
+    );
+}
+
+#[test]
+fn runs_functions_in_spans() {
+    let output = test_expand(quote! {
+        #[debug_try(span, span_fields(path), color = "never", on_panic = "skip")]
+        fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read(path)?)
+        }
+    });
+    assert!(output.contains(
+        "let __debug_try_span = :: tracing :: span ! (:: tracing :: Level :: ERROR , \"read\" , \"path\" = ? & (path) , error = :: tracing :: field :: Empty) . entered () ;"
+    ));
+    assert!(output.contains(
+        ":: tracing :: Span :: current () . record (\"error\" , :: tracing :: field :: display (& __debug_try_err)) ;"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(span, level = "info", color = "never", on_panic = "skip")]
+        async fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read(path)?)
+        }
+    });
+    assert!(output.contains(":: tracing :: Instrument :: instrument (async move {"));
+    assert!(
+        output.contains("let __debug_try_output : Result < Vec < u8 > , std :: io :: Error > = {")
+    );
+    assert!(output.contains(":: tracing :: Level :: INFO , \"read\" , error"));
+    assert!(!output.contains("entered"));
+}
+
+#[test]
+fn notes_stats() {
+    let input = |nested: bool| {
+        quote! {
+            #[debug_try(stats, nested = #nested)]
+            fn read() -> Result<u8, std::io::Error> {
+                let first = |data: &[u8]| -> Option<u8> { Some(*data.first()?) };
+                let len = || Ok(std::fs::read("a.txt")?.len());
+                fn inner() -> std::io::Result<Vec<u8>> {
+                    Ok(std::fs::read("b.txt")?)
+                }
+                dbg!(inner()?);
+                println!("{}", len()?);
+                Ok(first(&inner()?).unwrap_or(0))
+            }
+        }
+    };
+    let output = test_expand(input(false));
+    assert!(output.ends_with(
+        "note: `read`: instrumented 2 sites, 0 closures and 0 inner functions; left 4 `?` operators, 2 closures and 1 inner function as they are\n"
+    ));
+    let output = test_expand(input(true));
+    assert!(output.ends_with(
+        "note: `read`: instrumented 4 sites, 1 closure and 1 inner function; left 2 `?` operators, 1 closure and 0 inner functions as they are\n"
+    ));
+}
+
+#[test]
+fn redacts_messages() {
+    let output = test_expand(quote! {
+        #[debug_try(redact, color = "never", on_panic = "skip")]
+        fn fetch(url: &str) -> Result<String, Error> {
+            Ok(get(url)?)
+        }
+    });
+    assert!(!output.contains(":: debug_try :: __private :: report ("));
+    assert!(output.contains(
+        "let __debug_try_line = format ! (\"{}\\n\" , :: debug_try :: __private :: Redacted (format_args ! (\"Error propagated (<unknown>): {}{}\" , :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_verbose))) ;"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try::test(redact)]
+        fn fetches() -> Result<(), Error> {
+            get("https://example.com")?;
+            Ok(())
+        }
+    });
+    assert!(output.contains(
+        "eprintln ! (\"Test `fetches` failed: {}\" , & :: debug_try :: __private :: Sanitized (& :: debug_try :: __private :: Redacted (__debug_try_err))) ;"
+    ));
+    assert!(output.contains(":: debug_try :: __private :: redact (source)"));
+}
+
+#[test]
+fn truncates_errors() {
+    let output = test_expand(quote! {
+        #[debug_try(max_len = 1024, color = "never", on_panic = "skip")]
+        fn fetch(url: &str) -> Result<String, Error> {
+            Ok(get(url)?)
+        }
+    });
+    assert!(!output.contains(":: debug_try :: __private :: report ("));
+    assert!(output.contains(
+        "\"Error propagated (<unknown>): {}{}\\n\" , :: debug_try :: __private :: Sanitized (& :: debug_try :: __private :: Truncated (& __debug_try_err , 1024usize)) , __debug_try_verbose"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(max_len = 80, verbosity = "compact", backend = "tracing")]
+        fn fetch(url: &str) -> Result<String, Error> {
+            Ok(get(url)?)
+        }
+    });
+    assert!(output.contains(
+        "error = % :: debug_try :: __private :: Sanitized (& :: debug_try :: __private :: Truncated (& __debug_try_err , 80usize))"
+    ));
+}
+
+#[test]
+fn keeps_expression_in_match() {
+    let output = test_expand(quote! {
+        #[debug_try]
+        async fn lookup(cache: &Mutex<Cache>) -> Result<u8, Error> {
+            let value = cache.lock().unwrap().get(1)?;
+            yield_now().await;
+            Ok(value)
+        }
+    });
+    assert!(output.contains("match cache . lock () . unwrap () . get (1) {"));
+    assert!(output
+        .contains("Ok (__debug_try_ok) => :: core :: result :: Result :: Ok (__debug_try_ok) ,"));
+    assert!(!output.contains("map_err"));
+}
+
+#[test]
+fn sanitizes_errors() {
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn parse(input: &str) -> Result<u32, Error> {
+            Ok(input.parse()?)
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: report ("));
+
+    let output = test_expand(quote! {
+        #[debug_try(sanitize = false, on_panic = "skip")]
+        fn parse(input: &str) -> Result<u32, Error> {
+            Ok(input.parse()?)
+        }
+    });
+    assert!(!output.contains(":: debug_try :: __private :: report ("));
+    assert!(!output.contains("Sanitized"));
+
+    // JSON messages escape control characters themselves
+    let output = test_expand(quote! {
+        #[debug_try(format = "json", on_panic = "skip")]
+        fn parse(input: &str) -> Result<u32, Error> {
+            Ok(input.parse()?)
+        }
+    });
+    assert!(!output.contains("Sanitized"));
+
+    let output = test_expand(quote! {
+        #[debug_try::main]
+        fn main() -> Result<(), Error> {
+            parse("1")?;
+            Ok(())
+        }
+    });
+    assert!(output.contains(
+        ":: std :: string :: ToString :: to_string (& :: debug_try :: __private :: Sanitized (source))"
+    ));
+}
+
+#[test]
+fn labels_functions_with_names() {
+    let output = test_expand(quote! {
+        #[debug_try(name = "checkout-flow", color = "never", on_panic = "skip")]
+        fn charge(card: &Card) -> Result<(), Error> {
+            gateway().authorize(card)?;
+            Ok(())
+        }
+    });
+    assert!(!output.contains(":: debug_try :: __private :: report ("));
+    assert!(output.contains("\"Error propagated (<unknown>) [name=checkout-flow]: {}{}\\n\""));
+
+    let output = test_expand(quote! {
+        #[debug_try(name = "checkout-flow", format = "json")]
+        fn charge(card: &Card) -> Result<(), Error> {
+            gateway().authorize(card)?;
+            Ok(())
+        }
+    });
+    assert!(output.contains("\\\"fn\\\":\\\"charge\\\",\\\"name\\\":\\\"checkout-flow\\\""));
+
+    let output = test_expand(quote! {
+        #[debug_try(name = "checkout-flow", backend = "tracing")]
+        fn charge(card: &Card) -> Result<(), Error> {
+            gateway().authorize(card)?;
+            Ok(())
+        }
+    });
+    assert!(output.contains("function = \"charge\" , name = \"checkout-flow\""));
+}
+
+#[test]
+fn includes_build_info() {
+    std::env::set_var("DEBUG_TRY_TEST_BUILD", "1a2b3c4");
+    let output = test_expand(quote! {
+        #[debug_try(build_info = "DEBUG_TRY_TEST_BUILD", color = "never", on_panic = "skip")]
+        fn deploy() -> Result<(), Error> {
+            Ok(rollout()?)
+        }
+    });
+    assert!(!output.contains(":: debug_try :: __private :: report ("));
+    assert!(output.contains("\"Error propagated (<unknown>) [build=1a2b3c4]: {}{}\\n\""));
+
+    let output = test_expand(quote! {
+        #[debug_try(build_info = "DEBUG_TRY_TEST_BUILD", format = "logfmt")]
+        fn deploy() -> Result<(), Error> {
+            Ok(rollout()?)
+        }
+    });
+    assert!(output.contains(" fn=deploy build=1a2b3c4 "));
+
+    // without the variable, the messages are left as they are
+    let output = test_expand(quote! {
+        #[debug_try(build_info = "DEBUG_TRY_TEST_NO_BUILD")]
+        fn deploy() -> Result<(), Error> {
+            Ok(rollout()?)
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: report ("));
+
+    let output = test_expand(quote! {
+        #[debug_try(build_info = "")]
+        fn deploy() -> Result<(), Error> {
+            Ok(rollout()?)
+        }
+    });
+    assert_eq!(
+        output,
+        "error: Expected the name of an environment variable\n"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "opentelemetry"))]
+fn reports_disabled_opentelemetry_backend() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "opentelemetry")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: The \"opentelemetry\" backend requires the `opentelemetry` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "opentelemetry")]
+fn records_exceptions_on_opentelemetry_spans() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "opentelemetry", fields(order_id))]
+        fn checkout(order_id: u64) -> Result<(), Error> {
+            Ok(charge(order_id)?)
+        }
+    });
+    assert!(output.contains(":: opentelemetry :: trace :: get_active_span"));
+    assert!(output.contains("add_event (\"exception\""));
+    assert!(output.contains(
+        ":: opentelemetry :: KeyValue :: new (\"code.line.number\" , i64 :: from (0u32))"
+    ));
+    assert!(output.contains(
+        ":: opentelemetry :: KeyValue :: new (\"order_id\" , format ! (\"{:?}\" , & (order_id)))"
+    ));
+}
+
+#[test]
+#[cfg(not(feature = "usdt"))]
+fn reports_disabled_usdt_backend() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "usdt")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: The \"usdt\" backend requires the `usdt` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "usdt")]
+fn fires_usdt_probes() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "usdt")]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains(
+        ":: debug_try :: __private :: probe (\"<unknown>\" , 0u32 , 0u32 , \"load\" , & :: debug_try :: __private :: Sanitized (& __debug_try_err)) ;"
+    ));
+    assert!(!output.contains("eprintln"));
+}
+
+#[test]
+#[cfg(not(feature = "slog"))]
+fn reports_disabled_slog_backend() {
+    let output = test_expand(quote! {
+        #[debug_try(slog = self.log)]
+        fn f(&self) -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `slog` requires the `slog` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "slog")]
+fn logs_with_slog_logger() {
+    let output = test_expand(quote! {
+        #[debug_try(slog = self.log, fields(order_id))]
+        fn checkout(&self, order_id: u64) -> Result<(), Error> {
+            Ok(self.charge(order_id)?)
+        }
+    });
+    assert!(output.contains(":: slog :: error ! (self . log , \"Error propagated\" ;"));
+    assert!(output.contains("\"function\" => \"checkout\""));
+    assert!(output.contains("\"order_id\" => ? & (order_id)"));
+    assert!(!output.contains("__debug_try_message"));
+}
+
+#[test]
+fn emits_log_key_values() {
+    let output = test_expand(quote! {
+        #[debug_try(backend = "log", log_kv, fields(order_id))]
+        fn checkout(order_id: u64) -> Result<(), Error> {
+            Ok(charge(order_id)?)
+        }
+    });
+    assert!(output.contains(
+        ":: log :: error ! (\"error\" : % = :: debug_try :: __private :: Sanitized (& __debug_try_err) , \"error.chain\" : ? = __debug_try_chain , \"file\" = "
+    ));
+    assert!(output.contains("\"function\" = \"checkout\" , \"order_id\" : ? = & (order_id) ;"));
+}
+
+#[test]
+#[cfg(not(feature = "tokio"))]
+fn reports_disabled_task_ids() {
+    let output = test_expand(quote! {
+        #[debug_try(task_ids)]
+        async fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: `task_ids` requires the `tokio` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn includes_task_ids() {
+    let output = test_expand(quote! {
+        #[debug_try(task_ids)]
+        async fn fetch() -> Result<Vec<u8>, Error> {
+            Ok(download().await?)
+        }
+    });
+    assert!(output.contains("let __debug_try_task = :: debug_try :: __private :: task_id () ;"));
+    assert!(output.contains("[task={}]"));
+
+    let output = test_expand(quote! {
+        #[debug_try(task_ids, format = "json")]
+        async fn fetch() -> Result<Vec<u8>, Error> {
+            Ok(download().await?)
+        }
+    });
+    assert!(output.contains("\\\"task\\\":{}"));
+    assert!(output.contains("__debug_try_task . json ()"));
+}
+
+#[test]
+fn includes_callers() {
+    let output = test_expand(quote! {
+        #[debug_try(callers)]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output
+        .contains("let __debug_try_frame = :: debug_try :: __private :: enter_frame (\"load\") ;"));
+    assert!(output
+        .contains("let __debug_try_callers = :: debug_try :: __private :: callers (\"load\") ;"));
+    assert!(output.contains("[callers={}]"));
+
+    // `async` functions do not enter the stack
+    let output = test_expand(quote! {
+        #[debug_try(callers, format = "json")]
+        async fn fetch() -> Result<Vec<u8>, Error> {
+            Ok(download().await?)
+        }
+    });
+    assert!(!output.contains("enter_frame"));
+    assert!(output.contains("\\\"callers\\\":{}"));
+    assert!(output.contains("__debug_try_callers . json ()"));
+}
+
+#[test]
+fn includes_context() {
+    let output = test_expand(quote! {
+        #[debug_try(context)]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains("let __debug_try_context = :: debug_try :: __private :: context () ;"));
+    assert!(output.contains("[context={}]"));
+
+    let output = test_expand(quote! {
+        #[debug_try(context, format = "logfmt")]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains("context=\\\"{}\\\""));
+    assert!(output.contains(":: debug_try :: __private :: Json (& __debug_try_context)"));
+}
+
+#[test]
+fn counts_propagations_per_site() {
+    let output = test_expand(quote! {
+        #[debug_try(counts, color = "never", on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("static __DEBUG_TRY_HITS : :: core :: sync :: atomic :: AtomicUsize"));
+    assert!(output.contains("(seen {}\\u{d7})"));
+    assert!(!output.contains("[site="));
+
+    let output = test_expand(quote! {
+        #[debug_try(counts, format = "json")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("\\\"seen\\\":{}"));
+
+    // the static of the site does not shadow an item of the function with the same name
+    let output = test_expand(quote! {
+        #[debug_try(counts, fields(HITS))]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            const HITS: u32 = 7;
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("const HITS : u32 = 7 ;"));
+    assert!(!output.contains("static HITS"));
+    assert!(output.contains("& (HITS)"));
+}
+
+#[test]
+fn pauses_on_errors() {
+    let output = test_expand(quote! {
+        #[debug_try(pause_on_error = 30, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(
+        ":: debug_try :: __private :: pause (:: debug_try :: __private :: Pause :: Seconds (30u64) , :: core :: option :: Option :: None ,"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(pause_on_error = "signal", pause_per_site, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: Pause :: Signal"));
+    assert!(output.contains("static __DEBUG_TRY_PAUSED : :: core :: sync :: atomic :: AtomicBool"));
+    assert!(output.contains("& __DEBUG_TRY_PAUSED"));
+
+    // the static of the site does not shadow an item of the function with the same name
+    let output = test_expand(quote! {
+        #[debug_try(pause_on_error = "signal", pause_per_site, fields(PAUSED))]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            const PAUSED: bool = false;
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("const PAUSED : bool = false ;"));
+    assert!(!output.contains("static PAUSED"));
+    assert!(output.contains("& (PAUSED)"));
+}
+
+#[test]
+fn groups_sites_by_subsystem() {
+    let output = test_expand(quote! {
+        #[debug_try(group = "storage", summary = 100, on_panic = "skip")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains("[group=storage]: {}{}"));
+    assert!(output.contains(
+        "match :: debug_try :: __private :: group_summary (\"storage\") . record (100u64 , 0u64) {"
+    ));
+    assert!(output.contains("times in {:.1?} [group=storage] ("));
+    assert!(!output.contains("static __DEBUG_TRY_SUMMARY"));
+
+    let output = test_expand(quote! {
+        #[debug_try(group = "storage", format = "logfmt")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            std::fs::read("a.txt")?
+        }
+    });
+    assert!(output.contains(" group=storage "));
+}
+
+#[test]
+fn warns_about_arguments_without_effect() {
+    let output = test_expand(quote! {
+        #[debug_try(nested)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(Vec::new())
+        }
+    });
+    assert!(output.ends_with(
+        "warning: `#[debug_try]` has no effect on `read`, which has no `?` operators that are \
+         instrumented\n\
+         warning: `nested` has no effect on `read`, which has no closures or inner items\n"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(nested, once, sample = 10)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let read = || std::fs::read("a.txt");
+            Ok(read()?)
+        }
+    });
+    assert!(output.ends_with(
+        "warning: `sample` has no effect with `once`: `once` only reports the first error of \
+         each site, which is always sampled\n"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(nested)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let data = tokio::task::block_in_place(|| std::fs::read("a.txt"));
+            Ok(data?)
+        }
+    });
+    assert!(!output.contains("warning: "));
+}
+
+#[test]
+fn warns_about_functions_without_instrumented_operators() {
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            let read = || -> Result<Vec<u8>, std::io::Error> { Ok(std::fs::read("a.txt")?) };
+            read()
+        }
+    });
+    assert!(output.ends_with(
+        "warning: `#[debug_try]` has no effect on `read`, which has no `?` operators that are \
+         instrumented\n"
+    ));
+
+    // the closures of combinators are instrumented without `nested`
+    let output = test_expand(quote! {
+        #[debug_try]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(Some("a.txt").map(|path| std::fs::read(path)).transpose()?.unwrap_or_default())
+        }
+    });
+    assert!(!output.contains("warning: "));
+
+    let output = test_expand(quote! {
+        #[debug_try(once, sample = 10)]
+        mod io {
+            fn empty() -> Result<Vec<u8>, std::io::Error> {
+                Ok(Vec::new())
+            }
+            fn read() -> Result<Vec<u8>, std::io::Error> {
+                Ok(std::fs::read("a.txt")?)
+            }
+        }
+    });
+    assert!(output.ends_with(
+        "warning: `#[debug_try]` has no effect on `empty`, which has no `?` operators that are \
+         instrumented\n\
+         warning: `sample` has no effect with `once`: `once` only reports the first error of \
+         each site, which is always sampled\n"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(ffi = "abort", nested)]
+        extern "C" fn read() -> Result<(), std::io::Error> {
+            Ok(())
+        }
+    });
+    assert!(output.ends_with(
+        "warning: `nested` has no effect on `read`, which has no closures or inner items\n"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(nested, lints = false)]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(Vec::new())
+        }
+    });
+    assert!(!output.contains("warning: "));
+}
+
+#[test]
+fn writes_to_journald() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "journald", fields(cfg.path()), level = "warn")]
+        fn load(cfg: &Config) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(cfg.path())?)
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: journal (4u8 , format_args !"));
+    assert!(output.contains("(\"CODE_FUNC\" , & \"load\")"));
+    assert!(output.contains("(\"CFG_PATH__\" , & format_args ! (\"{:?}\" , & (cfg . path ())))"));
+    assert!(output.contains("# [cfg (not (target_os = \"linux\"))]"));
+}
+
+#[test]
+fn prints_pretty_debug_representations() {
+    let output = test_expand(quote! {
+        #[debug_try(debug_repr, pretty, color = "never")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output.contains(
+        "format_args ! (\"Error propagated (<unknown>): {:#}\\n    Debug: {:#?}{}\\n\" , \
+         :: debug_try :: __private :: Sanitized (& __debug_try_err) , __debug_try_err ,"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(debug_repr, pretty, format = "json")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output.contains(
+        ":: debug_try :: __private :: Json (format_args ! (\"{:#?}\" , __debug_try_err))"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(debug_repr, pretty, backend = "tracing")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output.contains(
+        "error . debug = % :: debug_try :: __private :: PrettyDebug (& __debug_try_err)"
+    ));
+
+    let output = test_expand(quote! {
+        #[debug_try(debug_repr, backend = "tracing")]
+        fn read() -> Result<Vec<u8>, std::io::Error> {
+            Ok(std::fs::read("a.txt")?)
+        }
+    });
+    assert!(output.contains("error . debug = ? __debug_try_err"));
+}
+
+#[test]
+fn aggregates_messages() {
+    let output = test_expand(quote! {
+        #[debug_try(aggregate_ms = 100)]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains(
+        "static __DEBUG_TRY_PENDING : :: debug_try :: __private :: Aggregated = :: debug_try :: \
+         __private :: Aggregated :: new () ;"
+    ));
+    assert!(output.contains(":: debug_try :: __private :: aggregate (& __DEBUG_TRY_PENDING ,"));
+    assert!(output.contains("100u64 , | __debug_try_message : :: std :: string :: String | {"));
+    assert!(!output.contains("thread :: spawn"));
+}
+
+#[test]
+fn switches_sites_with_the_environment() {
+    let output = test_expand(quote! {
+        #[debug_try(env_switch = "DEBUG_TRY")]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains(
+        "static __DEBUG_TRY_ENABLED : :: std :: sync :: OnceLock < bool > = :: std :: sync :: \
+         OnceLock :: new () ;"
+    ));
+    assert!(output.contains(":: std :: env :: var_os (\"DEBUG_TRY\")"));
+
+    // the static of the site does not shadow an item of the function with the same name
+    let output = test_expand(quote! {
+        #[debug_try(env_switch = "DEBUG_TRY", fields(ENABLED))]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            const ENABLED: bool = true;
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains("const ENABLED : bool = true ;"));
+    assert!(!output.contains("static ENABLED"));
+    assert!(output.contains("& (ENABLED)"));
+}
+
+#[test]
+fn switches_threads() {
+    let output = test_expand(quote! {
+        #[debug_try(thread_switch)]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains("if :: debug_try :: __private :: thread_enabled () {"));
+}
+
+#[test]
+fn writes_chrome_trace_events() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "chrome_trace", elapsed)]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(output.contains(
+        "let __debug_try_trace_span = :: debug_try :: __private :: trace_span (\"debug_try.trace.json\" , \"load\") ;"
+    ));
+    assert!(output.contains(
+        "if ! :: debug_try :: __private :: trace_event (\"debug_try.trace.json\" , \"Error propagated\" , format_args !"
+    ));
+    assert!(output
+        .contains(":: std :: option :: Option :: Some ((\"<unknown>\" , 0u32 , 0u32 , \"load\"))"));
+
+    // calls are only recorded with `elapsed`
+    let output = test_expand(quote! {
+        #[debug_try(sink = "chrome_trace", sink_path = "run.json")]
+        fn load(path: &Path) -> Result<String, std::io::Error> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+    });
+    assert!(!output.contains("trace_span"));
+    assert!(output.contains("trace_event (\"run.json\""));
+}
+
+#[test]
+#[cfg(not(feature = "etw"))]
+fn reports_disabled_etw_sink() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "etw")]
+        fn f() -> Result<(), ()> {
+            Ok(())
+        }
+    });
+    assert_eq!(
+        output,
+        "error: The \"etw\" sink requires the `etw` feature of `debug-try`\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "etw")]
+fn writes_etw_events() {
+    let output = test_expand(quote! {
+        #[debug_try(sink = "etw", fields(order_id), level = "warn", target = "shop")]
+        fn checkout(order_id: u64) -> Result<(), Error> {
+            Ok(charge(order_id)?)
+        }
+    });
+    assert!(output.contains(":: debug_try :: __private :: etw (3u8 , format_args !"));
+    assert!(output.contains(
+        ":: std :: option :: Option :: Some ((\"<unknown>\" , 0u32 , 0u32 , \"checkout\")) , \"shop\" , & [(\"order_id\" , & format_args ! (\"{:?}\" , & (order_id)))]"
+    ));
+    assert!(output.contains("# [cfg (not (windows))]"));
+}
@@ -0,0 +1,1148 @@
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// `debug_try` is a function attribute macro that will replace any occurence of the `?` try operator
+/// with code that prints to standard error whenever an error is propagated.
+///
+/// The macro works by replacing any occurence of `expr?` with
+/// ```ignore
+/// (match expr {
+///     Ok(value) => Ok(value),
+///     Err(err) => Err({
+///         /* Print error message and location to standard error */;
+///         err
+///     }),
+/// })?
+/// ```
+///
+/// Since the message is printed before the `?` operator converts the error with `From`, it shows
+/// the original error, with none of the details that the conversion may lose. The message is
+/// printed by a closure that is run out of line, in a `#[cold]` function that is never inlined, so
+/// that the code that reports errors does not bloat the function, and the path where no error
+/// occurs is the same as with a plain `?`. When the message has the default form, with none of the
+/// arguments that add to it, the closure only calls a function of the runtime that formats and
+/// prints it, so that each site adds little code.
+///
+/// Unlike `expr.map_err(|err| { /* ... */; err })?`, the `match` keeps the semantics of the plain
+/// `?`: the expression is the scrutinee, so its temporaries are dropped at the same point, and the
+/// value it produces is never moved into a closure, so a future that holds it is no less `Send`.
+///
+/// When an error is propagated, a message similar to this is printed:
+/// ```text
+/// Error propagated (file.rs:10:30): Some error message
+/// ```
+///
+/// If the application has installed a hook with [`set_hook`](../debug_try/fn.set_hook.html), the
+/// hook is called with the details of the error instead. The generated code refers to the
+/// `debug_try` crate, so the crate using the attribute must depend on it.
+///
+/// # Arguments
+///
+/// The macro can be used with or without arguments:
+/// ```ignore
+/// #[debug_try]
+/// #[debug_try(nested = false)]
+/// #[debug_try(nested = true, fields(user_id))]
+/// #[debug_try(nested, handler = my_crate::on_propagation, backend = log)]
+/// ```
+///
+/// A boolean argument given without a value, e.g. `nested`, is true. The arguments that take a
+/// path, e.g. `handler`, and the arguments that take one of a few choices, e.g. `backend`, accept
+/// it either as is or as a string literal, e.g. `backend = log` or `backend = "log"`.
+///
+/// The following arguments are supported:
+/// * `nested`: If true, the macro will transform closures and inner functions as well. By default,
+///   this is false. The `async` blocks of the function itself, like the bodies of tasks passed to
+///   `tokio::spawn`, are always transformed. With `nested`, so are those of closures and inner
+///   functions that do not return a `Result`, whose own `?` operators are left alone.
+/// * `combinators`: If false, the closures passed to `map_err`, `and_then`, `try_for_each`,
+///   `try_fold` and `ok_or_else` are only transformed with `nested`, like other closures.
+///   Otherwise, they are transformed even without `nested`, since they are part of the same flow
+///   of errors as the function, e.g. `items.try_for_each(|item| { store(item)?; Ok(()) })`. Like
+///   other closures, they are assumed to return a `Result`, unless they end with `Some(...)` or
+///   `None`, or their return type is not a `Result`. By default, this is true.
+/// * `nested_opts(...)`: Arguments that override the arguments above for closures and inner
+///   functions, e.g. `nested_opts(snippet = false, debug_repr = false)`, so that helpers can be
+///   reported with less detail than the annotated function itself.
+/// * `backend`: One of `"stderr"`, `"log"`, `"tracing"`, `"defmt"`, `"sentry"`, `"opentelemetry"`,
+///   `"usdt"`, `"wasm"` or `"none"`. With `"log"`, messages are emitted with `log::error!` from the
+///   [`log`](https://docs.rs/log) crate instead of being printed to standard error, so that they go
+///   through the logger installed by the application. With `"tracing"`, an error-level event is
+///   emitted with `tracing::event!` from the [`tracing`](https://docs.rs/tracing) crate, with the
+///   details as structured fields instead of a formatted message: `error` (the `Display` output),
+///   `error.chain` (the messages of its sources, if it is an error type), `file`, `line`, `column`
+///   and `function`, and `label`, `site`, `hits`, `host`, `pid`, `elapsed`, `duration`, `delta`,
+///   `error.debug`, `snippet`, `span_trace` and the `fields(...)` when enabled. With `"defmt"`,
+///   which requires the `defmt` feature of `debug-try`, messages are emitted with `defmt::error!`
+///   from the [`defmt`](https://docs.rs/defmt) crate, for embedded targets that have no standard
+///   error. The error and the fields are formatted with their `Display` and `Debug`
+///   implementations, and neither the hook installed with `set_hook` nor the `on_panic` argument
+///   have an effect. With `"sentry"`, which requires the `sentry` feature of `debug-try`, each
+///   message is sent to [Sentry](https://docs.rs/sentry) as an error-level event, with the `file`,
+///   `line`, `column`, `function`, `error.chain`, `label` and the `fields(...)` as extra data, and
+///   the function as the `debug_try.function` tag. With `"opentelemetry"`, which requires the
+///   `opentelemetry` feature of `debug-try`, each error is recorded as an `exception` event on the
+///   active span of [OpenTelemetry](https://docs.rs/opentelemetry), with the `exception.type`,
+///   `exception.message`, `code.file.path`, `code.line.number`, `code.column.number` and
+///   `code.function.name` attributes of the semantic conventions, the messages of its sources as
+///   `debug_try.error.chain`, and `debug_try.name`, `debug_try.label`, `debug_try.site` and the
+///   `fields(...)` when enabled, so that the locations of errors show up in the traces next to the
+///   spans. There is no event without an active span; to get a log record for each error instead,
+///   the `"log"` and `"tracing"` backends can be bridged to OpenTelemetry with
+///   [`opentelemetry-appender-log`](https://docs.rs/opentelemetry-appender-log) or
+///   [`opentelemetry-appender-tracing`](https://docs.rs/opentelemetry-appender-tracing). With
+///   `"usdt"`, which requires the `usdt` feature of `debug-try`, nothing is logged, and each error
+///   fires the `debug_try:propagated` static probe instead, with the file, line, column,
+///   function and error as its arguments, and the strings as pointers to NUL-terminated strings,
+///   so that errors can be traced in production with SystemTap, bpftrace or GDB, e.g. with
+///   `printf("%s:%d: %s\n", str(arg0), arg1, str(arg4))` in a bpftrace program for
+///   `usdt:./my-app:debug_try:propagated`. While no tracer is attached, the probe is a `nop` and
+///   the error is not even formatted. Messages that are not about a single site, like that of
+///   `summary`, fire the `debug_try:message` probe with the message. Probes are only emitted on
+///   Linux and Android, and do nothing elsewhere. With `"wasm"`, which requires the `wasm` feature
+///   of `debug-try`, messages are logged to the browser console with `console.error`, for
+///   `wasm32-unknown-unknown` where standard error goes nowhere.
+///   These require the crate using the attribute to depend on `log`, `tracing`, `defmt`, `sentry`,
+///   `opentelemetry` or [`web-sys`](https://docs.rs/web-sys) (with its `console` feature)
+///   respectively. With `"none"`, no messages are emitted, e.g. when only `metrics` are wanted. The
+///   `color` and `wasi_logging` arguments only apply to `"stderr"`. By default, this is `"stderr"`.
+/// * `level`: One of `"error"`, `"warn"`, `"info"`, `"debug"` or `"trace"`, the level that the
+///   backends other than `"stderr"` and the logger of `slog` emit messages at, and `wasi_logging`
+///   and the `"logcat"`, `"os_log"`, `"journald"` and `"etw"` sinks log them at, e.g. for errors
+///   that are expected and should not alert anyone. Sentry, the journal and ETW have no trace
+///   level, and the browser console is only told apart down to `console.debug`, so these use the
+///   debug level instead, and the events of `"opentelemetry"` have no level. The compact line of
+///   `repropagated = "compact"` is emitted at the debug level, or at the trace level with
+///   `level = "trace"`. By default, this is `"error"`.
+/// * `target`: The target of the events of the `"log"` and `"tracing"` backends, e.g.
+///   `target = "my_app::io"`, so that filters like `RUST_LOG` and subscribers for that target apply
+///   to them, and the context that `wasi_logging` logs with, which is also the tag of the
+///   `"logcat"` sink, the subsystem of the `"os_log"` sink, the identifier of the `"journald"` sink
+///   and the `target` field of the `"etw"` sink. By default, the target is the module path of the
+///   function, as with the macros of `log` and `tracing`, and the context is `"debug_try"`, while
+///   the journal identifies messages by the name of the program.
+/// * `log_kv`: If true, the messages of the `"log"` backend are just the headline, with the details
+///   of the site passed as key-values of the [`kv`](https://docs.rs/log/latest/log/kv/) API of
+///   `log` instead of being part of the message, so that structured log collectors can index them:
+///   the same keys as the fields of the `"tracing"` backend, `error`, `error.chain`, `file`,
+///   `line`, `column` and `function`, and `name`, `label`, `site`, `hits`, `host`, `pid`,
+///   `elapsed`, `duration`, `delta`, `error.debug`, `snippet` and the `fields(...)` when enabled.
+///   This requires the `kv` feature of `log`. By default, this is false.
+/// * `slog`: A [`slog`](https://docs.rs/slog) logger that the messages are logged with instead of
+///   the `backend`, which requires the `slog` feature of `debug-try`, e.g. `slog = self.log` for a
+///   `slog::Logger` that the type carries. The expression is evaluated where an error is reported,
+///   so it can refer to the parameters of the function, and it is not used for inner functions,
+///   which report to `backend` instead. Errors are logged with `slog::error!` (or the macro of
+///   `level`), with the details of the site as key-value pairs instead of a formatted message, like
+///   the fields of the `"tracing"` backend: `error`, `error.chain`, `file`, `line`, `column` and
+///   `function`, and `name`, `label`, `site`, `hits`, `host`, `pid`, `elapsed`, `duration`,
+///   `delta`, `error.debug`, `snippet` and the `fields(...)` when enabled. This requires the crate
+///   using the attribute to depend on `slog`.
+/// * `span_trace`: If true, the events of the `"tracing"` backend get a
+///   [`SpanTrace`](https://docs.rs/tracing-error/latest/tracing_error/struct.SpanTrace.html) of the
+///   spans entered at the site as the `span_trace` field, captured with `SpanTrace::capture` from
+///   the [`tracing-error`](https://docs.rs/tracing-error) crate, so that the context of the spans,
+///   like the ID of a request or the name of a task, is attached to each error. The spans are only
+///   recorded if the subscriber has the `ErrorLayer` of `tracing-error`. This requires the crate
+///   using the attribute to depend on `tracing-error`, and has no effect with other backends. By
+///   default, this is false.
+/// * `span`: If true, the function runs in a `tracing` span named after it, like with
+///   `#[tracing::instrument]`, at the level of the `level` argument and with the target of the
+///   `target` argument, and each site records the error in the `error` field of the span that is
+///   current when it is propagated, so that the errors show up in the existing spans of an
+///   application. The span is entered for the body of a plain function, and instruments the future
+///   of an `async fn` or of a boxed future, e.g. with `#[async_trait]`. This works with every
+///   backend, and requires the crate using the attribute to depend on `tracing`. By default, this
+///   is false.
+/// * `span_fields(...)`: Expressions that are recorded as fields of the span of `span`, e.g.
+///   `span_fields(user_id, path.display())`, evaluated when the function is called, and keyed like
+///   `fields(...)`. Inner functions leave them out.
+/// * `metrics`: If true, each propagated error increments the `debug_try_propagations_total`
+///   counter of the [`metrics`](https://docs.rs/metrics) crate, with the `file`, `line` and
+///   `function` of the site as labels, in addition to the message of the backend. This requires
+///   the crate using the attribute to depend on `metrics`. By default, this is false.
+/// * `sink`: One of `"stderr"`, `"stdout"`, `"file"`, `"capture"`, `"logcat"`, `"os_log"`,
+///   `"journald"`, `"etw"` or `"chrome_trace"`, where the `"stderr"` backend prints messages to.
+///   With `"stdout"`, messages are printed like with `println!`, and so are captured by test
+///   harnesses that only capture standard output. With `"file"`, messages are appended to a log
+///   file instead, which is opened when the first message is written. The path of the file is taken
+///   from the `DEBUG_TRY_FILE` environment variable at run time if it is set, and from `sink_path`
+///   otherwise. With `"capture"`, messages are appended to a buffer of the current thread instead,
+///   which tests can inspect with [`take_captured`](../debug_try/fn.take_captured.html). Each
+///   message is formatted first and written at once, so that messages of different threads do not
+///   interleave. Errors writing to standard error or standard output are ignored, so that a closed
+///   stream or a broken pipe does not make the program panic like `eprintln!` would, except in the
+///   unit tests of the crate, where messages are printed with the print macros that the test
+///   harness captures. With `"logcat"`, which requires the `android` feature of `debug-try`,
+///   messages are written to the Android log with `__android_log_write`, from the
+///   [`android_log-sys`](https://docs.rs/android_log-sys) crate, and with `"os_log"`, which
+///   requires the `apple` feature, to the unified log of Apple platforms with `os_log`, from the
+///   [`oslog`](https://docs.rs/oslog) crate, since the standard error of mobile apps goes nowhere
+///   that can be seen. These require the crate using the attribute to depend on the respective
+///   crate for those targets, and print to standard error on other targets. With `"journald"`,
+///   messages are written to the systemd journal with its native protocol, with the level as the
+///   `PRIORITY`, the location as `CODE_FILE`, `CODE_LINE`, `CODE_COLUMN` and `CODE_FUNC`, the
+///   `target` as the `SYSLOG_IDENTIFIER`, and each of the `fields(...)` as a field of its own,
+///   named like `ORDER_ID` for `order_id`, so that `journalctl` can filter by them and shows the
+///   location of each message. Messages are printed to standard error instead when there is no
+///   journal, e.g. on other targets than Linux. With `"etw"`, which requires the `etw` feature of
+///   `debug-try`, messages are written as `Error` events of the `debug_try` provider of Event
+///   Tracing for Windows, with the [`tracelogging`](https://docs.rs/tracelogging) crate, with the
+///   `message`, `file`, `line`, `column`, `function` and `target` as fields of the event, and the
+///   `fields(...)` as `name=value` pairs in a `fields` field, so that they can be captured with
+///   WPR, PerfView or `tracelog` (e.g. with `-guid *debug_try`) alongside other system traces.
+///   Messages are only formatted while a session listens to the provider, which is registered with
+///   the first message and stays registered, so this is meant for executables rather than DLLs that
+///   are unloaded. On other targets, messages are printed to standard error. With `"chrome_trace"`,
+///   messages are recorded as instant events, with the location of the site as their arguments, in
+///   a trace file of the [trace event
+///   format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU) that
+///   `chrome://tracing` and [Perfetto](https://ui.perfetto.dev) load, to see where errors happened
+///   on the timeline of a run. With `elapsed`, each call of the function is recorded as a span as
+///   well. The file is created when it is first used, replacing the trace of an earlier run, at the
+///   path taken from the `DEBUG_TRY_FILE` environment variable or `sink_path`, and all the
+///   functions of the process write to that one file. By default, this is `"stderr"`.
+/// * `sink_path`: The path of the log file of the `"file"` sink, or of the trace file of the
+///   `"chrome_trace"` sink, relative to the working directory of the program. By default, this is
+///   `"debug_try.log"`, or `"debug_try.trace.json"` for traces.
+/// * `lock_output`: If true, each error is reported under a lock that is shared by all the
+///   functions instrumented with `lock_output` in the process, from the hook installed with
+///   `set_hook` to the last line of the message, so that the reports of errors on different
+///   threads never mix, e.g. for snapshot tests over captured standard error that run with several
+///   test threads. A hook that reports errors itself does not wait for the lock it holds. This
+///   requires `std`. By default, this is false.
+/// * `format`: One of `"text"`, `"json"`, `"logfmt"`, `"rustc"` or `"auto"`. With `"json"`, each
+///   message is a JSON object on a single line, e.g.
+///   `{"file":"src/main.rs","line":10,"column":30,"fn":"main","error":"..."}`, with the label, site
+///   ID, elapsed time, duration, delta, fields and debug representation as further members when
+///   enabled, so that the output can be processed by tools like `jq`. With `"logfmt"`, each message
+///   is a line of `key=value` pairs, e.g.
+///   `level=error msg=propagated file=src/main.rs line=10 column=30 fn=main err="..."`, with the
+///   same further pairs, where the level is the one of the `level` argument, and values are quoted
+///   and escaped when needed. Fields are keyed by their expression, with characters other than
+///   letters, digits, `_`, `.` and `-` replaced by `_`. With `"rustc"`, messages look like the
+///   diagnostics of rustc, e.g. ``error: error propagated in `main`: ...`` followed by a line like
+///   `  --> src/main.rs:10:31`, where the column starts at 1, so that editors and terminals that
+///   recognize the diagnostics of Cargo, e.g. with the problem matchers of VS Code, link to the
+///   location. The level is `error`, `warning` or `note`, after the `level` argument, and the
+///   layout is always single-line. With `"auto"`, which requires `std`, messages are text when
+///   standard error is a terminal, and JSON otherwise, e.g. when it is redirected to a file or a
+///   pipe, or captured by systemd. JSON and logfmt messages are never colored, and the `"tracing"`
+///   backend ignores this argument. By default, this is `"text"`.
+/// * `print_macro`: The path of a macro to print messages with instead of `eprintln!`, as a
+///   string literal, e.g. `print_macro = "crate::serial_println"`. The macro is called like
+///   `eprintln!`, with a format string and its arguments. It takes precedence over `sink`, and
+///   messages are only colored with `color = "always"`.
+/// * `no_std`: If true, the generated code only uses `core`, so that the attribute can be used in
+///   `no_std` crates such as kernels and firmware. As there is no standard error, this requires
+///   `print_macro`, `handler` or the `"log"` or `"defmt"` backend. The arguments that need `std`
+///   (`verbosity = "full"`, `layout = "multiline"`, `format = "auto"`, `elapsed`, `indent`,
+///   `timing`, `delta`, `aggregate_ms`, `sink`, `sink_path`, `wasi_logging`, `metrics`,
+///   `env_switch` and the `"tracing"`, `"sentry"` and `"wasm"` backends) can not be used, and
+///   `on_panic` has no effect. By default, this is false.
+/// * `handler`: The path of a function to call instead of emitting a message, as a string
+///   literal, e.g. `handler = "my_crate::on_propagation"`. The function is called with the source
+///   file, line and column of the operator, and a reference to the error, and so can have a
+///   signature like `fn on_propagation<E: Display>(file: &str, line: u32, column: u32, err: &E)`.
+///   When set, the `backend`, `sink` and `aggregate_ms` arguments have no effect, and the hook
+///   installed with `set_hook` is not called.
+/// * `only = [...]`: A list of error types, e.g. `only = [io::Error, MyError]`. Messages are only
+///   emitted for errors of one of these types. Boxed errors, `anyhow::Error` and other types that
+///   can be borrowed as a `dyn Error` are downcast to the listed types, while other errors must be
+///   of one of the types themselves. The listed types must implement `Error`. Errors of a generic
+///   or non-`'static` type never match.
+/// * `filter`: The path of a predicate function, as a string literal, e.g.
+///   `filter = "my_crate::is_interesting"`. The function is called with the same arguments as a
+///   `handler`, and returns a `bool`: the message is only emitted if it returns true, e.g. to only
+///   report `NotFound` errors.
+/// * `map`: The path of a function, as a string literal, e.g. `map = "my_crate::enrich"`. After
+///   an error has been reported, it is passed to the function by value, and the error that the
+///   function returns is propagated instead, e.g. to convert, enrich or record it. The returned
+///   error is then converted with `From::from` by the `?` operator, as usual.
+/// * `rate_limit`: The maximum number of messages per site in a period, e.g. `rate_limit = "10/s"`
+///   or `rate_limit = "1/500ms"`, with the period in `ms`, `s`, `min` or `h`. Further messages
+///   of the site in the same period are dropped, so that a failing operation in a tight retry
+///   loop does not flood the output.
+/// * `sample`: A positive integer `N`, e.g. `sample = 100`, so that only the first of every `N`
+///   propagations of a site is reported, and the others are only counted. Each message tells how
+///   many were left out since the previous one, like `[sample=1/100 suppressed=99]`, or as the
+///   `sample` and `suppressed` members, pairs or fields of JSON, logfmt and `tracing` messages, so
+///   that failures that happen very often can still be seen without flooding the output.
+/// * `panic_on_error`: If true, the message is passed to `panic!` instead of being emitted, so
+///   that the first propagated error stops the thread right at the failing site, e.g. with
+///   `RUST_BACKTRACE=1` for a backtrace. The `backend`, `sink`, `handler` and `aggregate_ms`
+///   arguments then have no effect, but the filters still apply: errors that do not pass them are
+///   propagated as usual. While the thread is already panicking, `on_panic` applies instead.
+/// * `break_on_error`: If true, a breakpoint instruction is executed after the message is
+///   emitted, so that a debugger like `gdb` or `lldb` stops at the failing site while its state can
+///   still be inspected. Without a debugger attached, the process is usually terminated instead,
+///   e.g. with `SIGTRAP`. Only x86, x86-64 and AArch64 targets are supported; on others, the
+///   argument has no effect.
+/// * `pause_on_error`: Pauses the process after the message of the first error is emitted, so that
+///   a debugger can be attached, or the state in `/proc` inspected, while the failure is at hand.
+///   The message that comes first tells the ID of the process. The value is a number of seconds
+///   to sleep, like `pause_on_error = 30`, `"enter"` to wait for a line on standard input, or
+///   `"signal"` to stop the process with `SIGSTOP` until it gets `SIGCONT`, e.g. from
+///   `kill -CONT <pid>` or the debugger. Targets other than Linux, Android, the Apple platforms,
+///   the BSDs, Solaris and illumos wait for a line instead of a signal. By default, the process
+///   does not pause.
+/// * `pause_per_site`: If true, `pause_on_error` pauses at the first error of each site, rather
+///   than only at the first error of the process. By default, this is false.
+/// * `once`: If true, each site only emits a message the first time it propagates an error in the
+///   lifetime of the process, e.g. for operations that are retried many times.
+/// * `max_errors`: A number of errors, e.g. `max_errors = 100`. Once more errors than that have
+///   been propagated in the function (including its closures) in the lifetime of the process, the
+///   next propagation panics after the error has been reported, e.g. to stop a runaway retry loop
+///   in a soak test. Every propagation is counted, even those that are filtered out.
+/// * `retry`: A number of retries, e.g. `retry = 3`. When the expression of a `?` operator fails,
+///   it is evaluated again, up to this many times, and only the error of the last attempt is
+///   propagated. Each failed attempt before it is reported like a propagated error, as
+///   `Attempt failed` (or with `"kind":"retry"` in JSON messages), e.g. to debug a flaky
+///   integration environment. This is only sensible for idempotent expressions, and expressions
+///   that move a value can not be evaluated again. By default, errors are not retried.
+/// * `retry_delay_ms`: A number of milliseconds to wait before each retry of `retry`, with
+///   `std::thread::sleep`, which also blocks the thread in `async` functions. This requires
+///   `std`. By default, there is no delay.
+/// * `or_default`: If true, an experimental mode in which the `?` operators do not return errors:
+///   when the expression fails, the error is reported as `Error replaced with default` (or with
+///   `"kind":"default"` in JSON messages), and the operator evaluates to `Default::default()`
+///   instead, e.g. to see every failure of a prototype, or of a fault-injection experiment, while
+///   it keeps running. The `Ok` type of each expression must implement `Default`, and the error is
+///   not converted to the error type of the function, so the function only returns the errors that
+///   it builds itself. The arguments that apply to the propagated error, like `map` and `locate`,
+///   have no effect. By default, this is false.
+/// * `ffi`: For `extern` functions, like `extern "C" fn` functions exported to C, which are
+///   written as if they returned `Result<T, E>`: the function returns `T` to its caller instead,
+///   and its body moves to an inner function that is instrumented as usual. When the body returns
+///   an error, it is printed with its sources and the location of the last `?` operator that
+///   propagated it, like with `debug_try::main`, and then `"abort"` aborts the process, while the
+///   path of a function like `fn(E) -> T` returns the value that the function maps the error to,
+///   e.g. an error code. Every parameter must be bound to a name. This requires `std`.
+/// * `repropagated`: What to do with an error that is likely to have been reported already by an
+///   instrumented callee, so that an error is not reported in full by every frame it passes
+///   through. `"report"` (the default) reports it again, `"compact"` emits a short
+///   `Error re-propagated (...)` line instead, and `"quiet"` emits nothing, so that only the site
+///   where the error originated reports it. The compact line is only emitted by the `"stderr"`,
+///   `"log"`, `"tracing"` and `"wasm"` backends, and the latter three emit it at the debug level.
+///   The callee must use `"compact"` or `"quiet"` too. An error counts as reported if any error
+///   was reported on the same thread while the expression before the `?` was evaluated, so e.g.
+///   in `parse(read().ok())?`, an error from `parse` is mistaken for a re-propagated one if
+///   `read` reported an error.
+/// * `locate`: If true, the propagated errors are wrapped in a `debug_try::Located`, which holds
+///   the file, line and column of the `?` operator, so that the location travels with the error to
+///   wherever it is handled. The error type of the function must be convertible from the
+///   `Located`, e.g. `Located<E>` itself, `Box<dyn Error>` or `anyhow::Error`. An error that
+///   already is a `Located` is not wrapped again, and keeps the location where it was first
+///   propagated. The type of the error must be known at the `?` operator, so e.g. `text.parse()?`
+///   needs to be written as `text.parse::<i32>()?`. The message is still emitted, unless e.g.
+///   `backend = "none"` is set.
+/// * `eyre`: If true, the propagated errors are wrapped with `eyre::WrapErr::wrap_err_with`, with
+///   the location of the `?` operator and the name of the function as the message, so that an
+///   `eyre::Report` lists every site that propagated the error, e.g. in the sections rendered by
+///   `color-eyre`. The crate must depend on `eyre`, and the error type of the function must be
+///   `eyre::Report`, or convertible from it. The message is still emitted, unless e.g.
+///   `backend = "none"` is set.
+/// * `unwraps`: If true, `unwrap()` and `expect(...)` calls on a `Result` or an `Option` are
+///   instrumented too: before the call panics, a message like
+///   `Unwrap failed (file.rs:10:30): ParseIntError { kind: InvalidDigit }` is emitted, with the
+///   location of the call and the `Debug` representation of the error, or `None`. The panic
+///   itself is unchanged. Calls to methods of the same name on other types are left as they are,
+///   and so are calls on a reference to a `Result` or an `Option`. By default, this is false.
+/// * `discards`: If true, calls that throw away the error of a `Result` are instrumented too, so
+///   that errors that are swallowed rather than propagated are visible: `ok()`, `unwrap_or(...)`,
+///   `unwrap_or_default()`, and `map_err(...)` and `unwrap_or_else(...)` with a closure that
+///   ignores the error, like `|_| ...`, or with `drop`. When the `Result` is an error, a message
+///   like `Error discarded (file.rs:10:30): ParseIntError { kind: InvalidDigit }` is emitted, with
+///   the `Debug` representation of the error. Calls on an `Option`, on other types, or on a
+///   reference to a `Result` are left as they are. By default, this is false.
+/// * `ignored`: If true, `Result`s that are ignored are reported too: the value of a call, a method
+///   call or an `.await` that is bound with `let _ = ...` or used as a statement, like
+///   `fallible();`, is checked, and when it is an error, a message like
+///   `Error ignored (file.rs:10:5): ParseIntError { kind: InvalidDigit }` is emitted, with the
+///   `Debug` representation of the error. Other values are ignored as before. By default, this is
+///   false.
+/// * `yeets`: If true, the errors returned with `do yeet` on nightly toolchains are reported too,
+///   with a message like `Error returned (file.rs:10:9): ...` at the location of `do`. Either way,
+///   the `?` operators in the expression of a `do yeet` are instrumented. By default, this is
+///   false.
+/// * `env_switch`: The name of an environment variable that turns the messages off at run time
+///   when it is set to `0`, e.g. `env_switch = "DEBUG_TRY"`. The variable is read when an error is
+///   first propagated at a site, so that the same binary can be run with or without the output,
+///   and a disabled site only costs a single branch.
+/// * `site_lists`: If true, the sites can be turned off at run time with the `DEBUG_TRY_DENY`
+///   environment variable, or all sites but some with `DEBUG_TRY_ALLOW`, e.g. to silence a few
+///   noisy sites in production without a new build. Each is a list of entries separated by commas:
+///   the ID of a site (see `site_ids`), like `1a2b3c4d`, a file and a line, like
+///   `src/main.rs:42`, or a file, like `src/db.rs`, where the leading directories of the path can
+///   be left out. An entry like `@/etc/my_app/sites.txt` adds the entries of a file, one per line,
+///   where lines that start with `#` are comments. The variables are read when the first error is
+///   checked, and again as described in [Reloading at run time](#reloading-at-run-time). This
+///   requires `std`. By default, this is false.
+/// * `module_filter`: If true, the sites can be turned on and off by module at run time with the
+///   `DEBUG_TRY_FILTER` environment variable, like `RUST_LOG`, to mute noisy subsystems for a run.
+///   It is a list of directives separated by commas, like `my_app::db=on,my_app::http=off`, where
+///   a directive applies to a module and the modules inside it, the one with the longest path
+///   wins, and one without a path, like `off`, applies to all modules. A path without a state
+///   turns its modules on, and modules that no directive applies to are on. The variable is read
+///   when the first error is checked, and again as described in
+///   [Reloading at run time](#reloading-at-run-time). This requires `std`. By default, this is
+///   false.
+/// * `thread_switch`: If true, the sites can be turned on and off for each thread at run time with
+///   [`set_thread_enabled`](../debug_try/fn.set_thread_enabled.html), and for the threads that
+///   have not done so with [`set_threads_enabled`](../debug_try/fn.set_threads_enabled.html), e.g.
+///   to report errors only on the thread that handles a request that reproduces a bug. This
+///   requires `std`. By default, this is false.
+/// * `aggregate_ms`: A number of milliseconds. When set, the messages of the function are not
+///   emitted right away. Instead, all messages from the function (including its closures and
+///   `async` blocks) within this many milliseconds of the first one are emitted together, as a
+///   single event that lists each error. This keeps reports readable when several concurrent
+///   branches, e.g. of `try_join!`, fail at the same time. The messages are emitted from a
+///   background thread, which is shared by all functions, so this requires thread support. The
+///   messages of a window that has not ended when the process exits are lost, so the window
+///   should be short compared to how long the process runs.
+/// * `summary`: A number of errors, e.g. `summary = 1000`, or a period, e.g. `summary = "10s"`,
+///   with the period in `ms`, `s`, `min` or `h`. When set, only the first error of each site is
+///   reported as usual. The others are only counted, and a summary like
+///   `Error propagated 1203 times in 10.0s (src/main.rs:42:13), last: connection refused` is
+///   emitted with the backend after this many errors, or for the first error at least this long
+///   after the previous summary, so that a site that fails very often costs a few atomic
+///   operations per error rather than a message. Summaries are text messages in every `format`,
+///   and a `handler` or `panic_on_error = true` still gets every error. This requires `std`.
+/// * `verbosity`: One of `"compact"`, `"normal"` or `"full"`, a preset for the level of detail of
+///   the messages. With `"compact"`, messages give the location as `file:line`, and only the
+///   first line of the error. With `"full"`, text messages also list the sources of the error,
+///   one `Caused by: ...` line each, and end with a backtrace of the `?` operator, and `args` is
+///   true. The arguments given explicitly take precedence over the preset, e.g.
+///   `verbosity = "full", args = false`. By default, this is `"normal"`.
+/// * `layout`: One of `"line"` or `"multiline"`. With `"multiline"`, text messages start with a
+///   header line with the location, which is followed by the error, the sources of the error, the
+///   fields and the other details, each on indented lines of their own:
+///   ```text
+///   Error propagated (src/config.rs:12:30):
+///       failed to load the configuration
+///       Caused by:
+///           0: No such file or directory (os error 2)
+///       Fields:
+///           path = "config.toml"
+///   ```
+///   JSON messages and `defmt` messages always take a single line. By default, this is `"line"`.
+/// * `pretty`: If true, errors are printed with the alternate format (`{:#}`), which lets
+///   multi-line errors and error chains render across several lines. By default, this is false.
+/// * `wasi_logging`: A path (as a string) to the `wasi:logging/logging` bindings generated for
+///   your component, e.g. `wasi_logging = "bindings::wasi::logging::logging"`. When compiling for
+///   WASI preview 2 (`wasm32-wasip2`), messages are sent through the `log` function of that
+///   interface instead of standard error. On other targets, including WASI preview 1, messages
+///   are always printed to standard error.
+/// * `color`: One of `"always"`, `"auto"` or `"never"`. Controls whether messages are colored
+///   with ANSI escape codes. With `"auto"`, colors are used only when standard error is a terminal
+///   and the `NO_COLOR` environment variable is not set. By default, this is `"auto"`.
+/// * `hyperlinks`: If true, the location in colored messages is a terminal hyperlink (OSC 8) to
+///   the `file://` URL of the source file, so that it can be clicked in terminals that support
+///   them. It can also be a URL with `{path}`, `{line}` and `{column}` placeholders, to jump to the
+///   line in an editor, e.g. `"vscode://file{path}:{line}:{column}"`, where the path is absolute
+///   and the column starts at 1. Locations looked up at run time are not linked. By default, this
+///   is false.
+/// * `on_panic`: One of `"emergency"` or `"skip"`. Controls what happens when an error is
+///   propagated while the thread is panicking, e.g. inside a `Drop` implementation during
+///   unwinding. With `"emergency"`, the message is written to standard error without colors, and
+///   write failures are ignored instead of causing a double panic. With `"skip"`, no message is
+///   printed. By default, this is `"emergency"`.
+/// * `paths`: One of `"relative"`, `"absolute"`, `"crate"` or `"remapped"`. With `"relative"`, file
+///   paths in messages are relative to the workspace root (or the crate root, for crates outside of
+///   the workspace), unless they are remapped with `--remap-path-prefix`, e.g. for reproducible
+///   builds, in which case the remapped path is printed. With `"absolute"`, the full path of the
+///   source file on disk is printed, even if it is remapped. With `"crate"`, paths are relative to
+///   the root of the crate, like `src/lib.rs`, in a workspace as well as for remapped paths. With
+///   `"remapped"`, paths are printed as the compiler prints them, i.e. as passed to the compiler,
+///   or as remapped by `--remap-path-prefix`. By default, this is `"relative"`.
+/// * `location`: One of `"span"` or `"runtime"`. With `"span"`, the location of each `?` operator
+///   is determined when the function is compiled. With `"runtime"`, it is looked up with
+///   [`Location::caller`](core::panic::Location::caller) when an error is propagated, which
+///   gives the location of the macro invocation for functions that are generated by another
+///   macro. The paths are then as passed to the compiler, and `paths` has no effect. In a
+///   `#[track_caller]` function, the location is that of its caller. By default, the location is
+///   determined when the function is compiled where possible, as described under
+///   [Locations](#locations).
+/// * `site_ids`: If true, each message includes an identifier for the `?` operator and the number
+///   of times an error has been propagated there, like `[site=1a2b3c4d hits=3]`. The identifier is
+///   derived from the location and the expression, so it stays the same across builds, and is the
+///   one in the site map (see below). By default, this is false.
+/// * `counts`: If true, each message includes the number of times an error has been propagated
+///   at the site so far, like `(seen 17×)`, so that a message that repeats in a
+///   loop stands out from one that is printed once. The count is kept in a static atomic counter
+///   per site, which does not need `std`. By default, this is false.
+/// * `error_ids`: If true, each message includes an ID for the error, like `[error=42]`. An error
+///   gets a new ID at the first site that propagates it, and the sites in other functions that
+///   propagate it further print the same ID, so that the path of one error can be followed in
+///   interleaved logs. Like with `repropagated`, an error counts as the same if it was propagated
+///   by a callee while the expression of the `?` was evaluated, on the same thread. This requires
+///   `std`. By default, this is false.
+/// * `host_info`: If true, each message includes the name of the host and the ID of the process,
+///   like `[host=web-1 pid=4242]`, or as `host` and `pid` members or pairs in JSON and logfmt
+///   messages, so that the logs collected from many instances can be told apart. Both are looked
+///   up once, the name from the `HOSTNAME` or `COMPUTERNAME` environment variables, or on Linux
+///   from the kernel. This requires `std`. By default, this is false.
+/// * `task_ids`: If true, which requires the `tokio` feature of `debug-try`, each message includes
+///   the ID of the [Tokio](https://docs.rs/tokio) task that the error is propagated in, like
+///   `[task=17]`, or as a `task` member or pair in JSON and logfmt messages and a `task` field or
+///   tag with the other backends, so that the messages of many concurrent tasks can be grouped by
+///   task. Outside of a task, the ID is `none`, or `null` in JSON. By default, this is false.
+/// * `callers`: If true, each message includes the names of the one or two instrumented functions
+///   that called the function propagating the error, from the innermost, like
+///   `[callers=load_config < main]`, or as a `callers` member with an array or a pair in JSON and
+///   logfmt messages and a `callers` field with the other backends, so that it can be told which
+///   code path got there. The callers are found on a stack of the current thread that the
+///   functions instrumented with `callers` enter, so callers that are not instrumented with it are
+///   left out, which makes this most useful when it is set for the whole crate. `async` functions
+///   do not enter the stack, since other tasks run on their thread while they wait. If there are no
+///   such callers, they are `none`. This requires `std`. By default, this is false.
+/// * `context`: If true, each message includes the values of the context of the current thread,
+///   as set with [`set_context`](../debug_try/fn.set_context.html), like
+///   `[context=req_id=7f3a route=/users]`, or as a `context` member with an object of strings or a
+///   pair in JSON and logfmt messages and a `context` field with the other backends, so that the
+///   messages can be joined with the other logs of a request. If the context is empty, it is
+///   `none`. This requires `std`. By default, this is false.
+/// * `labels`: One of `"comments"` or `"none"`. With `"comments"`, each message is labeled with
+///   the comment directly above the statement containing the `?` operator, so that for example
+///   `// load user prefs` shows up as `[load user prefs]`. Both line comments and doc comments are
+///   used. By default, this is `"none"`.
+/// * `prefix`: A tag that starts every message from the function, e.g. `prefix = "payments"` for
+///   `[payments] Error propagated (...)`, so that the messages of one component of a larger program
+///   can be found with `grep`. JSON messages get it as the `prefix` member, `tracing` events as
+///   the `prefix` field, and Sentry events as the `debug_try.prefix` tag. It also applies to
+///   closures and inner functions. By default, there is no prefix.
+/// * `name`: A label for the function, e.g. `name = "checkout-flow"`, that its messages carry in
+///   addition to the name of the function, like `Error propagated (...) [name=checkout-flow]: ...`,
+///   so that the errors of many small helpers that take part in one logical operation can be
+///   grouped under one name. JSON messages and the entries of the site map get it as the `name`
+///   member, logfmt messages as the `name` pair, `tracing` events as the `name` field, and Sentry
+///   events as the `debug_try.name` tag. It also applies to closures and inner functions. By
+///   default, there is no name.
+/// * `group`: The logical subsystem of the function, e.g. `group = "storage"`, that its messages
+///   carry like `[group=storage]`, and that is a member, pair, field, tag or label of the same
+///   name like `name`, including of the `metrics` counter. With `summary`, the sites of all the
+///   functions of a group count their errors together, so that a summary tells how often the
+///   subsystem failed, as of the site of the last error, rather than how often each site did. By
+///   default, there is no group.
+/// * `package`: If true, each message includes the name and version of the package that contains
+///   the function, like `[my-crate v0.1.0]`, so that in a binary built from many crates, the
+///   component that an error came from can be told. These are taken from Cargo when the function
+///   is compiled. JSON messages get them as the `package` and `package_version` members,
+///   `tracing` events as the `package` and `package.version` fields, and Sentry events as the
+///   `debug_try.package` tag and the `package.version` extra. By default, this is false.
+/// * `build_info`: If true, each message includes what identifies the build, like
+///   `[build=1a2b3c4]`, so that in the logs of a deployment that runs several versions at once, an
+///   error can be traced to the exact build. This is the value of the first of the `GIT_HASH`,
+///   `VERGEN_GIT_SHA` and `VERGEN_GIT_DESCRIBE` environment variables that is set when the
+///   function is compiled, as with `cargo:rustc-env=GIT_HASH=...` from a build script, or the
+///   crate [`vergen`](https://docs.rs/vergen). The name of another variable can be given instead,
+///   e.g. `build_info = "CI_COMMIT_SHA"`. Messages are left as they are if the variable is not
+///   set. JSON messages get it as the `build` member, logfmt messages as the `build` pair,
+///   `tracing` events as the `build` field, and Sentry events as the `debug_try.build` tag. By
+///   default, this is false.
+/// * `fields(...)`: A list of expressions, e.g. `fields(user_id, cfg.path)`, that are appended to
+///   each message using their [`Debug`](std::fmt::Debug) representation. The expressions are only
+///   evaluated when an error is propagated, and may refer to any variable in scope, including one
+///   named `err`. The fields are not included in messages from inner functions.
+/// * `args`: If true, the parameters of the function are appended to each message like the
+///   `fields(...)`, e.g. `(user_id = 42, path = "a.txt")`, to show the inputs that led to the
+///   error, similar to `#[tracing::instrument]`. Like the fields, they are only formatted when an
+///   error is propagated, and so with their values at that point. `self`, and parameters that are
+///   bound to a pattern rather than a name, are not included. Inner functions include their own
+///   parameters, and closures include none. By default, this is false.
+/// * `skip(...)`: The parameters that `args` leaves out, e.g. `skip(conn, password)`, such as
+///   those that do not implement [`Debug`](std::fmt::Debug), that are moved before a `?` operator,
+///   or that should not be printed.
+/// * `debug_repr`: If true, the [`Debug`](std::fmt::Debug) representation of the error is printed
+///   on a second line, after the [`Display`](std::fmt::Display) representation. This requires the
+///   error type to implement `Debug`. With `pretty = true`, it is printed with `{:#?}`, also in the
+///   `error.debug` field of the structured backends. By default, this is false.
+/// * `io_details`: If true, when the error is a [`std::io::Error`], or one of its sources is, the
+///   [`ErrorKind`](std::io::ErrorKind) and the OS error code of that error follow the error, like
+///   `[kind=NotFound errno=2]`, or as `io_kind` and `errno` members or pairs in JSON and logfmt
+///   messages, to tell apart errors with the same message. Errors like `anyhow::Error` and
+///   `Box<dyn Error>` are looked into. This requires `std`. By default, this is false.
+/// * `formatters`: If true, the error is formatted with the formatter registered for its type
+///   with [`register_formatter`](../debug_try/fn.register_formatter.html) at run time, if there is
+///   one, rather than with its `Display` implementation, so that an application can show more of
+///   the errors of libraries like `sqlx` or `reqwest` than their messages do. Errors like
+///   `anyhow::Error` and `Box<dyn Error>` are matched by the type of the error that they hold. This
+///   requires `std`. By default, this is false.
+/// * `redact`: If true, each message is passed through the redactor installed with
+///   [`set_redactor`](../debug_try/fn.set_redactor.html) at run time before it is emitted, or
+///   through [`scrub`](../debug_try/fn.scrub.html), which hides the user and password of URLs, the
+///   values of query parameters like `token` and `api_key`, bearer tokens and user names in paths,
+///   so that secrets and personal data in the messages of errors do not end up in logs. This
+///   applies to every backend, to the `error` field of the `"tracing"` backend, and to the error
+///   returned by [`debug_try::main`](macro@main) and [`debug_try::test`](macro@test), but not to
+///   the error passed to the hook installed with `set_hook`, nor to the fields of the `"tracing"`
+///   and `"sentry"` backends other than the message and `error`. This requires `std`. By default,
+///   this is false.
+/// * `max_len`: A number of bytes, e.g. `max_len = 1024`. The error in each message is cut off
+///   after that many bytes of its `Display` output, at a character boundary, and followed by an
+///   ellipsis and the length of the whole output, like `… (52017 bytes)`, so that errors that
+///   include whole request bodies do not flood logs or the terminal. By default, errors are not
+///   truncated.
+/// * `sanitize`: If false, the control characters in the `Display` output of errors, and of their
+///   sources, are printed as they are. Otherwise, newlines are escaped as `\n`, and other control
+///   characters like the escape that starts ANSI sequences as `\u{1b}`, so that an error that
+///   includes untrusted input can neither forge lines of a log nor garble the terminal. This
+///   applies to text messages, to the `error` field of the `"tracing"` backend, and to the error
+///   returned by [`debug_try::main`](macro@main) and [`debug_try::test`](macro@test), but not to
+///   the error passed to the hook installed with `set_hook`. JSON and logfmt messages escape them
+///   anyway. By default, this is true.
+/// * `type_names`: If true, the name of the type of the error follows the error, along with the
+///   type that `?` converts it to, like `(std::io::error::Error -> my_crate::MyError)`, or as
+///   `error_type` and `target_type` members or pairs in JSON and logfmt messages, to see which
+///   `From` implementation is used. The converted type is only known when the return type of the
+///   function or closure names it, as in `Result<T, MyError>`, and not for aliases like
+///   `io::Result<T>`, or in `async` blocks. By default, this is false.
+/// * `bound`: How errors of a generic type are reported, for functions like
+///   `fn load<E: From<io::Error>>() -> Result<T, E>` where the type is not known to implement
+///   `Display`. With `"Display"`, a `Display` bound for the error type of the return type is added
+///   to the `where` clause of the function, which then also applies to its callers. With
+///   `"none"`, the error is formatted with `Display` only if its type is known to implement it,
+///   and otherwise the message shows the name of the type, like `<my_crate::MyError>`, along with
+///   the location. By default, every propagated error is required to implement `Display`.
+/// * `indent`: If true, text messages are indented by two spaces for every function instrumented
+///   with `indent` that is running on the current thread below the outermost one, so that the
+///   messages of an error that is propagated through nested calls, including the compact lines of
+///   `repropagated = "compact"`, show how deep each one was made. The depth is counted from
+///   function entry to return, so in `async` functions it also counts while they wait. By default,
+///   this is false.
+/// * `elapsed`: If true, each message includes how long the function had been running when the
+///   error was propagated, like `after 30.001s`. The time is measured from function entry, or
+///   from the first poll for `async` functions. With `sink = "chrome_trace"`, each call of the
+///   function is also recorded in the trace, from entry to return. By default, this is false.
+/// * `timing`: If true, each message includes how long the expression of the `?` operator took
+///   to evaluate before it failed, like `took 120.5ms`, e.g. to see the latency of a failed
+///   network call. The time is measured for every evaluation, but only formatted when an error is
+///   propagated. By default, this is false.
+/// * `delta`: If true, each message includes the time since the previous message of a function
+///   instrumented with `delta` on the current thread, like `+120.5ms`, to see whether errors come
+///   in bursts or at a steady rate. It is zero for the first message. This requires `std`. By
+///   default, this is false.
+/// * `cfg(...)`: A configuration predicate, e.g. `cfg(feature = "diagnostics")` or
+///   `cfg(all(unix, debug_assertions))`. The function is only instrumented when the predicate
+///   holds, and is left untouched otherwise, as if the attribute was not there. The predicate can
+///   also be given as a string, e.g. `cfg = "feature = \"diagnostics\""`.
+/// * `strip_release`: If true, the function is only instrumented in builds with debug assertions
+///   (`cfg(debug_assertions)`), so that release builds contain none of the instrumentation or its
+///   format strings. This can be combined with `cfg`, in which case both must hold. By default,
+///   this is false.
+/// * `enabled`: If false, the function is left exactly as it is, as if the attribute was not
+///   there, but the arguments are still checked. In `nested_opts(...)`, this leaves the closures
+///   and inner functions as they are. The `disabled` feature of `debug-try` does the same for
+///   every function in the build, so that the attribute can be left in shipped code and turned
+///   off for a whole build at no cost. By default, this is true.
+/// * `print_expansion`: If true, the function as generated by the macro is shown as a note when
+///   it is compiled, to inspect the instrumentation without `cargo expand`. By default, this is
+///   false.
+/// * `stats`: If true, a note like the one of `print_expansion` tells how many sites, closures and
+///   inner functions of the function were instrumented, and how many `?` operators, closures and
+///   inner functions were left as they are, e.g. to check what `nested` and `nested_opts(...)` do.
+///   The `?` operators of macros that are not substituted in count as left alone. By default, this
+///   is false.
+/// * `snippet`: If true, each message includes the source line of the `?` operator, with the
+///   failing expression underlined, similar to compiler diagnostics. The line is read from the
+///   source file when the macro is expanded. By default, this is false.
+/// * `rich`: If true, with [`debug_try::main`](macro@main) and [`debug_try::test`](macro@test),
+///   and with `ffi`, the error that the function returns is printed in sections, like with
+///   `color-eyre`: the error, its numbered sources below `Caused by:`, the sites that reported it
+///   on the way below `Propagated through:`, from the first one to the last `?` operator in the
+///   function, and the backtrace of that operator. The sites are those that emitted a message
+///   while the expression of that operator was evaluated. By default, this is false.
+/// * `crash_report`: If true, with [`debug_try::main`](macro@main), the error that `main` returns
+///   is also written to a crash report, a text file in the temporary directory like
+///   `/tmp/my-cli-crash-1700000000-1234.txt`, whose path is printed after the error, so that the
+///   users of a program can attach a single file to their bug reports. The report has the error
+///   and its sources, the sites that reported it on the way and the last `?` operator in `main`
+///   that propagated it, the backtrace of that operator, which is captured for the report even if
+///   backtraces are not enabled, and the package, its version and the system it ran on. This
+///   requires `std`. By default, this is false.
+/// * `tests`: If false, the `#[test]` functions of a module with the attribute are left as they
+///   are, see [On modules](#on-modules). It has no effect on functions. By default, this is true.
+/// * `lints`: If false, the attribute does not warn about arguments that have no effect, see
+///   [Warnings](#warnings), e.g. in the configuration file of a crate whose build output should
+///   stay quiet. By default, this is true.
+///
+/// # On modules
+///
+/// The attribute can be used on an inline module, like the `#[cfg(test)]` module of the unit
+/// tests of a crate, to instrument the functions in it, in its `impl` blocks and in its inner
+/// modules that return a `Result`, with the arguments of the attribute:
+///
+/// ```ignore
+/// #[debug_try]
+/// #[cfg(test)]
+/// mod tests {
+///     use super::*;
+///
+///     #[test]
+///     fn parses_config() -> Result<(), Error> {
+///         let config = parse(&fs::read_to_string("tests/config.toml")?)?;
+///         assert_eq!(config.name, "test");
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+/// The `#[test]` functions that return a `Result` are expanded like with
+/// [`debug_try::test`](macro@test), so that a failing test prints the trail of the error, unless
+/// `tests = false` is given. Functions that do not return a `Result`, `const` functions and items
+/// that have an attribute of this crate of their own, which takes precedence, are left as they
+/// are. The attribute can not be used with `ffi` on modules, nor on modules in files of their own,
+/// like `mod tests;`, as they reach procedural macros without their contents.
+///
+/// # Configuration file
+///
+/// Defaults for the arguments of every function in a crate can be set in a `debug_try.toml` file
+/// next to its `Cargo.toml`, so that they do not have to be repeated on every attribute:
+///
+/// ```toml
+/// backend = "log"
+/// format = "json"
+/// nested = true
+/// color = "never"
+/// ```
+///
+/// The file has a line like `key = value` for each argument, with the same keys and values as the
+/// attribute, except for those that take a list, like `fields(...)`. An argument given in the
+/// attribute, or in [`configure!`](configure), takes precedence over the file. Cargo rebuilds the
+/// crate when the file changes, but not when it is created, which takes a
+/// `cargo clean -p my-crate`.
+///
+/// # Warnings
+///
+/// The attribute on a function warns when it is compiled about arguments that have no effect on
+/// it: the attribute itself when the function has no `?` operators that are instrumented, e.g.
+/// only some in closures without `nested`, `nested` when it has no closures or inner items, and
+/// arguments that do nothing together with others, like `sample` or `rate_limit` with `once`,
+/// `counts` with `site_ids`, or `pause_per_site` without `pause_on_error`. The functions of a
+/// module with the attribute are checked as well, as are those with `debug_try::test` and
+/// `debug_try::main`, except for whether the attribute has any effect. Only the arguments of the
+/// attribute are checked, not the defaults of the crate.
+///
+/// With the `nightly` feature, the warnings are compiler warnings. On stable toolchains, they are
+/// printed like the notes of `stats`, which `#[allow(...)]` can not turn off, but `lints = false`
+/// can, on a function or for the whole crate in its configuration file or with `configure!`.
+///
+/// # Locations
+///
+/// The location in a message is the location of the `?` operator. When the function is generated
+/// by a declarative macro, and the operator is passed to the macro, this is where it is written.
+/// Operators that are written in the macro definition, or were created by other procedural
+/// macros, are reported at the location of the outermost macro invocation that produced them,
+/// which is in code that can be found. With the `nightly` feature, that location is determined
+/// when the function is compiled. Without it, it is looked up when an error is propagated, as with
+/// `location = "runtime"`, unless `location = "span"` is given, in which case the location in the
+/// macro definition is used.
+///
+/// Lines and columns start at 1, and columns count characters rather than bytes, like the
+/// locations in the diagnostics of rustc, so that `src/main.rs:10:31` points at the same place in
+/// an editor even when the line has multi-byte characters before the operator. This holds for
+/// every format and backend, the arguments of `handler` and `filter`, the fields of
+/// [`Event`](../debug_try/struct.Event.html), and the site map. Locations that are looked up when
+/// an error is propagated come from [`Location::caller`](core::panic::Location::caller), which
+/// counts wide characters like `日` as two columns, as a terminal displays them.
+///
+/// When several operators are chained in one expression, e.g. `a()?.b()?.c()?`, the message of
+/// each one also names its step of the chain, e.g. `` in `.b()?` ``, so that the steps can be told
+/// apart. The step is the `step` field of `tracing` events and Sentry events, and the `"step"`
+/// member of JSON messages.
+///
+/// # Site map
+///
+/// When the `DEBUG_TRY_SITE_MAP` environment variable is set at build time, to the path of a
+/// file, the macro appends an entry to that file for every site that it instruments, so that
+/// external tools can map the hits of the sites back to the code, or find error paths that are
+/// never taken. Each entry is a JSON object on a line of its own, like
+/// `{"file":"src/lib.rs","line":10,"column":30,"function":"read","site":"1a2b3c4d","kind":"try"}`,
+/// with the same location and identifier as the messages, and the label and the step of the site,
+/// if any. The kind is `"try"` for `?` operators, and `"unwrap"`, `"discard"` or `"ignore"` for the
+/// sites instrumented with `unwraps`, `discards` or `ignored`.
+///
+/// A relative path is relative to the directory that the compiler runs in, which for Cargo is the
+/// root of the workspace. Entries are only written when a function is expanded, which Cargo skips
+/// for crates that have not changed, so a complete map takes a clean build of the crate, like
+/// `cargo clean -p my-crate && cargo build`. A function that is expanded again appends its entries
+/// again, with the same identifiers.
+///
+/// # Verbosity at run time
+///
+/// The `DEBUG_TRY_VERBOSITY` environment variable, read when the first error is reported, changes
+/// how much the messages tell without a rebuild:
+///
+/// * `0`: No messages are emitted (unless they are panics, with `panic_on_error`).
+/// * `1`: The messages are emitted as the arguments say, which is also what happens when the
+///   variable is not set.
+/// * `2`: Text messages also list the messages of the sources of the error, each on a line of its
+///   own like `    Caused by: ...`.
+/// * `3`: Text messages also include a backtrace, captured when the error is reported.
+///
+/// The variable adds to the messages, so it does not take away what `verbosity = "full"` or
+/// `layout = "multiline"` include. It has no effect with `no_std`, and the `"tracing"`, `"defmt"`
+/// and `"none"` backends and a `handler` only honor `0`.
+///
+/// # Reloading at run time
+///
+/// The variables that are read at run time, `DEBUG_TRY_VERBOSITY`, `DEBUG_TRY_DENY`,
+/// `DEBUG_TRY_ALLOW` and `DEBUG_TRY_FILTER`, can also be set in a file named by the
+/// `DEBUG_TRY_CONFIG` environment variable, with a line like `DEBUG_TRY_VERBOSITY=2` for each,
+/// where lines that start with `#` are comments. The file takes precedence over the environment,
+/// and is read again when it changes, which is checked at most once a second when an error is
+/// reported, so that the messages of a long-running service can be turned up, or off, without a
+/// restart:
+///
+/// ```text
+/// $ echo DEBUG_TRY_VERBOSITY=3 > /etc/my_app/debug_try.env
+/// ```
+///
+/// The application can also have the configuration read again by calling
+/// [`reload_config`](../debug_try/fn.reload_config.html), or on Unix, when the process gets
+/// `SIGHUP`, by calling [`reload_on_sighup`](../debug_try/fn.reload_on_sighup.html) at startup.
+///
+/// # Streaming events
+///
+/// When the `DEBUG_TRY_SOCKET` environment variable is set at run time, to the path of a Unix
+/// domain socket, or on Windows of a named pipe, every propagated error is also sent there, so
+/// that an external viewer can follow the errors of a running process without its standard error.
+/// Each event is a JSON object with the fields of the [`Event`](../debug_try/struct.Event.html)
+/// passed to the hook, like
+/// `{"file":"src/lib.rs","line":10,"column":30,"function":"read","module_path":"app",...}`,
+/// preceded by its length in bytes as a 32-bit big-endian integer, where `message` is the message
+/// as emitted by the backend and `timestamp_ms` is the time since the Unix epoch. The viewer has
+/// to listen on the socket before the first error is propagated, and once a write fails, no more
+/// events are sent. Errors reported with a `handler` or `panic_on_error` are not sent.
+///
+/// # Combining with other attributes
+///
+/// The attribute can be combined with attributes that wrap the body of the function, in either
+/// order:
+///
+/// * `#[tokio::main]`, `#[tokio::test]` and similar attributes, which move the body to an `async`
+///   block.
+/// * `#[tracing::instrument]`, which moves the body to a closure that is called right away, when
+///   used with `err` or `ret`. Such closures are instrumented like the function itself, even
+///   without `nested`.
+/// * `#[async_recursion]`, and `#[async_trait]` on the `impl` block of an `async` method, which
+///   change the return type to a boxed future. The output of the future is taken as the return
+///   type, e.g. for `bound = "Display"`, and the `async` block that the body is moved to is
+///   instrumented like the function itself.
+///
+/// Below an attribute that changes the signature, the `?` operators are found in the body it
+/// generates, and the locations still point at the original source.
+///
+/// # In libraries
+///
+/// The attribute can be left in published code. The `disabled` feature of `debug-try` turns off
+/// the instrumentation of every function in the build, so that the attribute expands to the
+/// function exactly as it is written, and an application can enable it to ship a build without any
+/// instrumentation.
+///
+/// To let downstream users opt in instead, a library can forward a feature of its own and only
+/// instrument its functions when that feature is enabled, with `cfg(...)`:
+///
+/// ```toml
+/// [features]
+/// debug-try = []
+/// ```
+///
+/// ```ignore
+/// #[debug_try(cfg(feature = "debug-try"))]
+/// pub fn load(path: &Path) -> io::Result<Config> {
+///     // ...
+/// }
+/// ```
+///
+/// Without the feature, the function is left untouched, and the arguments are still checked.
+///
+/// # Limitations
+///
+/// * The macro can only transform functions that return `Result<T, E>` where `E` implements
+///   [`Display`](std::fmt::Display), unless a `handler` is used. An error type that does not is
+///   reported at the `?` operator that propagates it. Type aliases are recognized by their name
+///   ending in `Result`, e.g. `io::Result<T>` or `anyhow::Result<T>`. Using the attribute on a
+///   function that returns something that is clearly not a `Result`, e.g. `()`, an `Option`, a
+///   `bool`, a `String` or a reference, is an error, and nested functions and closures that do are
+///   left as they are.
+/// * The macro attribute can only be used on functions and inline modules, not closures. Since
+///   messages can not be printed while evaluating constants, using it on a `const fn` is an error,
+///   and nested `const` functions are left as they are.
+/// * The macro will only transform `?` try operators that occur in certain known macros:
+///   `println`, `eprintln`, `format`, `format_args`, `write`, `writeln`, `assert`, `assert_eq`,
+///   `assert_ne`, `debug_assert`, `debug_assert_eq`, `debug_assert_ne`, `panic`, `todo`,
+///   `unimplemented`, `vec` and `matches`, including when these are nested in each other, e.g.
+///   `println!("{}", format!("{}", value?))`. In `matches`, only the expression before the pattern
+///   is transformed. The `select`, `select_biased`, `join` and `try_join` macros of `tokio` and
+///   `futures` are recognized by their name, with or without a path, and in `select`, the futures,
+///   conditions and handlers of the branches are transformed.
+///
+/// # Example
+///
+/// ```
+/// use std::{error, fs, io, path};
+/// use debug_try::debug_try;
+/// # fn main() { my_func(); }
+///
+/// #[debug_try(nested = true)]
+/// fn my_func() -> Result<(), Box<dyn error::Error>> {
+///     fn file_size<P: AsRef<path::Path>>(file: P) -> Result<usize, io::Error> {
+///         let data = fs::read(file)?;
+///         Ok(data.len())
+///     }
+///
+///     println!("file size = {}", file_size("non_existing_file.txt")?);
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn debug_try(args: TokenStream, input: TokenStream) -> TokenStream {
+    let output = debug_try_core::expand(args.into(), input.clone().into());
+    output_or_errors(output, input)
+}
+
+/// `test` is an attribute macro for test functions that return a `Result`, which combines
+/// `#[test]` with [`#[debug_try]`](macro@debug_try).
+///
+/// The `?` operators in the test are instrumented like with `#[debug_try]`, and it takes the same
+/// arguments. If the test returns an error, the error and its sources are printed before the test
+/// fails, after the messages of the `?` operators that propagated it:
+/// ```text
+/// Error propagated (tests/config.rs:12:40): No such file or directory (os error 2)
+/// Test `loads_config` failed: failed to load the configuration
+///     Caused by: No such file or directory (os error 2)
+/// ```
+///
+/// The error type must implement [`Display`](std::fmt::Display).
+///
+/// # Example
+///
+/// ```
+/// # fn main() {}
+/// #[debug_try::test]
+/// fn reads_config() -> Result<(), std::io::Error> {
+///     let config = std::fs::read_to_string("Cargo.toml")?;
+///     assert!(config.contains("[package]"));
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, input: TokenStream) -> TokenStream {
+    let output = debug_try_core::expand_test(args.into(), input.clone().into());
+    output_or_errors(output, input)
+}
+
+/// `main` is an attribute macro for a `main` function that returns a `Result`, which instruments it
+/// like [`#[debug_try]`](macro@debug_try) and reports the error that it returns.
+///
+/// The `?` operators in `main` are instrumented like with `#[debug_try]`, and it takes the same
+/// arguments. If `main` returns an error, it is printed with its sources and the location of the
+/// last `?` operator in `main` that propagated it, instead of the `Debug` representation that is
+/// printed by default, and the process exits with a failure code:
+/// ```text
+/// Error propagated (src/main.rs:8:40): No such file or directory (os error 2)
+/// Error: failed to load the configuration
+///     Caused by: No such file or directory (os error 2)
+///     Propagated last at src/main.rs:8:40
+/// ```
+///
+/// With `rich`, the report also lists the sites it was propagated through:
+/// ```text
+/// Error: failed to load the configuration
+///
+/// Caused by:
+///    0: No such file or directory (os error 2)
+///
+/// Propagated through:
+///    0: src/config.rs:12:40
+///    1: src/main.rs:8:40
+/// ```
+///
+/// If backtraces are enabled with `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`, the backtrace of that
+/// `?` operator is printed as well. With `crash_report`, the report is also saved to a file for
+/// bug reports:
+/// ```text
+/// A crash report was saved to /tmp/app-crash-1700000000-1234.txt, please attach it to bug reports.
+/// ```
+/// The error type must implement
+/// [`Display`](std::fmt::Display), and `main` can not be `async`, but can be used below an
+/// attribute that runs it, e.g. `#[tokio::main]`.
+///
+/// # Example
+///
+/// ```no_run
+/// #[debug_try::main]
+/// fn main() -> Result<(), std::io::Error> {
+///     let config = std::fs::read_to_string("config.toml")?;
+///     println!("{}", config);
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, input: TokenStream) -> TokenStream {
+    let output = debug_try_core::expand_main(args.into(), input.clone().into());
+    output_or_errors(output, input)
+}
+
+/// `debug_try_all!` instruments every function in the items passed to it that returns a `Result`,
+/// like [`#[debug_try]`](macro@debug_try) on a module, so that a whole crate, or a large part of
+/// it, can be instrumented in one place, e.g. for a session of debugging with as much visibility
+/// as possible.
+///
+/// The arguments are given in a `#![debug_try(...)]` attribute at the start, and apply to every
+/// function. Functions in inner modules and `impl` blocks are instrumented as well, and `#[test]`
+/// functions that return a `Result` are expanded like with [`debug_try::test`](macro@test),
+/// unless `tests = false` is given. Functions that do not return a `Result`, `const` functions and
+/// items with an attribute of this crate of their own, which takes precedence, are left as they
+/// are.
+///
+/// Modules in files of their own, like `mod config;`, reach procedural macros without their
+/// contents, so they are not instrumented. To instrument the whole crate, the contents of each
+/// file can be wrapped in the macro as well, with the `#![...]` attributes of the file left
+/// outside of it, and the arguments given in a `debug_try.toml` file (see
+/// [Configuration file](macro@debug_try#configuration-file)) instead of being repeated in each
+/// file.
+///
+/// # Example
+///
+/// ```
+/// # fn main() { load().unwrap_err(); }
+/// debug_try::debug_try_all! {
+///     #![debug_try(nested)]
+///
+///     use std::{fs, io};
+///
+///     fn load() -> io::Result<String> {
+///         let config = fs::read_to_string("non_existing_file.txt")?;
+///         Ok(config.trim().to_string())
+///     }
+///
+///     mod parse {
+///         pub fn port(value: &str) -> Result<u16, std::num::ParseIntError> {
+///             Ok(value.trim().parse()?)
+///         }
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn debug_try_all(input: TokenStream) -> TokenStream {
+    let output = debug_try_core::expand_all(input.clone().into());
+    output_or_errors(output, without_inner_attrs(input))
+}
+
+/// `debug_try_item!` instruments the function passed to it like
+/// [`#[debug_try]`](macro@debug_try), for places where an attribute can not be used, e.g. for a
+/// function emitted by a `macro_rules!` macro, or an item inside another function-like macro.
+///
+/// The arguments are given in a `#![debug_try(...)]` attribute at the start, and the function has
+/// to return a `Result`, like with the attribute. Inline modules can be passed as well.
+///
+/// ```
+/// macro_rules! loader {
+///     ($name:ident, $path:expr) => {
+///         debug_try::debug_try_item! {
+///             #![debug_try(color = "never")]
+///             fn $name() -> std::io::Result<String> {
+///                 Ok(std::fs::read_to_string($path)?)
+///             }
+///         }
+///     };
+/// }
+///
+/// loader!(load_config, "non_existing_file.txt");
+/// # fn main() { load_config().unwrap_err(); }
+/// ```
+///
+/// Given a block instead, it instruments the `?` operators in it, which propagate their errors to
+/// the function around it, and whose messages name the function as `<block>`:
+///
+/// ```
+/// fn load() -> std::io::Result<String> {
+///     debug_try::debug_try_item! {{
+///         let config = std::fs::read_to_string("non_existing_file.txt")?;
+///         Ok(config.trim().to_string())
+///     }}
+/// }
+/// # fn main() { load().unwrap_err(); }
+/// ```
+///
+/// The block is entered like a function, so that `elapsed` measures it and `max_errors` counts its
+/// errors, but the arguments that need a signature, like `bound`, `args` or `span`, have no
+/// effect on it.
+#[proc_macro]
+pub fn debug_try_item(input: TokenStream) -> TokenStream {
+    let output = debug_try_core::expand_item(input.clone().into());
+    output_or_errors(output, without_inner_attrs(input))
+}
+
+/// `configure!` sets the defaults for the arguments of the functions of a crate, in the same way
+/// as the [configuration file](macro@debug_try#configuration-file), but in its source. It takes
+/// the same arguments as [`#[debug_try]`](macro@debug_try), and is written once in the crate
+/// root, where it expands to nothing:
+///
+/// ```
+/// debug_try::configure! {
+///     nested,
+///     color = "never",
+/// }
+///
+/// # fn main() { load().unwrap_err(); }
+/// #[debug_try::debug_try]
+/// fn load() -> std::io::Result<String> {
+///     Ok(std::fs::read_to_string("non_existing_file.txt")?)
+/// }
+/// ```
+///
+/// The arguments only apply to the attributes that the compiler expands after it, which are those
+/// that come after it in the crate root and in the modules declared after it, since the compiler
+/// then expands the macros in the order of the source. An attribute that is expanded before it,
+/// e.g. on a function above it, or in a module declared above it, is instrumented without its
+/// arguments, and without an error, so `configure!` should be the first item of the crate root.
+/// An argument given in the attribute takes precedence over `configure!`, which takes precedence
+/// over the configuration file.
+#[proc_macro]
+pub fn configure(input: TokenStream) -> TokenStream {
+    let output = debug_try_core::expand_configure(input.into());
+    output_or_errors(output, TokenStream::new())
+}
+
+/// Returns the items passed to `debug_try_all!` or `debug_try_item!` without the inner attributes
+/// at their start, which can not be emitted in their place.
+fn without_inner_attrs(input: TokenStream) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+    while let [TokenTree::Punct(pound), TokenTree::Punct(bang), TokenTree::Group(group), ..] =
+        tokens.as_slice()
+    {
+        if pound.as_char() != '#'
+            || bang.as_char() != '!'
+            || group.delimiter() != Delimiter::Bracket
+        {
+            break;
+        }
+        tokens.drain(..3);
+    }
+    tokens.into_iter().collect()
+}
+
+/// Returns the expanded function, or the function as it is, followed by the errors.
+fn output_or_errors<T: Into<TokenStream>>(
+    output: Result<T, Vec<debug_try_core::Diagnostic>>,
+    input: TokenStream,
+) -> TokenStream {
+    match output {
+        Ok(output) => output.into(),
+        Err(diags) => {
+            // keep the function as it is, so that the errors are not followed by others about it
+            // being missing
+            let mut output = input;
+            output.extend(
+                diags
+                    .iter()
+                    .map(|diag| TokenStream::from(diag.to_compile_error())),
+            );
+            output
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Middleware for web frameworks, which runs each request with [`with_context`] to set the values
+//! of its context, so that the errors propagated by the functions instrumented with `context`
+//! anywhere below a handler include them:
+//!
+//! * `req_id`: The value of the `x-request-id` header of the request, or else a number that
+//!   is unique in the process.
+//! * `method`: The method of the request, e.g. `GET`.
+//! * `route`: The route of the request, as matched by `actix-web`, e.g. `/users/{id}`, or else the
+//!   path of its URI, e.g. `/users/42`.
+
+use std::{format, string::String};
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "actix")]
+use core::future::{ready, Ready};
+
+use crate::{with_context, WithContext};
+
+/// Returns the ID of a request, from its `x-request-id` header if it has one.
+fn request_id(header: Option<&[u8]>) -> String {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    match header.and_then(|header| core::str::from_utf8(header).ok()) {
+        Some(id) => id.into(),
+        None => format!("{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+    }
+}
+
+/// A `tower` layer that sets the context of each request to its service, e.g. with `axum`:
+///
+/// ```ignore
+/// let app = Router::new()
+///     .route("/users/{id}", get(user))
+///     .layer(debug_try::middleware::ContextLayer);
+/// ```
+#[cfg(feature = "tower")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContextLayer;
+
+#[cfg(feature = "tower")]
+impl<S> tower_layer::Layer<S> for ContextLayer {
+    type Service = ContextService<S>;
+
+    fn layer(&self, inner: S) -> ContextService<S> {
+        ContextService { inner }
+    }
+}
+
+/// A `tower` service that sets the context of each request to the service it wraps, as created by
+/// [`ContextLayer`].
+#[cfg(feature = "tower")]
+#[derive(Clone, Debug)]
+pub struct ContextService<S> {
+    inner: S,
+}
+
+#[cfg(feature = "tower")]
+impl<S, B> tower_service::Service<http::Request<B>> for ContextService<S>
+where
+    S: tower_service::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = WithContext<S::Future>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> WithContext<S::Future> {
+        let header = request.headers().get("x-request-id");
+        let values = [
+            (
+                "req_id",
+                request_id(header.map(http::HeaderValue::as_bytes)),
+            ),
+            ("method", String::from(request.method().as_str())),
+            ("route", String::from(request.uri().path())),
+        ];
+        with_context(values, self.inner.call(request))
+    }
+}
+
+/// An `actix-web` middleware that sets the context of each request to the services it wraps:
+///
+/// ```ignore
+/// let app = App::new()
+///     .wrap(debug_try::middleware::ActixContext)
+///     .route("/users/{id}", web::get().to(user));
+/// ```
+#[cfg(feature = "actix")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ActixContext;
+
+#[cfg(feature = "actix")]
+impl<S, B> actix_web::dev::Transform<S, actix_web::dev::ServiceRequest> for ActixContext
+where
+    S: actix_web::dev::Service<
+        actix_web::dev::ServiceRequest,
+        Response = actix_web::dev::ServiceResponse<B>,
+        Error = actix_web::Error,
+    >,
+{
+    type Response = actix_web::dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ActixContextService<S>;
+    type InitError = ();
+    type Future = Ready<Result<ActixContextService<S>, ()>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ActixContextService { service }))
+    }
+}
+
+/// An `actix-web` service that sets the context of each request to the service it wraps, as
+/// created by [`ActixContext`].
+#[cfg(feature = "actix")]
+pub struct ActixContextService<S> {
+    service: S,
+}
+
+#[cfg(feature = "actix")]
+impl<S, B> actix_web::dev::Service<actix_web::dev::ServiceRequest> for ActixContextService<S>
+where
+    S: actix_web::dev::Service<
+        actix_web::dev::ServiceRequest,
+        Response = actix_web::dev::ServiceResponse<B>,
+        Error = actix_web::Error,
+    >,
+{
+    type Response = actix_web::dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = WithContext<S::Future>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, request: actix_web::dev::ServiceRequest) -> WithContext<S::Future> {
+        let header = request.headers().get("x-request-id");
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| String::from(request.path()));
+        let values = [
+            ("req_id", request_id(header.map(|header| header.as_bytes()))),
+            ("method", String::from(request.method().as_str())),
+            ("route", route),
+        ];
+        with_context(values, self.service.call(request))
+    }
+}
@@ -0,0 +1,20 @@
+fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+    debug_try::debug_try_item! {
+        #![debug_try(sink = "capture", color = "never", max_errors = 1, elapsed = true)]
+        {
+            let data = std::fs::read(path)?;
+            Ok(data)
+        }
+    }
+}
+
+#[test]
+fn enters_blocks() {
+    assert!(read("missing.txt").is_err());
+    let captured = debug_try::take_captured();
+    assert!(captured[0].contains("tests/blocks.rs:5:"), "{:?}", captured);
+    assert!(captured[0].contains(") after "), "{:?}", captured);
+
+    // the second error is more than `max_errors` allows
+    assert!(std::panic::catch_unwind(|| read("missing.txt")).is_err());
+}
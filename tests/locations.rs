@@ -0,0 +1,22 @@
+use debug_try::debug_try;
+
+#[debug_try(sink = "capture", color = "never")]
+fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+    Ok(std::fs::read(["日本", path][1])?)
+}
+
+#[test]
+fn counts_columns_in_characters() {
+    let line = include_str!("locations.rs")
+        .lines()
+        .position(|line| line.contains("\"日本\""))
+        .unwrap();
+    let source = include_str!("locations.rs").lines().nth(line).unwrap();
+    let column = source.chars().position(|ch| ch == '?').unwrap() + 1;
+    assert_ne!(column, source.find('?').unwrap() + 1);
+
+    assert!(read("missing.txt").is_err());
+    let captured = debug_try::take_captured();
+    let location = format!("tests/locations.rs:{}:{})", line + 1, column);
+    assert!(captured[0].contains(&location), "{:?}", captured);
+}
@@ -0,0 +1,29 @@
+use debug_try::debug_try;
+
+struct Handle(String);
+
+impl Handle {
+    fn unwrap(&self) -> &str {
+        &self.0
+    }
+}
+
+#[debug_try(unwraps = true, sink = "capture", color = "never")]
+fn length(handle: Handle, value: Option<usize>) -> Result<usize, std::io::Error> {
+    let name = handle.unwrap();
+    Ok(name.len() + handle.0.len() + value.unwrap())
+}
+
+#[test]
+fn borrows_receivers_of_other_types() {
+    assert_eq!(length(Handle("a".to_owned()), Some(1)).unwrap(), 3);
+    assert!(debug_try::take_captured().is_empty());
+
+    assert!(std::panic::catch_unwind(|| length(Handle("a".to_owned()), None)).is_err());
+    let captured = debug_try::take_captured();
+    assert!(
+        captured[0].starts_with("Unwrap failed (tests/unwraps.rs:14:"),
+        "{:?}",
+        captured
+    );
+}